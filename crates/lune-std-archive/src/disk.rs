@@ -0,0 +1,231 @@
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Component, Path, PathBuf};
+
+use filetime::FileTime;
+use mlua::prelude::*;
+
+use super::entry::{ArchiveEntry, ArchiveEntryKind};
+
+/**
+    Resolves the path of an archive entry inside of the given root directory.
+
+    # Errors
+
+    Errors if the entry path is absolute, or if it would escape the root directory.
+*/
+fn resolve_entry_path(root: &Path, entry: &ArchiveEntry) -> LuaResult<PathBuf> {
+    let relative = Path::new(&entry.path);
+    if !relative
+        .components()
+        .all(|component| matches!(component, Component::Normal(_)))
+    {
+        return Err(LuaError::RuntimeError(format!(
+            "Refusing to extract archive entry '{}' - path must be relative and stay inside the target directory",
+            entry.path
+        )));
+    }
+
+    // A symlink extracted earlier could point outside of the root, so we
+    // need to check where the closest existing ancestor really is, before
+    // creating any missing directories that the entry should be placed in
+    let path = root.join(relative);
+    let parent = path.parent().unwrap_or(root);
+    let existing = parent
+        .ancestors()
+        .find(|ancestor| fs::symlink_metadata(ancestor).is_ok())
+        .unwrap_or(root);
+    if !fs::canonicalize(existing)?.starts_with(root) {
+        return Err(LuaError::RuntimeError(format!(
+            "Refusing to extract archive entry '{}' - path resolves outside of the target directory",
+            entry.path
+        )));
+    }
+
+    fs::create_dir_all(parent)?;
+    Ok(path)
+}
+
+/**
+    Removes any existing symlink at the given path, so that we
+    never write through a symlink into some other location.
+*/
+fn remove_existing_symlink(path: &Path) -> LuaResult<()> {
+    match fs::symlink_metadata(path) {
+        Ok(meta) if meta.is_symlink() => Ok(fs::remove_file(path)?),
+        Ok(_) => Ok(()),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+#[cfg(unix)]
+fn set_mode(path: &Path, mode: u32) -> LuaResult<()> {
+    use std::os::unix::fs::PermissionsExt;
+    Ok(fs::set_permissions(path, fs::Permissions::from_mode(mode))?)
+}
+
+#[cfg(not(unix))]
+#[allow(clippy::unnecessary_wraps)]
+fn set_mode(_: &Path, _: u32) -> LuaResult<()> {
+    // Modes can not be represented on this platform, so we ignore them
+    Ok(())
+}
+
+#[cfg(unix)]
+fn get_mode(meta: &fs::Metadata) -> Option<u32> {
+    use std::os::unix::fs::PermissionsExt;
+    Some(meta.permissions().mode() & 0o7777)
+}
+
+#[cfg(not(unix))]
+fn get_mode(_: &fs::Metadata) -> Option<u32> {
+    None
+}
+
+fn create_symlink(target: &str, link: &Path) -> LuaResult<()> {
+    #[cfg(unix)]
+    {
+        Ok(std::os::unix::fs::symlink(target, link)?)
+    }
+    #[cfg(windows)]
+    {
+        // Windows needs to know if the target is a directory or not, resolved relative to the link
+        let resolved = link
+            .parent()
+            .map_or_else(|| PathBuf::from(target), |parent| parent.join(target));
+        if fs::metadata(resolved).is_ok_and(|meta| meta.is_dir()) {
+            Ok(std::os::windows::fs::symlink_dir(target, link)?)
+        } else {
+            Ok(std::os::windows::fs::symlink_file(target, link)?)
+        }
+    }
+}
+
+/**
+    Extracts the given entries into a directory, creating it if it does not exist.
+
+    File modes are only restored on unix platforms, and modification times of
+    directories are restored last, since writing files into them changes them.
+
+    # Errors
+
+    Errors if any entry would be extracted outside of the directory, or on any I/O error.
+*/
+pub fn extract(entries: Vec<ArchiveEntry>, dir: PathBuf) -> LuaResult<()> {
+    fs::create_dir_all(&dir)?;
+    let root = fs::canonicalize(&dir)?;
+
+    let mut dirs = Vec::new();
+    for entry in entries {
+        let path = resolve_entry_path(&root, &entry)?;
+        let mtime = entry
+            .modified_at
+            .map(|secs| FileTime::from_unix_time(secs, 0));
+
+        match entry.kind {
+            ArchiveEntryKind::Dir => {
+                remove_existing_symlink(&path)?;
+                fs::create_dir_all(&path)?;
+                dirs.push((path, entry.mode, mtime));
+            }
+            ArchiveEntryKind::Symlink => {
+                match fs::symlink_metadata(&path) {
+                    Ok(meta) if meta.is_dir() => fs::remove_dir_all(&path)?,
+                    Ok(_) => fs::remove_file(&path)?,
+                    Err(_) => {}
+                }
+                create_symlink(entry.link_target.as_deref().unwrap_or_default(), &path)?;
+                if let Some(mtime) = mtime {
+                    filetime::set_symlink_file_times(&path, mtime, mtime)?;
+                }
+            }
+            ArchiveEntryKind::File => {
+                remove_existing_symlink(&path)?;
+                fs::write(&path, entry.contents.unwrap_or_default())?;
+                if let Some(mode) = entry.mode {
+                    set_mode(&path, mode)?;
+                }
+                if let Some(mtime) = mtime {
+                    filetime::set_file_mtime(&path, mtime)?;
+                }
+            }
+        }
+    }
+
+    for (path, mode, mtime) in dirs.into_iter().rev() {
+        if let Some(mtime) = mtime {
+            filetime::set_file_mtime(&path, mtime)?;
+        }
+        if let Some(mode) = mode {
+            set_mode(&path, mode)?;
+        }
+    }
+
+    Ok(())
+}
+
+/**
+    Reads all files, directories and symlinks in the given directory
+    into archive entries, with paths relative to the directory.
+
+    Symlinks are never followed, and entries are sorted by path to
+    make sure that archives created from the same files are identical.
+
+    # Errors
+
+    Errors if the given path is not a directory, or on any I/O error.
+*/
+pub fn pack(dir: PathBuf) -> LuaResult<Vec<ArchiveEntry>> {
+    if !fs::metadata(&dir)?.is_dir() {
+        return Err(LuaError::RuntimeError(format!(
+            "Failed to pack archive - '{}' is not a directory",
+            dir.display()
+        )));
+    }
+
+    let mut entries = Vec::new();
+    let mut queue = vec![(dir, String::new())];
+    while let Some((current_dir, current_prefix)) = queue.pop() {
+        let mut children = fs::read_dir(&current_dir)?.collect::<Result<Vec<_>, _>>()?;
+        children.sort_by_key(fs::DirEntry::file_name);
+
+        let mut child_dirs = Vec::new();
+        for child in children {
+            let path = child.path();
+            let meta = fs::symlink_metadata(&path)?;
+            let name = child.file_name().to_string_lossy().into_owned();
+            let relative = if current_prefix.is_empty() {
+                name
+            } else {
+                format!("{current_prefix}/{name}")
+            };
+
+            let mut entry = ArchiveEntry {
+                path: relative.clone(),
+                mode: get_mode(&meta),
+                modified_at: Some(FileTime::from_last_modification_time(&meta).unix_seconds()),
+                ..ArchiveEntry::default()
+            };
+
+            if meta.is_symlink() {
+                entry.kind = ArchiveEntryKind::Symlink;
+                entry.link_target = Some(fs::read_link(&path)?.to_string_lossy().into_owned());
+            } else if meta.is_dir() {
+                entry.kind = ArchiveEntryKind::Dir;
+                child_dirs.push((path, relative));
+            } else {
+                let contents = fs::read(&path)?;
+                entry.size = contents.len() as u64;
+                entry.contents = Some(contents);
+            }
+
+            entries.push(entry);
+        }
+
+        queue.extend(child_dirs);
+    }
+
+    entries.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(entries)
+}
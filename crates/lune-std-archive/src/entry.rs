@@ -0,0 +1,181 @@
+use std::{fmt, str::FromStr};
+
+use bstr::BString;
+use mlua::prelude::*;
+
+use lune_std_datetime::DateTime;
+
+pub const DEFAULT_FILE_MODE: u32 = 0o644;
+pub const DEFAULT_DIR_MODE: u32 = 0o755;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ArchiveEntryKind {
+    #[default]
+    File,
+    Dir,
+    Symlink,
+}
+
+impl ArchiveEntryKind {
+    #[must_use]
+    pub fn all() -> &'static [Self] {
+        &[Self::File, Self::Dir, Self::Symlink]
+    }
+}
+
+impl fmt::Display for ArchiveEntryKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match *self {
+            Self::File => "file",
+            Self::Dir => "dir",
+            Self::Symlink => "symlink",
+        };
+        f.write_str(s)
+    }
+}
+
+impl FromStr for ArchiveEntryKind {
+    type Err = LuaError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s.trim().to_ascii_lowercase().as_str() {
+            "file" => Self::File,
+            "dir" => Self::Dir,
+            "symlink" => Self::Symlink,
+            _ => {
+                return Err(LuaError::RuntimeError(format!(
+                    "Invalid archive entry kind - got '{}', expected one of {}",
+                    s,
+                    ArchiveEntryKind::all()
+                        .iter()
+                        .map(|k| format!("'{k}'"))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                )));
+            }
+        })
+    }
+}
+
+/**
+    A single file, directory or symlink in an archive.
+
+    Paths always use forward slashes as separators, and never
+    contain a trailing slash, regardless of the archive format.
+*/
+#[derive(Debug, Clone, Default)]
+pub struct ArchiveEntry {
+    pub(crate) path: String,
+    pub(crate) kind: ArchiveEntryKind,
+    pub(crate) size: u64,
+    pub(crate) mode: Option<u32>,
+    pub(crate) modified_at: Option<i64>,
+    pub(crate) link_target: Option<String>,
+    pub(crate) contents: Option<Vec<u8>>,
+}
+
+impl ArchiveEntry {
+    #[must_use]
+    pub fn default_mode(&self) -> u32 {
+        match self.kind {
+            ArchiveEntryKind::Dir => DEFAULT_DIR_MODE,
+            ArchiveEntryKind::File | ArchiveEntryKind::Symlink => DEFAULT_FILE_MODE,
+        }
+    }
+
+    /**
+        Normalizes an entry path from an archive or from Lua, using forward
+        slashes as separators and removing any leading or trailing slashes.
+    */
+    #[must_use]
+    pub fn normalize_path(path: &str) -> String {
+        path.replace('\\', "/")
+            .split('/')
+            .filter(|part| !part.is_empty() && *part != ".")
+            .collect::<Vec<_>>()
+            .join("/")
+    }
+}
+
+impl IntoLua for ArchiveEntry {
+    fn into_lua(self, lua: &Lua) -> LuaResult<LuaValue> {
+        let modified_at = self
+            .modified_at
+            .and_then(|secs| DateTime::from_unix_timestamp_float(secs as f64).ok());
+
+        let tab = lua.create_table_with_capacity(0, 6)?;
+        tab.set("path", self.path)?;
+        tab.set("kind", self.kind.to_string())?;
+        tab.set("size", self.size)?;
+        tab.set("mode", self.mode)?;
+        tab.set("modifiedAt", modified_at)?;
+        tab.set("linkTarget", self.link_target)?;
+        tab.set_readonly(true);
+        Ok(LuaValue::Table(tab))
+    }
+}
+
+impl FromLua for ArchiveEntry {
+    fn from_lua(value: LuaValue, _: &Lua) -> LuaResult<Self> {
+        let LuaValue::Table(t) = value else {
+            return Err(LuaError::FromLuaConversionError {
+                from: value.type_name(),
+                to: "ArchiveEntry".to_string(),
+                message: Some(format!(
+                    "Invalid archive entry - expected table, got {}",
+                    value.type_name()
+                )),
+            });
+        };
+
+        let path: String = t.get("path")?;
+        let path = Self::normalize_path(&path);
+        if path.is_empty() {
+            return Err(LuaError::runtime(
+                "Invalid archive entry - path must not be empty",
+            ));
+        }
+
+        let kind = match t.get::<Option<String>>("kind")? {
+            Some(kind) => kind.parse()?,
+            None => ArchiveEntryKind::File,
+        };
+
+        let contents: Option<BString> = t.get("contents")?;
+        let link_target: Option<String> = t.get("linkTarget")?;
+        if kind == ArchiveEntryKind::Symlink && link_target.is_none() {
+            return Err(LuaError::RuntimeError(format!(
+                "Invalid archive entry '{path}' - symlinks must have a link target"
+            )));
+        }
+
+        let modified_at = match t.get::<LuaValue>("modifiedAt")? {
+            LuaValue::Nil => None,
+            LuaValue::Integer(i) => Some(i),
+            LuaValue::Number(n) => Some(n.floor() as i64),
+            LuaValue::UserData(ud) => {
+                Some(ud.borrow::<DateTime>()?.to_unix_timestamp_float().floor() as i64)
+            }
+            value => {
+                return Err(LuaError::RuntimeError(format!(
+                    "Invalid archive entry '{path}' - modifiedAt must be a DateTime or number, got {}",
+                    value.type_name()
+                )));
+            }
+        };
+
+        let contents = match kind {
+            ArchiveEntryKind::File => Some(contents.map(Vec::from).unwrap_or_default()),
+            ArchiveEntryKind::Dir | ArchiveEntryKind::Symlink => None,
+        };
+
+        Ok(Self {
+            size: contents.as_ref().map_or(0, |c| c.len() as u64),
+            path,
+            kind,
+            mode: t.get("mode")?,
+            modified_at,
+            link_target,
+            contents,
+        })
+    }
+}
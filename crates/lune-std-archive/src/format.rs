@@ -0,0 +1,119 @@
+use std::{fmt, str::FromStr};
+
+use mlua::prelude::*;
+
+use lune_std_serde::CompressDecompressFormat;
+
+const TAR_MAGIC_OFFSET: usize = 257;
+
+/**
+    An archive format supported by Lune.
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveFormat {
+    Zip,
+    Tar,
+    TarGz,
+}
+
+impl ArchiveFormat {
+    #[must_use]
+    pub fn all() -> &'static [Self] {
+        &[Self::Zip, Self::Tar, Self::TarGz]
+    }
+
+    /**
+        Detects a supported archive format from the given bytes.
+
+        Note that any gzip-compressed data is assumed to be a tar archive,
+        since we can not know what it contains without decompressing it.
+    */
+    pub fn detect_from_bytes(bytes: impl AsRef<[u8]>) -> Option<Self> {
+        match bytes.as_ref() {
+            // https://pkware.cachefly.net/webdocs/casestudies/APPNOTE.TXT - 4.3.7 and 4.3.16
+            b if b.starts_with(b"PK\x03\x04") || b.starts_with(b"PK\x05\x06") => Some(Self::Zip),
+            // https://www.gnu.org/software/tar/manual/html_node/Standard.html
+            b if b.len() > TAR_MAGIC_OFFSET + 5
+                && &b[TAR_MAGIC_OFFSET..TAR_MAGIC_OFFSET + 5] == b"ustar" =>
+            {
+                Some(Self::Tar)
+            }
+            b if matches!(
+                CompressDecompressFormat::detect_from_bytes(b),
+                Some(CompressDecompressFormat::GZip)
+            ) =>
+            {
+                Some(Self::TarGz)
+            }
+            _ => None,
+        }
+    }
+
+    /**
+        Gets the given format, or detects it from the given bytes if no format was given.
+
+        # Errors
+
+        Errors if no format was given and it could not be detected.
+    */
+    pub fn given_or_detect(format: Option<Self>, bytes: impl AsRef<[u8]>) -> LuaResult<Self> {
+        match format {
+            Some(format) => Ok(format),
+            None => Self::detect_from_bytes(bytes).ok_or_else(|| {
+                LuaError::runtime(
+                    "Failed to detect archive format - the format must be given explicitly",
+                )
+            }),
+        }
+    }
+}
+
+impl fmt::Display for ArchiveFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match *self {
+            Self::Zip => "zip",
+            Self::Tar => "tar",
+            Self::TarGz => "tar.gz",
+        };
+        f.write_str(s)
+    }
+}
+
+impl FromStr for ArchiveFormat {
+    type Err = LuaError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s.trim().to_ascii_lowercase().as_str() {
+            "zip" => Self::Zip,
+            "tar" => Self::Tar,
+            "tar.gz" | "tgz" => Self::TarGz,
+            _ => {
+                return Err(LuaError::RuntimeError(format!(
+                    "Invalid archive format - got '{}', expected one of {}",
+                    s,
+                    ArchiveFormat::all()
+                        .iter()
+                        .map(|f| format!("'{f}'"))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                )));
+            }
+        })
+    }
+}
+
+impl FromLua for ArchiveFormat {
+    fn from_lua(value: LuaValue, _: &Lua) -> LuaResult<Self> {
+        if let LuaValue::String(s) = value {
+            s.to_str()?.parse()
+        } else {
+            Err(LuaError::FromLuaConversionError {
+                from: value.type_name(),
+                to: "ArchiveFormat".to_string(),
+                message: Some(format!(
+                    "Invalid archive format - expected string, got {}",
+                    value.type_name()
+                )),
+            })
+        }
+    }
+}
@@ -0,0 +1,139 @@
+#![allow(clippy::cargo_common_metadata)]
+
+use bstr::BString;
+use mlua::prelude::*;
+
+use lune_std_serde::{CompressDecompressFormat, compress, decompress};
+use lune_utils::TableBuilder;
+
+mod disk;
+mod entry;
+mod format;
+mod tar;
+mod zip;
+
+pub use self::entry::{ArchiveEntry, ArchiveEntryKind};
+pub use self::format::ArchiveFormat;
+
+const TYPEDEFS: &str = include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/types.d.luau"));
+
+/**
+    Returns a string containing type definitions for the `archive` standard library.
+*/
+#[must_use]
+pub fn typedefs() -> String {
+    TYPEDEFS.to_string()
+}
+
+/**
+    Creates the `archive` standard library module.
+
+    # Errors
+
+    Errors when out of memory.
+*/
+pub fn module(lua: Lua) -> LuaResult<LuaTable> {
+    TableBuilder::new(lua)?
+        .with_async_function("list", archive_list)?
+        .with_async_function("read", archive_read)?
+        .with_async_function("extract", archive_extract)?
+        .with_async_function("create", archive_create)?
+        .with_async_function("pack", archive_pack)?
+        .build_readonly()
+}
+
+/**
+    Reads all entries from an archive in the given format,
+    optionally also reading the contents of all files.
+
+    # Errors
+
+    Errors if the archive is invalid or could not be decompressed.
+*/
+pub async fn read_entries(
+    bytes: Vec<u8>,
+    format: ArchiveFormat,
+    with_contents: bool,
+) -> LuaResult<Vec<ArchiveEntry>> {
+    match format {
+        ArchiveFormat::Zip => {
+            blocking::unblock(move || zip::read_entries(&bytes, with_contents)).await
+        }
+        ArchiveFormat::Tar => {
+            blocking::unblock(move || tar::read_entries(&bytes, with_contents)).await
+        }
+        ArchiveFormat::TarGz => {
+            let bytes = decompress(bytes, CompressDecompressFormat::GZip).await?;
+            blocking::unblock(move || tar::read_entries(&bytes, with_contents)).await
+        }
+    }
+}
+
+/**
+    Writes the given entries into a new archive in the given format.
+
+    # Errors
+
+    Errors if the archive could not be written or compressed.
+*/
+pub async fn write_entries(
+    entries: Vec<ArchiveEntry>,
+    format: ArchiveFormat,
+) -> LuaResult<Vec<u8>> {
+    match format {
+        ArchiveFormat::Zip => blocking::unblock(move || zip::write_entries(entries)).await,
+        ArchiveFormat::Tar => blocking::unblock(move || tar::write_entries(entries)).await,
+        ArchiveFormat::TarGz => {
+            let bytes = blocking::unblock(move || tar::write_entries(entries)).await?;
+            compress(bytes, CompressDecompressFormat::GZip, None).await
+        }
+    }
+}
+
+async fn archive_list(
+    _: Lua,
+    (bs, format): (BString, Option<ArchiveFormat>),
+) -> LuaResult<Vec<ArchiveEntry>> {
+    let format = ArchiveFormat::given_or_detect(format, &bs)?;
+    read_entries(bs.into(), format, false).await
+}
+
+async fn archive_read(
+    lua: Lua,
+    (bs, path, format): (BString, String, Option<ArchiveFormat>),
+) -> LuaResult<Option<LuaString>> {
+    let format = ArchiveFormat::given_or_detect(format, &bs)?;
+    let path = ArchiveEntry::normalize_path(&path);
+
+    // FUTURE: Stop reading the archive as soon as we find the entry
+    let entries = read_entries(bs.into(), format, true).await?;
+    entries
+        .into_iter()
+        .find(|entry| entry.kind == ArchiveEntryKind::File && entry.path == path)
+        .and_then(|entry| entry.contents)
+        .map(|contents| lua.create_string(contents))
+        .transpose()
+}
+
+async fn archive_extract(
+    _: Lua,
+    (bs, dir, format): (BString, String, Option<ArchiveFormat>),
+) -> LuaResult<()> {
+    let format = ArchiveFormat::given_or_detect(format, &bs)?;
+    let entries = read_entries(bs.into(), format, true).await?;
+    blocking::unblock(move || disk::extract(entries, dir.into())).await
+}
+
+async fn archive_create(
+    lua: Lua,
+    (entries, format): (Vec<ArchiveEntry>, ArchiveFormat),
+) -> LuaResult<LuaString> {
+    let bytes = write_entries(entries, format).await?;
+    lua.create_string(bytes)
+}
+
+async fn archive_pack(lua: Lua, (dir, format): (String, ArchiveFormat)) -> LuaResult<LuaString> {
+    let entries = blocking::unblock(move || disk::pack(dir.into())).await?;
+    let bytes = write_entries(entries, format).await?;
+    lua.create_string(bytes)
+}
@@ -0,0 +1,101 @@
+use std::io::{self, Read};
+
+use mlua::prelude::*;
+use tar::{Archive, Builder, EntryType, Header};
+
+use super::entry::{ArchiveEntry, ArchiveEntryKind};
+
+pub fn read_entries(bytes: &[u8], with_contents: bool) -> LuaResult<Vec<ArchiveEntry>> {
+    let mut archive = Archive::new(bytes);
+
+    let mut entries = Vec::new();
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let header = entry.header();
+
+        let kind = match header.entry_type() {
+            EntryType::Regular | EntryType::Continuous => ArchiveEntryKind::File,
+            EntryType::Directory => ArchiveEntryKind::Dir,
+            EntryType::Symlink => ArchiveEntryKind::Symlink,
+            // Hard links, devices, fifos, and any other special entries can
+            // not be represented in a portable way, so we skip those entirely
+            _ => continue,
+        };
+
+        let path = ArchiveEntry::normalize_path(&String::from_utf8_lossy(&entry.path_bytes()));
+        if path.is_empty() {
+            continue;
+        }
+
+        let mode = header.mode().ok().map(|mode| mode & 0o7777);
+        let modified_at = header
+            .mtime()
+            .ok()
+            .and_then(|mtime| i64::try_from(mtime).ok());
+        let link_target = entry
+            .link_name_bytes()
+            .map(|target| String::from_utf8_lossy(&target).into_owned());
+
+        let contents = if kind == ArchiveEntryKind::File && with_contents {
+            // NOTE: The size in the header is not trusted for allocating,
+            // since any archive may claim to contain a file of any size
+            let mut contents = Vec::new();
+            entry.read_to_end(&mut contents)?;
+            Some(contents)
+        } else {
+            None
+        };
+
+        entries.push(ArchiveEntry {
+            path,
+            kind,
+            size: entry.size(),
+            mode,
+            modified_at,
+            link_target: if kind == ArchiveEntryKind::Symlink {
+                link_target
+            } else {
+                None
+            },
+            contents,
+        });
+    }
+
+    Ok(entries)
+}
+
+pub fn write_entries(entries: Vec<ArchiveEntry>) -> LuaResult<Vec<u8>> {
+    let mut builder = Builder::new(Vec::new());
+
+    for entry in entries {
+        let mut header = Header::new_gnu();
+        header.set_mode(entry.mode.unwrap_or_else(|| entry.default_mode()));
+        header.set_mtime(
+            entry
+                .modified_at
+                .map_or(0, |t| u64::try_from(t).unwrap_or(0)),
+        );
+
+        match entry.kind {
+            ArchiveEntryKind::Dir => {
+                header.set_entry_type(EntryType::Directory);
+                header.set_size(0);
+                builder.append_data(&mut header, entry.path, io::empty())?;
+            }
+            ArchiveEntryKind::Symlink => {
+                header.set_entry_type(EntryType::Symlink);
+                header.set_size(0);
+                let target = entry.link_target.unwrap_or_default();
+                builder.append_link(&mut header, entry.path, target)?;
+            }
+            ArchiveEntryKind::File => {
+                let contents = entry.contents.unwrap_or_default();
+                header.set_entry_type(EntryType::Regular);
+                header.set_size(contents.len() as u64);
+                builder.append_data(&mut header, entry.path, contents.as_slice())?;
+            }
+        }
+    }
+
+    Ok(builder.into_inner()?)
+}
@@ -0,0 +1,136 @@
+use std::io::{Cursor, Read, Write};
+
+use chrono::{DateTime as ChronoDateTime, Datelike, NaiveDate, Timelike};
+use mlua::prelude::*;
+use zip::{
+    CompressionMethod, DateTime as ZipDateTime, ExtraField, ZipArchive, ZipWriter,
+    write::SimpleFileOptions,
+};
+
+use super::entry::{ArchiveEntry, ArchiveEntryKind};
+
+/*
+    NOTE: Timestamps in zip files are stored in the "MS-DOS" format, which has
+    a resolution of 2 seconds, and no time zone. We always read and write these
+    as UTC, but prefer the extended timestamp field when reading, if present.
+*/
+
+fn timestamp_from_zip(time: ZipDateTime) -> Option<i64> {
+    let date = NaiveDate::from_ymd_opt(
+        i32::from(time.year()),
+        u32::from(time.month()),
+        u32::from(time.day()),
+    )?;
+    let time = date.and_hms_opt(
+        u32::from(time.hour()),
+        u32::from(time.minute()),
+        u32::from(time.second()),
+    )?;
+    Some(time.and_utc().timestamp())
+}
+
+fn timestamp_to_zip(timestamp: i64) -> ZipDateTime {
+    ChronoDateTime::from_timestamp(timestamp, 0)
+        .and_then(|time| {
+            ZipDateTime::from_date_and_time(
+                u16::try_from(time.year()).ok()?,
+                time.month() as u8,
+                time.day() as u8,
+                time.hour() as u8,
+                time.minute() as u8,
+                time.second() as u8,
+            )
+            .ok()
+        })
+        .unwrap_or_default()
+}
+
+pub fn read_entries(bytes: &[u8], with_contents: bool) -> LuaResult<Vec<ArchiveEntry>> {
+    let mut archive = ZipArchive::new(Cursor::new(bytes)).into_lua_err()?;
+
+    let mut entries = Vec::with_capacity(archive.len());
+    for index in 0..archive.len() {
+        let mut file = archive.by_index(index).into_lua_err()?;
+
+        let path = ArchiveEntry::normalize_path(file.name());
+        if path.is_empty() {
+            continue;
+        }
+
+        let kind = if file.is_dir() {
+            ArchiveEntryKind::Dir
+        } else if file.is_symlink() {
+            ArchiveEntryKind::Symlink
+        } else {
+            ArchiveEntryKind::File
+        };
+
+        let modified_at = file
+            .extra_data_fields()
+            .find_map(|field| match field {
+                ExtraField::ExtendedTimestamp(ts) => ts.mod_time().map(i64::from),
+                ExtraField::Ntfs(_) => None,
+            })
+            .or_else(|| file.last_modified().and_then(timestamp_from_zip));
+
+        let mut entry = ArchiveEntry {
+            path,
+            kind,
+            size: file.size(),
+            mode: file.unix_mode().map(|mode| mode & 0o7777),
+            modified_at,
+            link_target: None,
+            contents: None,
+        };
+
+        // Symlinks in zip files store their target as the file contents
+        if kind == ArchiveEntryKind::Symlink {
+            let mut target = String::new();
+            file.read_to_string(&mut target)?;
+            entry.link_target = Some(target);
+        } else if kind == ArchiveEntryKind::File && with_contents {
+            // NOTE: The size in the header is not trusted for allocating,
+            // since any archive may claim to contain a file of any size
+            let mut contents = Vec::new();
+            file.read_to_end(&mut contents)?;
+            entry.contents = Some(contents);
+        }
+
+        entries.push(entry);
+    }
+
+    Ok(entries)
+}
+
+pub fn write_entries(entries: Vec<ArchiveEntry>) -> LuaResult<Vec<u8>> {
+    let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+
+    for entry in entries {
+        let mut options = SimpleFileOptions::default()
+            .compression_method(CompressionMethod::Deflated)
+            .unix_permissions(entry.mode.unwrap_or_else(|| entry.default_mode()));
+        if let Some(modified_at) = entry.modified_at {
+            options = options.last_modified_time(timestamp_to_zip(modified_at));
+        }
+
+        match entry.kind {
+            ArchiveEntryKind::Dir => {
+                writer
+                    .add_directory(format!("{}/", entry.path), options)
+                    .into_lua_err()?;
+            }
+            ArchiveEntryKind::Symlink => {
+                let target = entry.link_target.unwrap_or_default();
+                writer
+                    .add_symlink(entry.path, target, options)
+                    .into_lua_err()?;
+            }
+            ArchiveEntryKind::File => {
+                writer.start_file(entry.path, options).into_lua_err()?;
+                writer.write_all(&entry.contents.unwrap_or_default())?;
+            }
+        }
+    }
+
+    Ok(writer.finish().into_lua_err()?.into_inner())
+}
@@ -60,6 +60,17 @@ impl DateTime {
         Ok(Self { inner })
     }
 
+    /**
+        Returns the number of seconds passed since the UNIX epoch, as a float.
+
+        This is the inverse of [`DateTime::from_unix_timestamp_float`].
+    */
+    #[must_use]
+    pub fn to_unix_timestamp_float(self) -> f64 {
+        self.inner.timestamp() as f64
+            + f64::from(self.inner.timestamp_subsec_nanos()) / 1_000_000_000f64
+    }
+
     /**
         Transforms individual date & time values into a new
         `DateTime` struct, using the universal (UTC) time zone.
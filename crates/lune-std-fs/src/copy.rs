@@ -1,73 +1,136 @@
-use std::collections::VecDeque;
 use std::io::ErrorKind;
 use std::path::{Path, PathBuf};
 
 use async_fs as fs;
+use filetime::FileTime;
 use futures_lite::prelude::*;
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
 use mlua::prelude::*;
 
-use super::options::FsWriteOptions;
+use super::metadata::FsMetadataKind;
+use super::options::{FsCopyOptions, FsWalkOptions};
+use super::walk::FsWalkerInner;
+
+const COPY_BUFFER_SIZE: usize = 256 * 1024;
 
 pub struct CopyContents {
     // Vec<(relative depth, path)>
     pub dirs: Vec<(usize, PathBuf)>,
     pub files: Vec<(usize, PathBuf)>,
+    pub symlinks: Vec<(usize, PathBuf)>,
+    pub total_bytes: u64,
+}
+
+/**
+    Progress for a single copy operation, which is passed
+    to the `onProgress` callback every time it changes.
+*/
+#[derive(Debug, Clone, Copy, Default)]
+struct CopyProgress {
+    bytes_copied: u64,
+    bytes_total: u64,
+    files_copied: usize,
+    files_total: usize,
+}
+
+impl IntoLua for CopyProgress {
+    fn into_lua(self, lua: &Lua) -> LuaResult<LuaValue> {
+        let tab = lua.create_table_with_capacity(0, 4)?;
+        tab.set("bytesCopied", self.bytes_copied)?;
+        tab.set("bytesTotal", self.bytes_total)?;
+        tab.set("filesCopied", self.files_copied)?;
+        tab.set("filesTotal", self.files_total)?;
+        tab.set_readonly(true);
+        Ok(LuaValue::Table(tab))
+    }
+}
+
+struct CopyReporter {
+    callback: Option<LuaFunction>,
+    progress: CopyProgress,
+}
+
+impl CopyReporter {
+    async fn report(&self) -> LuaResult<()> {
+        // NOTE: The callback must be called asynchronously, since it
+        // may yield, for example when waiting or writing to stdout
+        match &self.callback {
+            Some(callback) => callback.call_async::<()>(self.progress).await,
+            None => Ok(()),
+        }
+    }
+}
+
+fn build_include_set(patterns: &[String]) -> LuaResult<Option<GlobSet>> {
+    if patterns.is_empty() {
+        return Ok(None);
+    }
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        let glob = GlobBuilder::new(pattern)
+            .literal_separator(true)
+            .build()
+            .map_err(|e| {
+                LuaError::RuntimeError(format!("Invalid include pattern '{pattern}'\n{e}"))
+            })?;
+        builder.add(glob);
+    }
+    Ok(Some(builder.build().into_lua_err()?))
 }
 
-async fn get_contents_at(root: PathBuf, _: FsWriteOptions) -> LuaResult<CopyContents> {
+async fn get_contents_at(root: PathBuf, options: &FsCopyOptions) -> LuaResult<CopyContents> {
     let mut dirs = Vec::new();
     let mut files = Vec::new();
+    let mut symlinks = Vec::new();
+    let mut total_bytes = 0;
 
-    let mut queue = VecDeque::new();
+    let include = build_include_set(&options.include)?;
 
-    let normalized_root = fs::canonicalize(&root).await.map_err(|e| {
-        LuaError::RuntimeError(format!("Failed to canonicalize root directory path\n{e}"))
-    })?;
+    // NOTE: The walker guards against symlink cycles when following
+    // symlinks, and never descends into any excluded directories
+    let mut walker = FsWalkerInner::new(
+        root.clone(),
+        FsWalkOptions {
+            max_depth: None,
+            follow_symlinks: options.follow_symlinks,
+            exclude: options.exclude.clone(),
+        },
+    )?;
 
-    // Push initial children of the root path into the queue
-    let mut reader = fs::read_dir(&normalized_root).await?;
-    while let Some(entry) = reader.try_next().await? {
-        queue.push_back((1, entry.path()));
-    }
+    while let Some(entry) = walker.next().await? {
+        // SAFETY: The walker only ever yields paths inside of the root, so unwrap is safe
+        let relative = entry.path.strip_prefix(&root).unwrap().to_path_buf();
 
-    // Go through the current queue, pushing to it
-    // when we find any new descendant directories
-    // FUTURE: Try to do async reading here concurrently to speed it up a bit
-    while let Some((current_depth, current_path)) = queue.pop_front() {
-        let meta = fs::metadata(&current_path).await?;
-        if meta.is_symlink() {
-            return Err(LuaError::RuntimeError(format!(
-                "Symlinks are not yet supported, encountered at path '{}'",
-                current_path.display()
-            )));
-        } else if meta.is_dir() {
-            // FUTURE: Add an option in FsWriteOptions for max depth and limit it here
-            let mut entries = fs::read_dir(&current_path).await?;
-            while let Some(entry) = entries.try_next().await? {
-                queue.push_back((current_depth + 1, entry.path()));
+        // Directories are only copied as-is when there is no include filter,
+        // otherwise they are created as needed for any included files
+        if entry.kind == FsMetadataKind::Dir {
+            if include.is_none() {
+                dirs.push((entry.depth, relative));
             }
-            dirs.push((current_depth, current_path));
-        } else {
-            files.push((current_depth, current_path));
+            continue;
         }
-    }
 
-    // Ensure that all directory and file paths are relative to the root path
-    // SAFETY: Since we only ever push dirs and files relative to the root, unwrap is safe
-    for (_, dir) in &mut dirs {
-        *dir = dir.strip_prefix(&normalized_root).unwrap().to_path_buf();
-    }
-    for (_, file) in &mut files {
-        *file = file.strip_prefix(&normalized_root).unwrap().to_path_buf();
-    }
+        if include
+            .as_ref()
+            .is_some_and(|include| !include.is_match(&relative))
+        {
+            continue;
+        }
 
-    // FUTURE: Deduplicate paths such that these directories:
-    // - foo/
-    // - foo/bar/
-    // - foo/bar/baz/
-    // turn into a single foo/bar/baz/ and let create_dir_all do the heavy lifting
+        if entry.kind == FsMetadataKind::Symlink {
+            symlinks.push((entry.depth, relative));
+        } else {
+            total_bytes += fs::metadata(&entry.path).await?.len();
+            files.push((entry.depth, relative));
+        }
+    }
 
-    Ok(CopyContents { dirs, files })
+    Ok(CopyContents {
+        dirs,
+        files,
+        symlinks,
+        total_bytes,
+    })
 }
 
 async fn ensure_no_dir_exists(path: impl AsRef<Path>) -> LuaResult<()> {
@@ -92,17 +155,99 @@ async fn ensure_no_file_exists(path: impl AsRef<Path>) -> LuaResult<()> {
     }
 }
 
+/**
+    Creates a symlink at `link` pointing to `target`.
+
+    On Windows, the target is resolved relative to the link to
+    know if a file or directory symlink should be created.
+*/
+pub async fn create_symlink(target: impl AsRef<Path>, link: impl AsRef<Path>) -> LuaResult<()> {
+    let target = target.as_ref();
+    let link = link.as_ref();
+    #[cfg(unix)]
+    {
+        fs::unix::symlink(target, link).await.into_lua_err()
+    }
+    #[cfg(windows)]
+    {
+        let resolved = link
+            .parent()
+            .map_or_else(|| target.to_path_buf(), |parent| parent.join(target));
+        if fs::metadata(resolved).await.is_ok_and(|meta| meta.is_dir()) {
+            fs::windows::symlink_dir(target, link).await.into_lua_err()
+        } else {
+            fs::windows::symlink_file(target, link).await.into_lua_err()
+        }
+    }
+}
+
+async fn copy_timestamps(source: &Path, target: &Path, is_symlink: bool) -> LuaResult<()> {
+    let meta = if is_symlink {
+        fs::symlink_metadata(source).await?
+    } else {
+        fs::metadata(source).await?
+    };
+    let atime = FileTime::from_last_access_time(&meta);
+    let mtime = FileTime::from_last_modification_time(&meta);
+    let target = target.to_path_buf();
+    blocking::unblock(move || {
+        if is_symlink {
+            filetime::set_symlink_file_times(target, atime, mtime)
+        } else {
+            filetime::set_file_times(target, atime, mtime)
+        }
+    })
+    .await
+    .into_lua_err()
+}
+
+async fn copy_file(
+    source: &Path,
+    target: &Path,
+    options: &FsCopyOptions,
+    reporter: &mut CopyReporter,
+) -> LuaResult<()> {
+    if reporter.callback.is_none() {
+        fs::copy(source, target).await?;
+    } else {
+        // Copy in chunks to be able to report progress for large files,
+        // making sure to also copy permissions just like fs::copy does
+        let mut reader = fs::File::open(source).await?;
+        let mut writer = fs::File::create(target).await?;
+        let mut buffer = vec![0; COPY_BUFFER_SIZE];
+        loop {
+            let read = reader.read(&mut buffer).await?;
+            if read == 0 {
+                break;
+            }
+            writer.write_all(&buffer[..read]).await?;
+            reporter.progress.bytes_copied += read as u64;
+            reporter.report().await?;
+        }
+        writer.flush().await?;
+        let permissions = reader.metadata().await?.permissions();
+        fs::set_permissions(target, permissions).await?;
+    }
+
+    if options.preserve_timestamps {
+        copy_timestamps(source, target, false).await?;
+    }
+
+    reporter.progress.files_copied += 1;
+    reporter.report().await
+}
+
 pub async fn copy(
     source: impl AsRef<Path>,
     target: impl AsRef<Path>,
-    options: FsWriteOptions,
+    options: FsCopyOptions,
 ) -> LuaResult<()> {
     let source = source.as_ref();
     let target = target.as_ref();
 
     // Check if we got a file or directory - we will handle them differently below
-    let (is_dir, is_file) = match fs::metadata(&source).await {
-        Ok(meta) => (meta.is_dir(), meta.is_file()),
+    let (is_dir, is_file, len) = match fs::metadata(&source).await {
+        Ok(meta) => (meta.is_dir(), meta.is_file(), meta.len()),
         Err(e) if e.kind() == ErrorKind::NotFound => {
             return Err(LuaError::RuntimeError(format!(
                 "No file or directory exists at the path '{}'",
@@ -123,7 +268,8 @@ pub async fn copy(
     // 1. If we are not allowed to overwrite, make sure nothing exists at the target path
     // 2. If we are allowed to overwrite, remove any previous entry at the path
     // 3. Write all directories first
-    // 4. Write all files
+    // 4. Write all files and symlinks
+    // 5. Copy directory timestamps last, since writing into them changes them
 
     if !options.overwrite {
         if is_file {
@@ -133,14 +279,23 @@ pub async fn copy(
         }
     }
 
+    let mut reporter = CopyReporter {
+        callback: options.on_progress.clone(),
+        progress: CopyProgress::default(),
+    };
+
     if is_file {
-        fs::copy(source, target).await?;
+        reporter.progress.bytes_total = len;
+        reporter.progress.files_total = 1;
+        copy_file(source, target, &options, &mut reporter).await?;
     } else if is_dir {
-        let contents = get_contents_at(source.to_path_buf(), options).await?;
+        let contents = get_contents_at(source.to_path_buf(), &options).await?;
+        reporter.progress.bytes_total = contents.total_bytes;
+        reporter.progress.files_total = contents.files.len() + contents.symlinks.len();
 
         if options.overwrite {
-            let (is_dir, is_file) = match fs::metadata(&target).await {
-                Ok(meta) => (meta.is_dir(), meta.is_file()),
+            let (is_dir, is_file) = match fs::symlink_metadata(&target).await {
+                Ok(meta) => (meta.is_dir(), !meta.is_dir()),
                 Err(e) if e.kind() == ErrorKind::NotFound => (false, false),
                 Err(e) => return Err(e.into()),
             };
@@ -159,7 +314,31 @@ pub async fn copy(
             fs::create_dir_all(target.join(dir)).await?;
         }
         for (_, file) in &contents.files {
-            fs::copy(source.join(file), target.join(file)).await?;
+            let file_target = target.join(file);
+            if let Some(parent) = file_target.parent() {
+                fs::create_dir_all(parent).await?;
+            }
+            copy_file(&source.join(file), &file_target, &options, &mut reporter).await?;
+        }
+        for (_, link) in &contents.symlinks {
+            let link_source = source.join(link);
+            let link_target = target.join(link);
+            if let Some(parent) = link_target.parent() {
+                fs::create_dir_all(parent).await?;
+            }
+            create_symlink(fs::read_link(&link_source).await?, &link_target).await?;
+            if options.preserve_timestamps {
+                copy_timestamps(&link_source, &link_target, true).await?;
+            }
+            reporter.progress.files_copied += 1;
+            reporter.report().await?;
+        }
+
+        if options.preserve_timestamps {
+            for (_, dir) in contents.dirs.iter().rev() {
+                copy_timestamps(&source.join(dir), &target.join(dir), false).await?;
+            }
+            copy_timestamps(source, target, false).await?;
         }
     }
 
@@ -0,0 +1,251 @@
+use std::io::SeekFrom;
+use std::path::Path;
+use std::sync::Arc;
+
+use async_fs::File;
+use async_lock::Mutex as AsyncMutex;
+use bstr::{BString, ByteSlice};
+use futures_lite::prelude::*;
+use mlua::prelude::*;
+
+use super::options::FsOpenMode;
+
+const DEFAULT_BUFFER_SIZE: usize = 1024;
+const LINE_BUFFER_SIZE: usize = 8192;
+const MAX_READ_CHUNK_SIZE: usize = 64 * 1024;
+
+// Inner (plumbing) implementation
+
+#[derive(Debug)]
+struct FsFileInner {
+    file: File,
+    // Bytes that were read ahead of the logical cursor when
+    // looking for line endings, and not yet given to Lua
+    buffered: Vec<u8>,
+}
+
+impl FsFileInner {
+    async fn unread_buffered(&mut self) -> std::io::Result<()> {
+        if !self.buffered.is_empty() {
+            let offset = i64::try_from(self.buffered.len()).expect("buffer fits in i64");
+            self.buffered.clear();
+            self.file.seek(SeekFrom::Current(-offset)).await?;
+        }
+        Ok(())
+    }
+
+    async fn read(&mut self, size: usize) -> std::io::Result<Vec<u8>> {
+        if !self.buffered.is_empty() {
+            let take = size.min(self.buffered.len());
+            return Ok(self.buffered.drain(..take).collect());
+        }
+
+        // NOTE: The requested size may be much larger than the file, so we
+        // never allocate more than a single chunk ahead of the data read
+        let mut buf = Vec::new();
+        while buf.len() < size {
+            let start = buf.len();
+            let chunk = (size - start).min(MAX_READ_CHUNK_SIZE);
+            buf.resize(start + chunk, 0);
+            let read = self.file.read(&mut buf[start..]).await?;
+            buf.truncate(start + read);
+            if read < chunk {
+                break;
+            }
+        }
+
+        Ok(buf)
+    }
+
+    async fn read_line(&mut self) -> std::io::Result<Option<Vec<u8>>> {
+        let mut searched = 0;
+        loop {
+            if let Some(pos) = self.buffered[searched..].find_byte(b'\n') {
+                let end = searched + pos;
+                let mut line: Vec<u8> = self.buffered.drain(..=end).collect();
+                line.pop();
+                if line.last() == Some(&b'\r') {
+                    line.pop();
+                }
+                return Ok(Some(line));
+            }
+            searched = self.buffered.len();
+
+            let mut chunk = vec![0; LINE_BUFFER_SIZE];
+            let read = self.file.read(&mut chunk).await?;
+            if read == 0 {
+                // Reached the end of the file, return whatever we have left as
+                // the last line, or nothing if there is no more data at all
+                if self.buffered.is_empty() {
+                    return Ok(None);
+                }
+                return Ok(Some(std::mem::take(&mut self.buffered)));
+            }
+            self.buffered.extend_from_slice(&chunk[..read]);
+        }
+    }
+
+    async fn write(&mut self, data: &[u8]) -> std::io::Result<()> {
+        self.unread_buffered().await?;
+        self.file.write_all(data).await
+    }
+
+    async fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        let pos = match pos {
+            SeekFrom::Current(offset) => {
+                let buffered = i64::try_from(self.buffered.len()).expect("buffer fits in i64");
+                SeekFrom::Current(offset - buffered)
+            }
+            pos => pos,
+        };
+        self.buffered.clear();
+        self.file.seek(pos).await
+    }
+
+    async fn flush(&mut self) -> std::io::Result<()> {
+        self.unread_buffered().await?;
+        self.file.flush().await
+    }
+
+    async fn truncate(&mut self, size: Option<u64>) -> std::io::Result<()> {
+        self.unread_buffered().await?;
+        let size = match size {
+            Some(size) => size,
+            None => self.file.seek(SeekFrom::Current(0)).await?,
+        };
+        self.file.set_len(size).await
+    }
+
+    async fn close(&mut self) -> std::io::Result<()> {
+        self.buffered.clear();
+        self.file.flush().await?;
+        self.file.close().await
+    }
+}
+
+// Outer (lua-accessible, clonable) implementation
+
+/**
+    A handle to an open file, created using `fs.open`.
+
+    Reads and writes go directly through to the underlying file,
+    without ever loading more of it into memory than requested.
+*/
+#[derive(Debug, Clone)]
+pub struct FsFile {
+    inner: Arc<AsyncMutex<Option<FsFileInner>>>,
+}
+
+impl FsFile {
+    pub async fn open(path: impl AsRef<Path>, mode: FsOpenMode) -> LuaResult<Self> {
+        let file = mode.as_open_options().open(path).await?;
+        Ok(Self {
+            inner: Arc::new(AsyncMutex::new(Some(FsFileInner {
+                file,
+                buffered: Vec::new(),
+            }))),
+        })
+    }
+}
+
+fn closed_error() -> LuaError {
+    LuaError::runtime("File handle has been closed")
+}
+
+fn parse_whence(whence: Option<&str>, offset: i64) -> LuaResult<SeekFrom> {
+    Ok(match whence.unwrap_or("cur") {
+        "set" => SeekFrom::Start(u64::try_from(offset).map_err(|_| {
+            LuaError::RuntimeError(format!(
+                "Invalid seek offset - expected a non-negative offset from the start, got {offset}"
+            ))
+        })?),
+        "cur" => SeekFrom::Current(offset),
+        "end" => SeekFrom::End(offset),
+        s => {
+            return Err(LuaError::RuntimeError(format!(
+                "Invalid seek position - got '{s}', expected one of 'set', 'cur', 'end'"
+            )));
+        }
+    })
+}
+
+impl LuaUserData for FsFile {
+    fn add_methods<M: LuaUserDataMethods<Self>>(methods: &mut M) {
+        methods.add_async_method(
+            "read",
+            |lua, this, (size, as_buffer): (Option<usize>, Option<bool>)| {
+                let inner = this.inner.clone();
+                let size = size.unwrap_or(DEFAULT_BUFFER_SIZE);
+                async move {
+                    let mut inner = inner.lock().await;
+                    let inner = inner.as_mut().ok_or_else(closed_error)?;
+                    let bytes = inner.read(size).await.into_lua_err()?;
+                    if bytes.is_empty() && size > 0 {
+                        Ok(LuaValue::Nil)
+                    } else if as_buffer.unwrap_or(false) {
+                        Ok(LuaValue::Buffer(lua.create_buffer(bytes)?))
+                    } else {
+                        Ok(LuaValue::String(lua.create_string(bytes)?))
+                    }
+                }
+            },
+        );
+        methods.add_async_method("readLine", |lua, this, (): ()| {
+            let inner = this.inner.clone();
+            async move {
+                let mut inner = inner.lock().await;
+                let inner = inner.as_mut().ok_or_else(closed_error)?;
+                match inner.read_line().await.into_lua_err()? {
+                    Some(line) => Ok(LuaValue::String(lua.create_string(line)?)),
+                    None => Ok(LuaValue::Nil),
+                }
+            }
+        });
+        methods.add_async_method("write", |_, this, data: BString| {
+            let inner = this.inner.clone();
+            async move {
+                let mut inner = inner.lock().await;
+                let inner = inner.as_mut().ok_or_else(closed_error)?;
+                inner.write(data.as_bytes()).await.into_lua_err()
+            }
+        });
+        methods.add_async_method(
+            "seek",
+            |_, this, (whence, offset): (Option<String>, Option<i64>)| {
+                let inner = this.inner.clone();
+                async move {
+                    let pos = parse_whence(whence.as_deref(), offset.unwrap_or(0))?;
+                    let mut inner = inner.lock().await;
+                    let inner = inner.as_mut().ok_or_else(closed_error)?;
+                    inner.seek(pos).await.into_lua_err()
+                }
+            },
+        );
+        methods.add_async_method("flush", |_, this, (): ()| {
+            let inner = this.inner.clone();
+            async move {
+                let mut inner = inner.lock().await;
+                let inner = inner.as_mut().ok_or_else(closed_error)?;
+                inner.flush().await.into_lua_err()
+            }
+        });
+        methods.add_async_method("truncate", |_, this, size: Option<u64>| {
+            let inner = this.inner.clone();
+            async move {
+                let mut inner = inner.lock().await;
+                let inner = inner.as_mut().ok_or_else(closed_error)?;
+                inner.truncate(size).await.into_lua_err()
+            }
+        });
+        methods.add_async_method("close", |_, this, (): ()| {
+            let inner = this.inner.clone();
+            async move {
+                let mut inner = inner.lock().await;
+                match inner.take() {
+                    Some(mut inner) => inner.close().await.into_lua_err(),
+                    None => Ok(()),
+                }
+            }
+        });
+    }
+}
@@ -11,12 +11,27 @@ use mlua::prelude::*;
 use lune_utils::TableBuilder;
 
 mod copy;
+mod file;
+mod lock;
 mod metadata;
 mod options;
+mod temp;
+mod walk;
+mod watch;
+mod write;
 
-use self::copy::copy;
-use self::metadata::FsMetadata;
-use self::options::FsWriteOptions;
+use self::copy::{copy, create_symlink};
+use self::file::FsFile;
+use self::lock::FsLock;
+use self::metadata::{FsMetadata, FsMetadataKind};
+use self::options::{
+    FsCopyOptions, FsLockOptions, FsOpenMode, FsPermissionsOptions, FsTempOptions, FsTimesOptions,
+    FsWalkOptions, FsWatchOptions, FsWriteFileOptions, FsWriteOptions,
+};
+use self::temp::{FsTempPath, cleanup_temp_paths};
+use self::walk::FsWalker;
+use self::watch::FsWatcher;
+use self::write::write_file;
 
 const TYPEDEFS: &str = include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/types.d.luau"));
 
@@ -39,6 +54,11 @@ pub fn module(lua: Lua) -> LuaResult<LuaTable> {
     TableBuilder::new(lua)?
         .with_async_function("readFile", fs_read_file)?
         .with_async_function("readDir", fs_read_dir)?
+        .with_async_function("open", fs_open)?
+        .with_function("walk", fs_walk)?
+        .with_function("glob", fs_glob)?
+        .with_async_function("tempFile", fs_temp_file)?
+        .with_async_function("tempDir", fs_temp_dir)?
         .with_async_function("writeFile", fs_write_file)?
         .with_async_function("writeDir", fs_write_dir)?
         .with_async_function("removeFile", fs_remove_file)?
@@ -46,11 +66,27 @@ pub fn module(lua: Lua) -> LuaResult<LuaTable> {
         .with_async_function("metadata", fs_metadata)?
         .with_async_function("isFile", fs_is_file)?
         .with_async_function("isDir", fs_is_dir)?
+        .with_async_function("symlink", fs_symlink)?
+        .with_async_function("readLink", fs_read_link)?
+        .with_async_function("setPermissions", fs_set_permissions)?
+        .with_async_function("setTimes", fs_set_times)?
         .with_async_function("move", fs_move)?
         .with_async_function("copy", fs_copy)?
+        .with_async_function("lock", fs_lock)?
+        .with_function("watch", fs_watch)?
         .build_readonly()
 }
 
+/**
+    Cleans up any resources held by the `fs` standard library in the given Lua state.
+
+    This removes all temporary files and directories that were not explicitly kept,
+    and should be called once the Lua state will no longer be used.
+*/
+pub fn cleanup(lua: &Lua) {
+    cleanup_temp_paths(lua);
+}
+
 async fn fs_read_file(lua: Lua, path: String) -> LuaResult<LuaString> {
     let bytes = fs::read(&path).await.into_lua_err()?;
 
@@ -73,8 +109,31 @@ async fn fs_read_dir(_: Lua, path: String) -> LuaResult<Vec<String>> {
     Ok(dir_strings)
 }
 
-async fn fs_write_file(_: Lua, (path, contents): (String, BString)) -> LuaResult<()> {
-    fs::write(&path, contents.as_bytes()).await.into_lua_err()
+fn fs_walk(_: &Lua, (root, options): (String, FsWalkOptions)) -> LuaResult<FsWalker> {
+    FsWalker::new(root, options)
+}
+
+fn fs_glob(_: &Lua, (pattern, options): (String, FsWalkOptions)) -> LuaResult<FsWalker> {
+    FsWalker::glob(&pattern, options)
+}
+
+async fn fs_open(_: Lua, (path, mode): (String, FsOpenMode)) -> LuaResult<FsFile> {
+    FsFile::open(path, mode).await
+}
+
+async fn fs_temp_file(lua: Lua, options: FsTempOptions) -> LuaResult<FsTempPath> {
+    FsTempPath::create(&lua, FsMetadataKind::File, options).await
+}
+
+async fn fs_temp_dir(lua: Lua, options: FsTempOptions) -> LuaResult<FsTempPath> {
+    FsTempPath::create(&lua, FsMetadataKind::Dir, options).await
+}
+
+async fn fs_write_file(
+    _: Lua,
+    (path, contents, options): (String, BString, FsWriteFileOptions),
+) -> LuaResult<()> {
+    write_file(&path, contents.as_bytes(), options).await
 }
 
 async fn fs_write_dir(_: Lua, path: String) -> LuaResult<()> {
@@ -90,10 +149,20 @@ async fn fs_remove_dir(_: Lua, path: String) -> LuaResult<()> {
 }
 
 async fn fs_metadata(_: Lua, path: String) -> LuaResult<FsMetadata> {
-    match fs::metadata(path).await {
-        Err(e) if e.kind() == IoErrorKind::NotFound => Ok(FsMetadata::not_found()),
-        Ok(meta) => Ok(FsMetadata::from(meta)),
-        Err(e) => Err(e.into()),
+    let meta = match fs::metadata(&path).await {
+        Err(e) if e.kind() == IoErrorKind::NotFound => return Ok(FsMetadata::not_found()),
+        Ok(meta) => FsMetadata::from(meta),
+        Err(e) => return Err(e.into()),
+    };
+    // Metadata follows symlinks, so we need to check the path itself to find a symlink target
+    let is_symlink = fs::symlink_metadata(&path)
+        .await
+        .is_ok_and(|meta| meta.is_symlink());
+    if is_symlink {
+        let target = fs::read_link(&path).await.into_lua_err()?;
+        Ok(meta.with_symlink_target(Some(target)))
+    } else {
+        Ok(meta)
     }
 }
 
@@ -113,6 +182,65 @@ async fn fs_is_dir(_: Lua, path: String) -> LuaResult<bool> {
     }
 }
 
+async fn fs_symlink(_: Lua, (target, link): (String, String)) -> LuaResult<()> {
+    create_symlink(target, link).await
+}
+
+async fn fs_read_link(_: Lua, path: String) -> LuaResult<String> {
+    let target = fs::read_link(&path).await.into_lua_err()?;
+    Ok(target.to_string_lossy().into_owned())
+}
+
+async fn fs_set_permissions(
+    _: Lua,
+    (path, options): (String, FsPermissionsOptions),
+) -> LuaResult<()> {
+    let mut permissions = fs::metadata(&path).await.into_lua_err()?.permissions();
+    if let Some(mode) = options.mode {
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            permissions.set_mode(mode);
+        }
+        #[cfg(not(unix))]
+        {
+            let _ = mode;
+            return Err(LuaError::runtime(
+                "Setting permission mode bits is only supported on unix platforms",
+            ));
+        }
+    }
+    if let Some(read_only) = options.read_only {
+        #[cfg(unix)]
+        {
+            // NOTE: Clearing the read-only flag using set_readonly would make the
+            // path writable by everyone, so we only give back write access to the owner
+            use std::os::unix::fs::PermissionsExt;
+            if read_only {
+                permissions.set_readonly(true);
+            } else {
+                permissions.set_mode(permissions.mode() | 0o200);
+            }
+        }
+        #[cfg(not(unix))]
+        {
+            permissions.set_readonly(read_only);
+        }
+    }
+    fs::set_permissions(&path, permissions).await.into_lua_err()
+}
+
+async fn fs_set_times(_: Lua, (path, options): (String, FsTimesOptions)) -> LuaResult<()> {
+    blocking::unblock(move || match (options.accessed_at, options.modified_at) {
+        (Some(atime), Some(mtime)) => filetime::set_file_times(&path, atime, mtime),
+        (Some(atime), None) => filetime::set_file_atime(&path, atime),
+        (None, Some(mtime)) => filetime::set_file_mtime(&path, mtime),
+        (None, None) => Ok(()),
+    })
+    .await
+    .into_lua_err()
+}
+
 async fn fs_move(_: Lua, (from, to, options): (String, String, FsWriteOptions)) -> LuaResult<()> {
     let path_from = PathBuf::from(from);
     if !path_from.exists() {
@@ -132,6 +260,14 @@ async fn fs_move(_: Lua, (from, to, options): (String, String, FsWriteOptions))
     Ok(())
 }
 
-async fn fs_copy(_: Lua, (from, to, options): (String, String, FsWriteOptions)) -> LuaResult<()> {
+async fn fs_copy(_: Lua, (from, to, options): (String, String, FsCopyOptions)) -> LuaResult<()> {
     copy(from, to, options).await
 }
+
+async fn fs_lock(_: Lua, (path, options): (String, FsLockOptions)) -> LuaResult<Option<FsLock>> {
+    FsLock::acquire(path, options).await
+}
+
+fn fs_watch(_: &Lua, (path, options): (String, FsWatchOptions)) -> LuaResult<FsWatcher> {
+    FsWatcher::new(path, options)
+}
@@ -0,0 +1,101 @@
+use std::fs::{File, OpenOptions, TryLockError};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use async_io::Timer;
+use mlua::prelude::*;
+
+use super::options::FsLockOptions;
+
+const LOCK_POLL_MIN_DELAY: Duration = Duration::from_millis(1);
+const LOCK_POLL_MAX_DELAY: Duration = Duration::from_millis(100);
+
+/**
+    An advisory lock on a file, created using `fs.lock`.
+
+    The lock is held for as long as the underlying file handle is
+    open, and is released when unlocked or garbage collected, or
+    by the operating system when the process exits.
+*/
+#[derive(Debug, Clone)]
+pub struct FsLock {
+    file: Arc<Mutex<Option<File>>>,
+    path: PathBuf,
+    shared: bool,
+}
+
+impl FsLock {
+    /**
+        Acquires a lock on the file at the given path, creating the file if it does not exist.
+
+        Returns `None` if the lock is currently held elsewhere and we should not wait for it.
+    */
+    pub async fn acquire(
+        path: impl Into<PathBuf>,
+        options: FsLockOptions,
+    ) -> LuaResult<Option<Self>> {
+        let path = path.into();
+        let open_path = path.clone();
+
+        let file = blocking::unblock(move || {
+            OpenOptions::new()
+                .read(true)
+                .write(true)
+                .create(true)
+                .truncate(false)
+                .open(open_path)
+        })
+        .await?;
+
+        // NOTE: Blocking until the lock is released elsewhere would tie up a
+        // thread for as long as it is held, and could not be cancelled, so we
+        // instead poll for it without blocking, backing off between attempts
+        let mut delay = LOCK_POLL_MIN_DELAY;
+        let file = loop {
+            let result = if options.shared {
+                file.try_lock_shared()
+            } else {
+                file.try_lock()
+            };
+            match result {
+                Ok(()) => break Some(file),
+                Err(TryLockError::WouldBlock) if options.wait => {
+                    Timer::after(delay).await;
+                    delay = (delay * 2).min(LOCK_POLL_MAX_DELAY);
+                }
+                Err(TryLockError::WouldBlock) => break None,
+                Err(TryLockError::Error(e)) => return Err(e.into_lua_err()),
+            }
+        };
+
+        Ok(file.map(|file| Self {
+            file: Arc::new(Mutex::new(Some(file))),
+            path,
+            shared: options.shared,
+        }))
+    }
+}
+
+impl LuaUserData for FsLock {
+    fn add_fields<F: LuaUserDataFields<Self>>(fields: &mut F) {
+        fields.add_field_method_get("path", |_, this| {
+            Ok(this.path.to_string_lossy().into_owned())
+        });
+        fields.add_field_method_get("shared", |_, this| Ok(this.shared));
+        fields.add_field_method_get("locked", |_, this| {
+            Ok(this.file.lock().expect("poisoned lock").is_some())
+        });
+    }
+
+    fn add_methods<M: LuaUserDataMethods<Self>>(methods: &mut M) {
+        methods.add_method("unlock", |_, this, (): ()| {
+            // Closing the file also releases the lock, but unlocking
+            // explicitly first lets us surface any errors from doing so
+            match this.file.lock().expect("poisoned lock").take() {
+                Some(file) => file.unlock().into_lua_err(),
+                None => Ok(()),
+            }
+        });
+    }
+}
@@ -2,10 +2,14 @@ use std::{
     fmt,
     fs::{FileType as StdFileType, Metadata as StdMetadata, Permissions as StdPermissions},
     io::Result as IoResult,
+    path::PathBuf,
     str::FromStr,
     time::SystemTime,
 };
 
+#[cfg(unix)]
+use std::os::unix::fs::{MetadataExt, PermissionsExt};
+
 use mlua::prelude::*;
 
 use lune_std_datetime::DateTime;
@@ -74,20 +78,28 @@ impl IntoLua for FsMetadataKind {
 #[derive(Debug, Clone)]
 pub struct FsPermissions {
     pub(crate) read_only: bool,
+    pub(crate) mode: Option<u32>,
 }
 
 impl From<StdPermissions> for FsPermissions {
     fn from(value: StdPermissions) -> Self {
+        #[cfg(unix)]
+        let mode = Some(value.mode() & 0o7777);
+        #[cfg(not(unix))]
+        let mode = None;
+
         Self {
             read_only: value.readonly(),
+            mode,
         }
     }
 }
 
 impl IntoLua for FsPermissions {
     fn into_lua(self, lua: &Lua) -> LuaResult<LuaValue> {
-        let tab = lua.create_table_with_capacity(0, 1)?;
+        let tab = lua.create_table_with_capacity(0, 2)?;
         tab.set("readOnly", self.read_only)?;
+        tab.set("mode", self.mode)?;
         tab.set_readonly(true);
         Ok(LuaValue::Table(tab))
     }
@@ -101,6 +113,12 @@ pub struct FsMetadata {
     pub(crate) modified_at: Option<DateTime>,
     pub(crate) accessed_at: Option<DateTime>,
     pub(crate) permissions: Option<FsPermissions>,
+    pub(crate) size: Option<u64>,
+    pub(crate) uid: Option<u32>,
+    pub(crate) gid: Option<u32>,
+    pub(crate) inode: Option<u64>,
+    pub(crate) link_count: Option<u64>,
+    pub(crate) symlink_target: Option<PathBuf>,
 }
 
 impl FsMetadata {
@@ -112,19 +130,40 @@ impl FsMetadata {
             modified_at: None,
             accessed_at: None,
             permissions: None,
+            size: None,
+            uid: None,
+            gid: None,
+            inode: None,
+            link_count: None,
+            symlink_target: None,
         }
     }
+
+    pub fn with_symlink_target(mut self, target: Option<PathBuf>) -> Self {
+        self.symlink_target = target;
+        self
+    }
 }
 
 impl IntoLua for FsMetadata {
     fn into_lua(self, lua: &Lua) -> LuaResult<LuaValue> {
-        let tab = lua.create_table_with_capacity(0, 6)?;
+        let symlink_target = self
+            .symlink_target
+            .map(|target| target.to_string_lossy().into_owned());
+
+        let tab = lua.create_table_with_capacity(0, 12)?;
         tab.set("kind", self.kind)?;
         tab.set("exists", self.exists)?;
         tab.set("createdAt", self.created_at)?;
         tab.set("modifiedAt", self.modified_at)?;
         tab.set("accessedAt", self.accessed_at)?;
         tab.set("permissions", self.permissions)?;
+        tab.set("size", self.size)?;
+        tab.set("uid", self.uid)?;
+        tab.set("gid", self.gid)?;
+        tab.set("inode", self.inode)?;
+        tab.set("linkCount", self.link_count)?;
+        tab.set("symlinkTarget", symlink_target)?;
         tab.set_readonly(true);
         Ok(LuaValue::Table(tab))
     }
@@ -132,6 +171,16 @@ impl IntoLua for FsMetadata {
 
 impl From<StdMetadata> for FsMetadata {
     fn from(value: StdMetadata) -> Self {
+        #[cfg(unix)]
+        let (uid, gid, inode, link_count) = (
+            Some(value.uid()),
+            Some(value.gid()),
+            Some(value.ino()),
+            Some(value.nlink()),
+        );
+        #[cfg(not(unix))]
+        let (uid, gid, inode, link_count) = (None, None, None, None);
+
         Self {
             kind: value.file_type().into(),
             exists: true,
@@ -139,6 +188,12 @@ impl From<StdMetadata> for FsMetadata {
             modified_at: system_time_to_timestamp(value.modified()),
             accessed_at: system_time_to_timestamp(value.accessed()),
             permissions: Some(FsPermissions::from(value.permissions())),
+            size: Some(value.len()),
+            uid,
+            gid,
+            inode,
+            link_count,
+            symlink_target: None,
         }
     }
 }
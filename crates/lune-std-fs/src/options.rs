@@ -1,5 +1,10 @@
+use std::{fmt, str::FromStr, time::Duration};
+
+use filetime::FileTime;
 use mlua::prelude::*;
 
+use lune_std_datetime::DateTime;
+
 #[derive(Debug, Clone, Copy)]
 pub struct FsWriteOptions {
     pub(crate) overwrite: bool,
@@ -29,3 +34,452 @@ impl FromLua for FsWriteOptions {
         })
     }
 }
+
+#[derive(Debug, Clone)]
+pub struct FsCopyOptions {
+    pub(crate) overwrite: bool,
+    pub(crate) include: Vec<String>,
+    pub(crate) exclude: Vec<String>,
+    pub(crate) follow_symlinks: bool,
+    pub(crate) preserve_timestamps: bool,
+    pub(crate) on_progress: Option<LuaFunction>,
+}
+
+impl Default for FsCopyOptions {
+    fn default() -> Self {
+        Self {
+            overwrite: false,
+            include: Vec::new(),
+            exclude: Vec::new(),
+            // NOTE: Copying has always followed symlinks and copied their
+            // targets, so we keep doing that unless explicitly told not to
+            follow_symlinks: true,
+            preserve_timestamps: false,
+            on_progress: None,
+        }
+    }
+}
+
+impl FromLua for FsCopyOptions {
+    fn from_lua(value: LuaValue, _: &Lua) -> LuaResult<Self> {
+        Ok(match value {
+            LuaValue::Nil => Self::default(),
+            LuaValue::Boolean(b) => Self {
+                overwrite: b,
+                ..Self::default()
+            },
+            LuaValue::Table(t) => {
+                let overwrite: Option<bool> = t.get("overwrite")?;
+                let include: Option<Vec<String>> = t.get("include")?;
+                let exclude: Option<Vec<String>> = t.get("exclude")?;
+                let follow_symlinks: Option<bool> = t.get("followSymlinks")?;
+                let preserve_timestamps: Option<bool> = t.get("preserveTimestamps")?;
+                Self {
+                    overwrite: overwrite.unwrap_or(false),
+                    include: include.unwrap_or_default(),
+                    exclude: exclude.unwrap_or_default(),
+                    follow_symlinks: follow_symlinks.unwrap_or(true),
+                    preserve_timestamps: preserve_timestamps.unwrap_or(false),
+                    on_progress: t.get("onProgress")?,
+                }
+            }
+            _ => {
+                return Err(LuaError::FromLuaConversionError {
+                    from: value.type_name(),
+                    to: "FsCopyOptions".to_string(),
+                    message: Some(format!(
+                        "Invalid copy options - expected boolean or table, got {}",
+                        value.type_name()
+                    )),
+                });
+            }
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FsWriteFileOptions {
+    pub(crate) append: bool,
+    pub(crate) create_new: bool,
+    pub(crate) atomic: bool,
+}
+
+impl FromLua for FsWriteFileOptions {
+    fn from_lua(value: LuaValue, _: &Lua) -> LuaResult<Self> {
+        Ok(match value {
+            LuaValue::Nil => Self::default(),
+            LuaValue::Table(t) => {
+                let append: Option<bool> = t.get("append")?;
+                let create_new: Option<bool> = t.get("createNew")?;
+                let atomic: Option<bool> = t.get("atomic")?;
+                let options = Self {
+                    append: append.unwrap_or(false),
+                    create_new: create_new.unwrap_or(false),
+                    atomic: atomic.unwrap_or(false),
+                };
+                if options.append && options.atomic {
+                    return Err(LuaError::runtime(
+                        "Invalid write options - append and atomic can not be used together",
+                    ));
+                }
+                options
+            }
+            _ => {
+                return Err(LuaError::FromLuaConversionError {
+                    from: value.type_name(),
+                    to: "FsWriteFileOptions".to_string(),
+                    message: Some(format!(
+                        "Invalid write options - expected table, got {}",
+                        value.type_name()
+                    )),
+                });
+            }
+        })
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct FsTempOptions {
+    pub(crate) prefix: Option<String>,
+    pub(crate) suffix: Option<String>,
+    pub(crate) dir: Option<String>,
+    pub(crate) keep: bool,
+}
+
+impl FromLua for FsTempOptions {
+    fn from_lua(value: LuaValue, _: &Lua) -> LuaResult<Self> {
+        Ok(match value {
+            LuaValue::Nil => Self::default(),
+            LuaValue::Table(t) => {
+                let keep: Option<bool> = t.get("keep")?;
+                Self {
+                    prefix: t.get("prefix")?,
+                    suffix: t.get("suffix")?,
+                    dir: t.get("dir")?,
+                    keep: keep.unwrap_or(false),
+                }
+            }
+            _ => {
+                return Err(LuaError::FromLuaConversionError {
+                    from: value.type_name(),
+                    to: "FsTempOptions".to_string(),
+                    message: Some(format!(
+                        "Invalid temp options - expected table, got {}",
+                        value.type_name()
+                    )),
+                });
+            }
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct FsLockOptions {
+    pub(crate) shared: bool,
+    pub(crate) wait: bool,
+}
+
+impl Default for FsLockOptions {
+    fn default() -> Self {
+        Self {
+            shared: false,
+            wait: true,
+        }
+    }
+}
+
+impl FromLua for FsLockOptions {
+    fn from_lua(value: LuaValue, _: &Lua) -> LuaResult<Self> {
+        Ok(match value {
+            LuaValue::Nil => Self::default(),
+            LuaValue::Table(t) => {
+                let shared: Option<bool> = t.get("shared")?;
+                let wait: Option<bool> = t.get("wait")?;
+                Self {
+                    shared: shared.unwrap_or(false),
+                    wait: wait.unwrap_or(true),
+                }
+            }
+            _ => {
+                return Err(LuaError::FromLuaConversionError {
+                    from: value.type_name(),
+                    to: "FsLockOptions".to_string(),
+                    message: Some(format!(
+                        "Invalid lock options - expected table, got {}",
+                        value.type_name()
+                    )),
+                });
+            }
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct FsWatchOptions {
+    pub(crate) recursive: bool,
+    pub(crate) debounce: Duration,
+}
+
+impl FromLua for FsWatchOptions {
+    fn from_lua(value: LuaValue, _: &Lua) -> LuaResult<Self> {
+        Ok(match value {
+            LuaValue::Nil => Self {
+                recursive: false,
+                debounce: Duration::ZERO,
+            },
+            LuaValue::Table(t) => {
+                let recursive: Option<bool> = t.get("recursive")?;
+                let debounce: Option<f64> = t.get("debounce")?;
+                let debounce = match debounce {
+                    None => Duration::ZERO,
+                    Some(secs) => Duration::try_from_secs_f64(secs).map_err(|_| {
+                        LuaError::RuntimeError(format!(
+                            "Invalid watch options - debounce must be a positive number, got {secs}"
+                        ))
+                    })?,
+                };
+                Self {
+                    recursive: recursive.unwrap_or(false),
+                    debounce,
+                }
+            }
+            _ => {
+                return Err(LuaError::FromLuaConversionError {
+                    from: value.type_name(),
+                    to: "FsWatchOptions".to_string(),
+                    message: Some(format!(
+                        "Invalid watch options - expected table, got {}",
+                        value.type_name()
+                    )),
+                });
+            }
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FsOpenMode {
+    #[default]
+    Read,
+    Write,
+    Append,
+    ReadWrite,
+    ReadWriteTruncate,
+    ReadAppend,
+}
+
+impl FsOpenMode {
+    pub fn all() -> &'static [Self] {
+        &[
+            Self::Read,
+            Self::Write,
+            Self::Append,
+            Self::ReadWrite,
+            Self::ReadWriteTruncate,
+            Self::ReadAppend,
+        ]
+    }
+
+    pub fn as_open_options(self) -> async_fs::OpenOptions {
+        let mut options = async_fs::OpenOptions::new();
+        match self {
+            Self::Read => options.read(true),
+            Self::Write => options.write(true).create(true).truncate(true),
+            Self::Append => options.append(true).create(true),
+            Self::ReadWrite => options.read(true).write(true),
+            Self::ReadWriteTruncate => options.read(true).write(true).create(true).truncate(true),
+            Self::ReadAppend => options.read(true).append(true).create(true),
+        };
+        options
+    }
+}
+
+impl fmt::Display for FsOpenMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match *self {
+            Self::Read => "r",
+            Self::Write => "w",
+            Self::Append => "a",
+            Self::ReadWrite => "r+",
+            Self::ReadWriteTruncate => "w+",
+            Self::ReadAppend => "a+",
+        };
+        f.write_str(s)
+    }
+}
+
+impl FromStr for FsOpenMode {
+    type Err = LuaError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // NOTE: The binary flag is accepted for compatibility with io.open
+        // in other Lua runtimes, but files are always opened in binary mode
+        Ok(match s.trim().replace('b', "").as_str() {
+            "r" => Self::Read,
+            "w" => Self::Write,
+            "a" => Self::Append,
+            "r+" => Self::ReadWrite,
+            "w+" => Self::ReadWriteTruncate,
+            "a+" => Self::ReadAppend,
+            _ => {
+                return Err(LuaError::RuntimeError(format!(
+                    "Invalid file open mode - got '{}', expected one of {}",
+                    s,
+                    FsOpenMode::all()
+                        .iter()
+                        .map(|k| format!("'{k}'"))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                )));
+            }
+        })
+    }
+}
+
+impl FromLua for FsOpenMode {
+    fn from_lua(value: LuaValue, _: &Lua) -> LuaResult<Self> {
+        match value {
+            LuaValue::Nil => Ok(Self::default()),
+            LuaValue::String(s) => s.to_str()?.parse(),
+            _ => Err(LuaError::FromLuaConversionError {
+                from: value.type_name(),
+                to: "FsOpenMode".to_string(),
+                message: Some(format!(
+                    "Invalid file open mode - expected string, got {}",
+                    value.type_name()
+                )),
+            }),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct FsWalkOptions {
+    pub(crate) max_depth: Option<usize>,
+    pub(crate) follow_symlinks: bool,
+    pub(crate) exclude: Vec<String>,
+}
+
+impl FromLua for FsWalkOptions {
+    fn from_lua(value: LuaValue, _: &Lua) -> LuaResult<Self> {
+        Ok(match value {
+            LuaValue::Nil => Self::default(),
+            LuaValue::Table(t) => {
+                let max_depth: Option<usize> = t.get("maxDepth")?;
+                let follow_symlinks: Option<bool> = t.get("followSymlinks")?;
+                let exclude: Option<Vec<String>> = t.get("exclude")?;
+                Self {
+                    max_depth,
+                    follow_symlinks: follow_symlinks.unwrap_or(false),
+                    exclude: exclude.unwrap_or_default(),
+                }
+            }
+            _ => {
+                return Err(LuaError::FromLuaConversionError {
+                    from: value.type_name(),
+                    to: "FsWalkOptions".to_string(),
+                    message: Some(format!(
+                        "Invalid walk options - expected table, got {}",
+                        value.type_name()
+                    )),
+                });
+            }
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FsPermissionsOptions {
+    pub(crate) read_only: Option<bool>,
+    pub(crate) mode: Option<u32>,
+}
+
+impl FromLua for FsPermissionsOptions {
+    fn from_lua(value: LuaValue, _: &Lua) -> LuaResult<Self> {
+        match value {
+            LuaValue::Table(t) => {
+                let read_only: Option<bool> = t.get("readOnly")?;
+                let mode: Option<u32> = t.get("mode")?;
+                if read_only.is_some() && mode.is_some() {
+                    return Err(LuaError::FromLuaConversionError {
+                        from: "table",
+                        to: "FsPermissionsOptions".to_string(),
+                        message: Some(
+                            "Invalid permissions - 'readOnly' and 'mode' can not be given together"
+                                .to_string(),
+                        ),
+                    });
+                }
+                Ok(Self { read_only, mode })
+            }
+            _ => Err(LuaError::FromLuaConversionError {
+                from: value.type_name(),
+                to: "FsPermissionsOptions".to_string(),
+                message: Some(format!(
+                    "Invalid permissions - expected table, got {}",
+                    value.type_name()
+                )),
+            }),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FsTimesOptions {
+    pub(crate) modified_at: Option<FileTime>,
+    pub(crate) accessed_at: Option<FileTime>,
+}
+
+fn file_time_from_lua(value: LuaValue) -> LuaResult<Option<FileTime>> {
+    let timestamp = match value {
+        LuaValue::Nil => return Ok(None),
+        LuaValue::Integer(i) => i as f64,
+        LuaValue::Number(n) => n,
+        LuaValue::UserData(ud) => ud.borrow::<DateTime>()?.to_unix_timestamp_float(),
+        _ => {
+            return Err(LuaError::FromLuaConversionError {
+                from: value.type_name(),
+                to: "FileTime".to_string(),
+                message: Some(format!(
+                    "Invalid time - expected DateTime or number, got {}",
+                    value.type_name()
+                )),
+            });
+        }
+    };
+    if !timestamp.is_finite() {
+        return Err(LuaError::FromLuaConversionError {
+            from: "number",
+            to: "FileTime".to_string(),
+            message: Some(format!(
+                "Invalid time - expected a finite number, got {timestamp}"
+            )),
+        });
+    }
+    let mut seconds = timestamp.floor() as i64;
+    let mut nanos = ((timestamp - timestamp.floor()) * 1_000_000_000f64).round() as u32;
+    // Rounding may result in a full second, which is not a valid amount of nanoseconds
+    if nanos >= 1_000_000_000 {
+        seconds = seconds.saturating_add(1);
+        nanos = 0;
+    }
+    Ok(Some(FileTime::from_unix_time(seconds, nanos)))
+}
+
+impl FromLua for FsTimesOptions {
+    fn from_lua(value: LuaValue, _: &Lua) -> LuaResult<Self> {
+        match value {
+            LuaValue::Table(t) => Ok(Self {
+                modified_at: file_time_from_lua(t.get("modifiedAt")?)?,
+                accessed_at: file_time_from_lua(t.get("accessedAt")?)?,
+            }),
+            _ => Err(LuaError::FromLuaConversionError {
+                from: value.type_name(),
+                to: "FsTimesOptions".to_string(),
+                message: Some(format!(
+                    "Invalid times - expected table, got {}",
+                    value.type_name()
+                )),
+            }),
+        }
+    }
+}
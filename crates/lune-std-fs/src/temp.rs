@@ -0,0 +1,208 @@
+use std::ffi::OsString;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use async_fs as fs;
+use mlua::prelude::*;
+
+use super::metadata::FsMetadataKind;
+use super::options::FsTempOptions;
+
+const DEFAULT_PREFIX: &str = "lune-";
+const MAX_CREATE_ATTEMPTS: usize = 64;
+
+static TEMP_NAME_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/**
+    Creates a file name that is unique within this process, and
+    very unlikely to collide with names from any other process.
+*/
+pub(crate) fn unique_temp_name(prefix: impl Into<OsString>, suffix: &str) -> OsString {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.subsec_nanos());
+    let count = TEMP_NAME_COUNTER.fetch_add(1, Ordering::Relaxed);
+
+    let mut name = prefix.into();
+    name.push(format!("{:x}{nanos:08x}{count}", std::process::id()));
+    name.push(suffix);
+    name
+}
+
+// Inner (plumbing) implementation
+
+/**
+    A temporary file or directory, which is removed when dropped unless it should be kept.
+
+    Since this is dropped both when the Lua handle is garbage collected and when the
+    Lua state itself is closed, temporary paths never outlive the runtime that made them.
+*/
+#[derive(Debug)]
+struct FsTempPathInner {
+    path: PathBuf,
+    kind: FsMetadataKind,
+    keep: bool,
+    closed: bool,
+}
+
+impl FsTempPathInner {
+    fn cleanup(&mut self) {
+        if !self.keep && !self.closed {
+            self.closed = true;
+            // NOTE: There is nowhere to report errors to here,
+            // cleanup of temporary paths is best-effort anyway
+            let result = if self.kind == FsMetadataKind::Dir {
+                std::fs::remove_dir_all(&self.path)
+            } else {
+                std::fs::remove_file(&self.path)
+            };
+            result.ok();
+        }
+    }
+}
+
+impl Drop for FsTempPathInner {
+    fn drop(&mut self) {
+        self.cleanup();
+    }
+}
+
+/**
+    All temporary paths created in a Lua state, stored as app data.
+
+    Handles may be kept alive until the process exits, for example by being stored
+    in a global, so this lets the runtime clean them up when it shuts down.
+*/
+#[derive(Debug, Default)]
+struct FsTempRegistry {
+    paths: Vec<Weak<Mutex<FsTempPathInner>>>,
+}
+
+/**
+    Removes all temporary paths created in the given Lua state
+    that have not yet been closed, and that should not be kept.
+*/
+pub fn cleanup_temp_paths(lua: &Lua) {
+    if let Some(registry) = lua.remove_app_data::<FsTempRegistry>() {
+        for inner in registry.paths.iter().filter_map(Weak::upgrade) {
+            inner.lock().expect("poisoned lock").cleanup();
+        }
+    }
+}
+
+// Outer (lua-accessible, clonable) implementation
+
+/**
+    A handle to a temporary file or directory, created using `fs.tempFile` or `fs.tempDir`.
+*/
+#[derive(Debug, Clone)]
+pub struct FsTempPath {
+    inner: Arc<Mutex<FsTempPathInner>>,
+    path: PathBuf,
+}
+
+impl FsTempPath {
+    pub async fn create(
+        lua: &Lua,
+        kind: FsMetadataKind,
+        options: FsTempOptions,
+    ) -> LuaResult<Self> {
+        let dir = options.dir.map_or_else(std::env::temp_dir, PathBuf::from);
+        let prefix = options.prefix.as_deref().unwrap_or(DEFAULT_PREFIX);
+        let suffix = options.suffix.as_deref().unwrap_or_default();
+
+        let mut attempts = 0;
+        let path = loop {
+            let path = dir.join(unique_temp_name(prefix, suffix));
+            match create_exclusive(&path, kind).await {
+                Ok(()) => break path,
+                Err(e) if e.kind() == ErrorKind::AlreadyExists => {
+                    attempts += 1;
+                    if attempts >= MAX_CREATE_ATTEMPTS {
+                        return Err(LuaError::RuntimeError(format!(
+                            "Failed to create a unique temporary {kind} in '{}'",
+                            dir.display()
+                        )));
+                    }
+                }
+                Err(e) => return Err(e.into()),
+            }
+        };
+
+        let inner = Arc::new(Mutex::new(FsTempPathInner {
+            path: path.clone(),
+            kind,
+            keep: options.keep,
+            closed: false,
+        }));
+
+        if lua.app_data_ref::<FsTempRegistry>().is_none() {
+            lua.set_app_data(FsTempRegistry::default());
+        }
+        let mut registry = lua
+            .app_data_mut::<FsTempRegistry>()
+            .expect("registry was just set");
+        registry.paths.retain(|path| path.strong_count() > 0);
+        registry.paths.push(Arc::downgrade(&inner));
+
+        Ok(Self { inner, path })
+    }
+}
+
+async fn create_exclusive(path: &Path, kind: FsMetadataKind) -> std::io::Result<()> {
+    if kind == FsMetadataKind::Dir {
+        fs::create_dir(path).await
+    } else {
+        fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(path)
+            .await
+            .map(drop)
+    }
+}
+
+impl LuaUserData for FsTempPath {
+    fn add_fields<F: LuaUserDataFields<Self>>(fields: &mut F) {
+        fields.add_field_method_get("path", |_, this| {
+            Ok(this.path.to_string_lossy().into_owned())
+        });
+        fields.add_field_method_get("kind", |_, this| {
+            Ok(this.inner.lock().expect("poisoned lock").kind)
+        });
+        fields.add_field_method_get("closed", |_, this| {
+            Ok(this.inner.lock().expect("poisoned lock").closed)
+        });
+    }
+
+    fn add_methods<M: LuaUserDataMethods<Self>>(methods: &mut M) {
+        methods.add_async_method("close", |_, this, (): ()| {
+            let inner = Arc::clone(&this.inner);
+            async move {
+                let (path, kind) = {
+                    let mut inner = inner.lock().expect("poisoned lock");
+                    if inner.closed {
+                        return Ok(());
+                    }
+                    inner.closed = true;
+                    if inner.keep {
+                        return Ok(());
+                    }
+                    (inner.path.clone(), inner.kind)
+                };
+                let result = if kind == FsMetadataKind::Dir {
+                    fs::remove_dir_all(&path).await
+                } else {
+                    fs::remove_file(&path).await
+                };
+                match result {
+                    Err(e) if e.kind() == ErrorKind::NotFound => Ok(()),
+                    result => result.into_lua_err(),
+                }
+            }
+        });
+    }
+}
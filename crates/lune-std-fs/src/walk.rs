@@ -0,0 +1,281 @@
+use std::collections::{HashSet, VecDeque};
+use std::fs::FileType as StdFileType;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+
+use async_fs as fs;
+use async_lock::Mutex as AsyncMutex;
+use futures_lite::prelude::*;
+use globset::{GlobBuilder, GlobMatcher};
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use mlua::prelude::*;
+
+use super::metadata::FsMetadataKind;
+use super::options::FsWalkOptions;
+
+const GLOB_META_CHARS: &[char] = &['*', '?', '[', ']', '{', '}'];
+
+#[derive(Debug, Clone)]
+pub struct FsWalkEntry {
+    pub(crate) path: PathBuf,
+    pub(crate) kind: FsMetadataKind,
+    pub(crate) depth: usize,
+}
+
+impl IntoLua for FsWalkEntry {
+    fn into_lua(self, lua: &Lua) -> LuaResult<LuaValue> {
+        let name = self
+            .path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned());
+
+        let tab = lua.create_table_with_capacity(0, 4)?;
+        tab.set("path", self.path.to_string_lossy().into_owned())?;
+        tab.set("name", name)?;
+        tab.set("kind", self.kind)?;
+        tab.set("depth", self.depth)?;
+        tab.set_readonly(true);
+        Ok(LuaValue::Table(tab))
+    }
+}
+
+// Inner (plumbing) implementation
+
+/**
+    A lazy, depth-first directory walker.
+
+    Entries in each directory are sorted by name, and each directory is
+    only read once the walker reaches it, so walking a large tree will
+    never hold more than the directories along the current path in memory.
+*/
+#[derive(Debug)]
+pub(crate) struct FsWalkerInner {
+    root: PathBuf,
+    options: FsWalkOptions,
+    exclude: Option<Gitignore>,
+    matcher: Option<GlobMatcher>,
+    stack: Vec<VecDeque<(PathBuf, StdFileType)>>,
+    visited: HashSet<PathBuf>,
+    started: bool,
+}
+
+impl FsWalkerInner {
+    pub(crate) fn new(root: impl Into<PathBuf>, options: FsWalkOptions) -> LuaResult<Self> {
+        let root = root.into();
+
+        let exclude = if options.exclude.is_empty() {
+            None
+        } else {
+            let mut builder = GitignoreBuilder::new(&root);
+            for pattern in &options.exclude {
+                builder.add_line(None, pattern).map_err(|e| {
+                    LuaError::RuntimeError(format!("Invalid exclude pattern '{pattern}'\n{e}"))
+                })?;
+            }
+            Some(builder.build().into_lua_err()?)
+        };
+
+        Ok(Self {
+            root,
+            options,
+            exclude,
+            matcher: None,
+            stack: Vec::new(),
+            visited: HashSet::new(),
+            started: false,
+        })
+    }
+
+    /**
+        Creates a walker that only yields entries matching the given glob pattern.
+
+        The walk starts at the longest prefix of the pattern that contains no glob
+        characters, and will not go deeper than the pattern itself unless it contains `**`.
+    */
+    fn glob(pattern: &str, mut options: FsWalkOptions) -> LuaResult<Self> {
+        let pattern_path = Path::new(pattern);
+
+        let mut root = PathBuf::new();
+        let mut rest = Vec::new();
+        for component in pattern_path.components() {
+            let is_literal = match component {
+                Component::Normal(s) => !s.to_string_lossy().contains(GLOB_META_CHARS),
+                _ => true,
+            };
+            if rest.is_empty() && is_literal {
+                root.push(component);
+            } else {
+                rest.push(component.as_os_str().to_string_lossy().into_owned());
+            }
+        }
+
+        // Globs without any wildcards should still match the file they point to
+        if rest.is_empty()
+            && let Some(name) = root.file_name()
+        {
+            rest.push(globset::escape(&name.to_string_lossy()));
+            root.pop();
+        }
+
+        let has_recursive = rest.iter().any(|c| c.contains("**"));
+        if !has_recursive {
+            options.max_depth = Some(options.max_depth.map_or(rest.len(), |d| d.min(rest.len())));
+        }
+
+        let matcher = GlobBuilder::new(&rest.join("/"))
+            .literal_separator(true)
+            .build()
+            .map_err(|e| LuaError::RuntimeError(format!("Invalid glob pattern '{pattern}'\n{e}")))?
+            .compile_matcher();
+
+        let mut walker = Self::new(root, options)?;
+        walker.matcher = Some(matcher);
+        Ok(walker)
+    }
+
+    async fn push_dir(&mut self, dir: &Path) -> LuaResult<()> {
+        if self.options.follow_symlinks {
+            // Guard against symlink cycles by never entering the same directory twice
+            let canonical = fs::canonicalize(read_path(dir)).await?;
+            if !self.visited.insert(canonical) {
+                return Ok(());
+            }
+        }
+
+        let mut entries = Vec::new();
+        let mut reader = fs::read_dir(read_path(dir)).await?;
+        while let Some(entry) = reader.try_next().await? {
+            let file_type = entry.file_type().await?;
+            entries.push((dir.join(entry.file_name()), file_type));
+        }
+        entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+        self.stack.push(entries.into());
+        Ok(())
+    }
+
+    async fn resolve_kind(&self, path: &Path, file_type: StdFileType) -> FsMetadataKind {
+        if file_type.is_symlink() && self.options.follow_symlinks {
+            match fs::metadata(path).await {
+                Ok(meta) => kind_from_file_type(meta.file_type()),
+                // Broken symlinks are yielded as symlinks, even when following
+                Err(_) => FsMetadataKind::Symlink,
+            }
+        } else {
+            kind_from_file_type(file_type)
+        }
+    }
+
+    fn is_excluded(&self, path: &Path, is_dir: bool) -> bool {
+        self.exclude
+            .as_ref()
+            .is_some_and(|exclude| exclude.matched(path, is_dir).is_ignore())
+    }
+
+    fn is_matched(&self, path: &Path) -> bool {
+        self.matcher.as_ref().is_none_or(|matcher| {
+            path.strip_prefix(&self.root)
+                .is_ok_and(|relative| matcher.is_match(relative))
+        })
+    }
+
+    pub(crate) async fn next(&mut self) -> LuaResult<Option<FsWalkEntry>> {
+        if !self.started {
+            self.started = true;
+            match fs::metadata(read_path(&self.root)).await {
+                Ok(meta) if meta.is_dir() => {
+                    let root = self.root.clone();
+                    self.push_dir(&root).await?;
+                }
+                // Globs that point into a missing directory
+                // should simply yield nothing, and not error
+                Ok(_) | Err(_) if self.matcher.is_some() => return Ok(None),
+                Ok(_) => {
+                    return Err(LuaError::RuntimeError(format!(
+                        "The path '{}' is not a directory",
+                        self.root.display()
+                    )));
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+
+        while let Some(entries) = self.stack.last_mut() {
+            let Some((path, file_type)) = entries.pop_front() else {
+                self.stack.pop();
+                continue;
+            };
+
+            let depth = self.stack.len();
+            let kind = self.resolve_kind(&path, file_type).await;
+            if self.is_excluded(&path, kind == FsMetadataKind::Dir) {
+                continue;
+            }
+
+            let can_descend = self.options.max_depth.is_none_or(|max| depth < max);
+            if kind == FsMetadataKind::Dir && can_descend {
+                self.push_dir(&path).await?;
+            }
+
+            if self.is_matched(&path) {
+                return Ok(Some(FsWalkEntry { path, kind, depth }));
+            }
+        }
+
+        Ok(None)
+    }
+}
+
+fn read_path(path: &Path) -> &Path {
+    if path.as_os_str().is_empty() {
+        Path::new(".")
+    } else {
+        path
+    }
+}
+
+fn kind_from_file_type(file_type: StdFileType) -> FsMetadataKind {
+    // NOTE: Special files such as sockets and fifos are reported as
+    // files here, since we should not panic while walking a directory
+    if file_type.is_dir() {
+        FsMetadataKind::Dir
+    } else if file_type.is_symlink() {
+        FsMetadataKind::Symlink
+    } else {
+        FsMetadataKind::File
+    }
+}
+
+// Outer (lua-accessible, clonable) implementation
+
+#[derive(Debug, Clone)]
+pub struct FsWalker {
+    inner: Arc<AsyncMutex<FsWalkerInner>>,
+}
+
+impl FsWalker {
+    pub fn new(root: impl Into<PathBuf>, options: FsWalkOptions) -> LuaResult<Self> {
+        FsWalkerInner::new(root, options).map(Self::from)
+    }
+
+    pub fn glob(pattern: &str, options: FsWalkOptions) -> LuaResult<Self> {
+        FsWalkerInner::glob(pattern, options).map(Self::from)
+    }
+}
+
+impl From<FsWalkerInner> for FsWalker {
+    fn from(inner: FsWalkerInner) -> Self {
+        Self {
+            inner: Arc::new(AsyncMutex::new(inner)),
+        }
+    }
+}
+
+impl LuaUserData for FsWalker {
+    fn add_methods<M: LuaUserDataMethods<Self>>(methods: &mut M) {
+        methods.add_async_method("next", |_, this, (): ()| {
+            let inner = this.inner.clone();
+            async move { inner.lock().await.next().await }
+        });
+    }
+}
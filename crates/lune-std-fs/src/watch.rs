@@ -0,0 +1,236 @@
+use std::collections::{HashSet, VecDeque};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use async_channel::{Receiver, Sender, unbounded};
+use async_io::Timer;
+use async_lock::Mutex as AsyncMutex;
+use futures_lite::prelude::*;
+use mlua::prelude::*;
+use notify::event::{EventKind, ModifyKind, RenameMode};
+use notify::{Event, RecommendedWatcher, RecursiveMode, Watcher};
+
+use super::options::FsWatchOptions;
+
+/*
+    Events that keep arriving would otherwise extend the debounce window forever,
+    so a batch is delivered once it is either this many times older than the
+    debounce duration, or has collected this many distinct events
+*/
+const MAX_DEBOUNCE_WINDOW_FACTOR: u32 = 10;
+const MAX_DEBOUNCE_EVENTS: usize = 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FsWatchEventKind {
+    Create,
+    Modify,
+    Remove,
+    Rename,
+}
+
+impl FsWatchEventKind {
+    fn from_notify(kind: EventKind) -> Option<Self> {
+        // NOTE: Paired renames are also delivered as separate events for
+        // both the old and the new path, so we skip the combined event
+        match kind {
+            EventKind::Create(_) => Some(Self::Create),
+            EventKind::Modify(ModifyKind::Name(RenameMode::Both))
+            | EventKind::Access(_)
+            | EventKind::Other => None,
+            EventKind::Modify(ModifyKind::Name(_)) => Some(Self::Rename),
+            EventKind::Modify(_) | EventKind::Any => Some(Self::Modify),
+            EventKind::Remove(_) => Some(Self::Remove),
+        }
+    }
+}
+
+impl fmt::Display for FsWatchEventKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::Create => "create",
+                Self::Modify => "modify",
+                Self::Remove => "remove",
+                Self::Rename => "rename",
+            }
+        )
+    }
+}
+
+impl IntoLua for FsWatchEventKind {
+    fn into_lua(self, lua: &Lua) -> LuaResult<LuaValue> {
+        self.to_string().into_lua(lua)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct FsWatchEvent {
+    pub(crate) kind: FsWatchEventKind,
+    pub(crate) paths: Vec<PathBuf>,
+}
+
+impl FsWatchEvent {
+    fn from_notify(event: Event) -> Option<Self> {
+        let kind = FsWatchEventKind::from_notify(event.kind)?;
+        Some(Self {
+            kind,
+            paths: event.paths,
+        })
+    }
+}
+
+impl IntoLua for FsWatchEvent {
+    fn into_lua(self, lua: &Lua) -> LuaResult<LuaValue> {
+        let paths = lua.create_sequence_from(
+            self.paths
+                .iter()
+                .map(|path| path.to_string_lossy().into_owned()),
+        )?;
+        paths.set_readonly(true);
+
+        let tab = lua.create_table_with_capacity(0, 2)?;
+        tab.set("kind", self.kind)?;
+        tab.set("paths", paths)?;
+        tab.set_readonly(true);
+        Ok(LuaValue::Table(tab))
+    }
+}
+
+/**
+    A filesystem watcher, delivering events from a background
+    thread to Lua through an async channel.
+
+    Events are only received while the underlying watcher is alive,
+    closing the watcher drops it and also closes the channel.
+*/
+#[derive(Debug, Clone)]
+pub struct FsWatcher {
+    watcher: Arc<Mutex<Option<RecommendedWatcher>>>,
+    events_rx: Receiver<Result<FsWatchEvent, String>>,
+    pending: Arc<AsyncMutex<VecDeque<Result<FsWatchEvent, String>>>>,
+    debounce: Duration,
+}
+
+impl FsWatcher {
+    pub fn new(path: impl AsRef<Path>, options: FsWatchOptions) -> LuaResult<Self> {
+        let path = path.as_ref();
+
+        let (events_tx, events_rx) = unbounded();
+        let mut watcher = notify::recommended_watcher(move |res| handle_event(&events_tx, res))
+            .map_err(|e| {
+                LuaError::RuntimeError(format!("Failed to create filesystem watcher\n{e}"))
+            })?;
+
+        let mode = if options.recursive {
+            RecursiveMode::Recursive
+        } else {
+            RecursiveMode::NonRecursive
+        };
+        watcher.watch(path, mode).map_err(|e| {
+            LuaError::RuntimeError(format!(
+                "Failed to watch the path '{}'\n{e}",
+                path.display()
+            ))
+        })?;
+
+        Ok(Self {
+            watcher: Arc::new(Mutex::new(Some(watcher))),
+            events_rx,
+            pending: Arc::new(AsyncMutex::new(VecDeque::new())),
+            debounce: options.debounce,
+        })
+    }
+
+    fn close(&self) {
+        // NOTE: Dropping the watcher also drops the event sender, which
+        // will wake up any threads currently waiting for the next event
+        self.watcher.lock().unwrap().take();
+    }
+
+    fn is_closed(&self) -> bool {
+        self.watcher.lock().unwrap().is_none()
+    }
+
+    async fn next(&self) -> LuaResult<Option<FsWatchEvent>> {
+        let mut pending = self.pending.lock().await;
+
+        if self.is_closed() {
+            pending.clear();
+            return Ok(None);
+        }
+
+        if pending.is_empty() {
+            let Ok(first) = self.events_rx.recv().await else {
+                return Ok(None);
+            };
+            let mut seen = HashSet::new();
+            if let Ok(event) = &first {
+                seen.insert(event.clone());
+            }
+            pending.push_back(first);
+
+            // Keep collecting events until no new ones arrive within the debounce
+            // window, skipping any exact duplicates of events we already have
+            if !self.debounce.is_zero() {
+                let batch_deadline = Instant::now() + self.debounce * MAX_DEBOUNCE_WINDOW_FACTOR;
+                while pending.len() < MAX_DEBOUNCE_EVENTS {
+                    let deadline = (Instant::now() + self.debounce).min(batch_deadline);
+                    let next = async { self.events_rx.recv().await.ok() }
+                        .or(async {
+                            Timer::at(deadline).await;
+                            None
+                        })
+                        .await;
+                    let Some(next) = next else {
+                        break;
+                    };
+                    let is_duplicate = match &next {
+                        Ok(event) => !seen.insert(event.clone()),
+                        Err(_) => false,
+                    };
+                    if !is_duplicate {
+                        pending.push_back(next);
+                    }
+                }
+            }
+        }
+
+        pending
+            .pop_front()
+            .transpose()
+            .map_err(LuaError::RuntimeError)
+    }
+}
+
+impl LuaUserData for FsWatcher {
+    fn add_fields<F: LuaUserDataFields<Self>>(fields: &mut F) {
+        fields.add_field_method_get("closed", |_, this| Ok(this.is_closed()));
+    }
+
+    fn add_methods<M: LuaUserDataMethods<Self>>(methods: &mut M) {
+        methods.add_async_method("next", |_, this, (): ()| {
+            let this = Self::clone(&this);
+            async move { this.next().await }
+        });
+        methods.add_method("close", |_, this, (): ()| {
+            this.close();
+            Ok(())
+        });
+    }
+}
+
+fn handle_event(tx: &Sender<Result<FsWatchEvent, String>>, res: notify::Result<Event>) {
+    let msg = match res {
+        Ok(event) => match FsWatchEvent::from_notify(event) {
+            Some(event) => Ok(event),
+            None => return,
+        },
+        Err(e) => Err(format!("Failed to receive filesystem event\n{e}")),
+    };
+    // Will only error if the watcher has been closed
+    let _ = tx.try_send(msg);
+}
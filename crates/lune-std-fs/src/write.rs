@@ -0,0 +1,106 @@
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+
+use async_fs as fs;
+use futures_lite::prelude::*;
+use mlua::prelude::*;
+
+use super::options::FsWriteFileOptions;
+use super::temp::unique_temp_name;
+
+/**
+    Creates a unique path for a temporary file, in the same directory as the given path.
+
+    The temporary file must be in the same directory as the target for the final
+    rename to be atomic, since renames across filesystems are not guaranteed to be.
+*/
+fn temp_sibling_path(path: &Path) -> PathBuf {
+    let mut prefix = OsString::from(".");
+    prefix.push(path.file_name().unwrap_or_default());
+    prefix.push(".");
+    path.with_file_name(unique_temp_name(prefix, ".tmp"))
+}
+
+async fn write_temp_file(temp: &Path, target: &Path, contents: &[u8]) -> std::io::Result<()> {
+    let mut file = fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(temp)
+        .await?;
+
+    // Keep any permissions of the file we are about to replace
+    if let Ok(meta) = fs::metadata(target).await {
+        file.set_permissions(meta.permissions()).await?;
+    }
+
+    file.write_all(contents).await?;
+    file.sync_all().await?;
+
+    Ok(())
+}
+
+async fn write_atomic(path: &Path, contents: &[u8], create_new: bool) -> std::io::Result<()> {
+    let temp = temp_sibling_path(path);
+
+    let result = match write_temp_file(&temp, path, contents).await {
+        // Hard links never replace an existing file, unlike renames,
+        // so we can use them to atomically create a new file instead
+        Ok(()) if create_new => fs::hard_link(&temp, path).await,
+        Ok(()) => fs::rename(&temp, path).await,
+        Err(e) => Err(e),
+    };
+
+    if create_new || result.is_err() {
+        fs::remove_file(&temp).await.ok();
+    }
+    result?;
+
+    // Make sure the rename itself is persisted, and not just the contents
+    #[cfg(unix)]
+    if let Some(parent) = path.parent() {
+        let parent = if parent.as_os_str().is_empty() {
+            Path::new(".")
+        } else {
+            parent
+        };
+        if let Ok(dir) = fs::File::open(parent).await {
+            dir.sync_all().await.ok();
+        }
+    }
+
+    Ok(())
+}
+
+pub async fn write_file(
+    path: impl AsRef<Path>,
+    contents: impl AsRef<[u8]>,
+    options: FsWriteFileOptions,
+) -> LuaResult<()> {
+    let path = path.as_ref();
+    let contents = contents.as_ref();
+
+    if options.atomic {
+        return write_atomic(path, contents, options.create_new)
+            .await
+            .into_lua_err();
+    }
+
+    let mut open_options = fs::OpenOptions::new();
+    open_options.write(true);
+    if options.create_new {
+        open_options.create_new(true);
+    } else {
+        open_options.create(true);
+    }
+    if options.append {
+        open_options.append(true);
+    } else {
+        open_options.truncate(true);
+    }
+
+    let mut file = open_options.open(path).await?;
+    file.write_all(contents).await?;
+    file.flush().await?;
+
+    Ok(())
+}
@@ -7,7 +7,9 @@ use hyper::{
 
 use mlua::prelude::*;
 
-use lune_std_serde::{CompressDecompressFormat, decompress};
+use lune_std_serde::{
+    CompressDecompressFormat, CompressDecompressOptions, CompressDecompressStream, decompress,
+};
 
 pub async fn handle_incoming_body(
     headers: &HeaderMap,
@@ -17,11 +19,7 @@ pub async fn handle_incoming_body(
     let mut body = body.collect().await.into_lua_err()?.to_bytes();
 
     let was_decompressed = if should_decompress {
-        let decompress_format = headers
-            .get(CONTENT_ENCODING)
-            .and_then(|value| value.to_str().ok())
-            .and_then(CompressDecompressFormat::detect_from_header_str);
-        if let Some(format) = decompress_format {
+        if let Some(format) = content_encoding_format(headers) {
             body = Bytes::from(decompress(body, format).await?);
             true
         } else {
@@ -33,3 +31,24 @@ pub async fn handle_incoming_body(
 
     Ok((body, was_decompressed))
 }
+
+/**
+    Creates a streaming decompressor for an incoming body that is
+    read in chunks, if the body was compressed using a known format.
+*/
+pub fn incoming_body_decompressor(
+    headers: &HeaderMap,
+) -> LuaResult<Option<CompressDecompressStream>> {
+    content_encoding_format(headers)
+        .map(|format| {
+            CompressDecompressStream::decompressor(format, &CompressDecompressOptions::default())
+        })
+        .transpose()
+}
+
+fn content_encoding_format(headers: &HeaderMap) -> Option<CompressDecompressFormat> {
+    headers
+        .get(CONTENT_ENCODING)
+        .and_then(|value| value.to_str().ok())
+        .and_then(CompressDecompressFormat::detect_from_header_str)
+}
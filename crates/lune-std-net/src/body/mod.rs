@@ -4,8 +4,12 @@ mod cursor;
 mod incoming;
 mod inner;
 mod readable;
+mod stream;
+mod writer;
 
 pub use self::cursor::ReadableBodyCursor;
-pub use self::incoming::handle_incoming_body;
+pub use self::incoming::{handle_incoming_body, incoming_body_decompressor};
 pub use self::inner::ReadableBodyInner;
 pub use self::readable::ReadableBody;
+pub use self::stream::IncomingBodyStream;
+pub use self::writer::BodyWriter;
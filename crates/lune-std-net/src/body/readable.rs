@@ -1,12 +1,16 @@
-use std::convert::Infallible;
+use std::io::Error;
 use std::pin::Pin;
-use std::task::{Context, Poll};
+use std::task::{Context, Poll, ready};
 
+use async_channel::{Receiver, bounded};
+use futures_lite::Stream;
 use hyper::body::{Body, Bytes, Frame, SizeHint};
 use mlua::prelude::*;
 
 use super::cursor::ReadableBodyCursor;
 
+type ReadableBodyStream = Pin<Box<Receiver<Result<Bytes, Error>>>>;
+
 /**
     Zero-copy wrapper for a readable body.
 
@@ -15,15 +19,29 @@ use super::cursor::ReadableBodyCursor;
 
     If the body was created from a `Vec<u8>`, `Bytes`, or a `String`, reading
     bytes is always safe and does not go through any additional indirections.
+
+    A body may also be streamed, in which case chunks are received over time
+    from a `BodyWriter`, and the body is not available as a single slice.
 */
-#[derive(Debug, Clone)]
+#[derive(Debug)]
 pub struct ReadableBody {
     cursor: Option<ReadableBodyCursor>,
+    stream: Option<ReadableBodyStream>,
 }
 
 impl ReadableBody {
     pub const fn empty() -> Self {
-        Self { cursor: None }
+        Self {
+            cursor: None,
+            stream: None,
+        }
+    }
+
+    pub(super) fn from_stream(receiver: Receiver<Result<Bytes, Error>>) -> Self {
+        Self {
+            cursor: None,
+            stream: Some(Box::pin(receiver)),
+        }
     }
 
     pub fn as_slice(&self) -> &[u8] {
@@ -41,22 +59,56 @@ impl ReadableBody {
     }
 }
 
+impl Clone for ReadableBody {
+    fn clone(&self) -> Self {
+        // NOTE: Each chunk of a streamed body can only be received once, so a
+        // clone must not share the stream, since the chunks would be split
+        // between the two - sending the clone instead aborts with an error,
+        // which is better than the client silently receiving a partial body
+        let stream = self.stream.as_ref().map(|_| {
+            let (sender, receiver) = bounded(1);
+            let error = Error::other("Streamed body can not be cloned");
+            sender.try_send(Err(error)).ok();
+            Box::pin(receiver) as ReadableBodyStream
+        });
+        Self {
+            cursor: self.cursor.clone(),
+            stream,
+        }
+    }
+}
+
 impl Body for ReadableBody {
     type Data = ReadableBodyCursor;
-    type Error = Infallible;
+    type Error = Error;
 
     fn poll_frame(
         mut self: Pin<&mut Self>,
-        _cx: &mut Context<'_>,
+        cx: &mut Context<'_>,
     ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
-        Poll::Ready(self.cursor.take().map(|d| Ok(Frame::data(d))))
+        if let Some(cursor) = self.cursor.take() {
+            return Poll::Ready(Some(Ok(Frame::data(cursor))));
+        }
+        let Some(stream) = self.stream.as_mut() else {
+            return Poll::Ready(None);
+        };
+        let next = ready!(stream.as_mut().poll_next(cx));
+        if !matches!(next, Some(Ok(_))) {
+            self.stream = None;
+        }
+        Poll::Ready(next.map(|res| res.map(|b| Frame::data(b.into()))))
     }
 
     fn is_end_stream(&self) -> bool {
-        self.cursor.is_none()
+        self.cursor.is_none() && self.stream.is_none()
     }
 
     fn size_hint(&self) -> SizeHint {
+        if self.stream.is_some() {
+            // Streamed bodies have an unknown size,
+            // which makes HTTP/1 use chunked encoding
+            return SizeHint::default();
+        }
         self.cursor.as_ref().map_or_else(
             || SizeHint::with_exact(0),
             |c| SizeHint::with_exact(c.len() as u64),
@@ -71,6 +123,7 @@ where
     fn from(value: T) -> Self {
         Self {
             cursor: Some(value.into()),
+            stream: None,
         }
     }
 }
@@ -82,6 +135,7 @@ where
     fn from(value: Option<T>) -> Self {
         Self {
             cursor: value.map(Into::into),
+            stream: None,
         }
     }
 }
@@ -0,0 +1,172 @@
+use std::{path::PathBuf, sync::Arc};
+
+use async_lock::Mutex as AsyncMutex;
+use blocking::{Unblock, unblock};
+use futures_lite::prelude::*;
+use http_body_util::BodyExt;
+use hyper::body::{Bytes, Incoming};
+
+use mlua::prelude::*;
+
+use lune_std_serde::CompressDecompressStream;
+
+const DEFAULT_BUFFER_SIZE: usize = 1024;
+
+// Inner (plumbing) implementation
+
+#[derive(Debug)]
+struct IncomingBodyStreamInner {
+    body: Incoming,
+    // Bytes from the last received frame that
+    // were not yet given to Lua by a read call
+    buffered: Bytes,
+    // Decompressor for the body, if it was compressed
+    // and should be decompressed as it is being read
+    decoder: Option<CompressDecompressStream>,
+}
+
+impl IncomingBodyStreamInner {
+    async fn read(&mut self, size: usize) -> LuaResult<Option<Bytes>> {
+        while self.buffered.is_empty() {
+            match self.body.frame().await {
+                None => match self.decoder.take() {
+                    // Decompressors may hold on to some output until the body has ended
+                    Some(decoder) => self.buffered = Bytes::from(decoder.finish().await?),
+                    None => return Ok(None),
+                },
+                Some(frame) => {
+                    // Trailers and other non-data frames are skipped
+                    if let Ok(data) = frame.into_lua_err()?.into_data() {
+                        self.buffered = match &self.decoder {
+                            Some(decoder) => Bytes::from(decoder.write(&data).await?),
+                            None => data,
+                        };
+                    }
+                }
+            }
+        }
+        let take = size.min(self.buffered.len());
+        Ok(Some(self.buffered.split_to(take)))
+    }
+}
+
+// Outer (lua-accessible, clonable) implementation
+
+/**
+    A readable stream for an incoming body that has not yet been received.
+
+    Data is only received from the connection as it is being read,
+    and is never buffered in its entirety, meaning that this can be
+    used for large bodies that would not otherwise fit into memory.
+*/
+#[derive(Debug, Clone)]
+pub struct IncomingBodyStream {
+    inner: Arc<AsyncMutex<Option<IncomingBodyStreamInner>>>,
+}
+
+impl IncomingBodyStream {
+    /**
+        Creates a new stream for the given incoming body, which is
+        decompressed as it is being read if a decompressor is given.
+    */
+    pub fn new(body: Incoming, decoder: Option<CompressDecompressStream>) -> Self {
+        Self {
+            inner: Arc::new(AsyncMutex::new(Some(IncomingBodyStreamInner {
+                body,
+                buffered: Bytes::new(),
+                decoder,
+            }))),
+        }
+    }
+
+    /**
+        Reads up to `size` bytes from the stream,
+        returning `None` once the stream has ended.
+    */
+    pub async fn read(&self, size: usize) -> LuaResult<Option<Bytes>> {
+        let mut inner = self.inner.lock().await;
+        match inner.as_mut() {
+            Some(inner) => inner.read(size).await,
+            None => Ok(None),
+        }
+    }
+
+    /**
+        Closes the stream, discarding any data that has not yet been read.
+    */
+    pub async fn close(&self) {
+        self.inner.lock().await.take();
+    }
+
+    async fn pipe_to_file(&self, path: PathBuf) -> LuaResult<u64> {
+        let file = unblock(move || std::fs::File::create(path)).await?;
+        let mut file = Unblock::new(file);
+
+        let mut written = 0;
+        while let Some(chunk) = self.read(usize::MAX).await? {
+            file.write_all(&chunk).await?;
+            written += chunk.len() as u64;
+        }
+
+        file.flush().await?;
+        Ok(written)
+    }
+
+    async fn pipe_to_writer(&self, lua: &Lua, writer: impl LuaObjectLike) -> LuaResult<u64> {
+        let mut written = 0;
+        while let Some(chunk) = self.read(usize::MAX).await? {
+            let data = lua.create_string(&chunk)?;
+            writer.call_async_method::<()>("write", data).await?;
+            written += chunk.len() as u64;
+        }
+        Ok(written)
+    }
+}
+
+impl LuaUserData for IncomingBodyStream {
+    fn add_methods<M: LuaUserDataMethods<Self>>(methods: &mut M) {
+        methods.add_async_method(
+            "read",
+            |lua, this, (size, as_buffer): (Option<usize>, Option<bool>)| {
+                let this = this.clone();
+                let size = size.unwrap_or(DEFAULT_BUFFER_SIZE);
+                async move {
+                    if size == 0 {
+                        return Ok(LuaValue::String(lua.create_string("")?));
+                    }
+                    match this.read(size).await? {
+                        None => Ok(LuaValue::Nil),
+                        Some(bytes) if as_buffer.unwrap_or(false) => {
+                            Ok(LuaValue::Buffer(lua.create_buffer(bytes)?))
+                        }
+                        Some(bytes) => Ok(LuaValue::String(lua.create_string(bytes)?)),
+                    }
+                }
+            },
+        );
+        methods.add_async_method("pipeTo", |lua, this, target: LuaValue| {
+            let this = this.clone();
+            async move {
+                match target {
+                    LuaValue::String(path) => {
+                        let path = PathBuf::from(path.to_str()?.to_string());
+                        this.pipe_to_file(path).await
+                    }
+                    LuaValue::Table(writer) => this.pipe_to_writer(&lua, writer).await,
+                    LuaValue::UserData(writer) => this.pipe_to_writer(&lua, writer).await,
+                    v => Err(LuaError::RuntimeError(format!(
+                        "Invalid pipe target - expected file path or writer, got {}",
+                        v.type_name()
+                    ))),
+                }
+            }
+        });
+        methods.add_async_method("close", |_, this, (): ()| {
+            let this = this.clone();
+            async move {
+                this.close().await;
+                Ok(())
+            }
+        });
+    }
+}
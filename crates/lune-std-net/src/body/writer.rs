@@ -0,0 +1,92 @@
+use std::io::Error;
+
+use async_channel::{Sender, bounded};
+use hyper::body::Bytes;
+
+use mlua::prelude::*;
+
+use crate::shared::sse::ServerSentEvent;
+
+use super::readable::ReadableBody;
+
+/**
+    The writing half of a streamed body, given to body producers in `net.serve`.
+
+    Chunks are sent one at a time, meaning writes will wait until the
+    previous chunk has been consumed by the connection before returning.
+*/
+#[derive(Debug, Clone)]
+pub struct BodyWriter {
+    sender: Sender<Result<Bytes, Error>>,
+}
+
+impl BodyWriter {
+    /**
+        Creates a new body writer, and the readable body that it writes to.
+    */
+    pub fn channel() -> (Self, ReadableBody) {
+        let (sender, receiver) = bounded(1);
+        (Self { sender }, ReadableBody::from_stream(receiver))
+    }
+
+    /**
+        Writes a chunk of data to the body.
+
+        Errors if the body has been closed, or if the receiving end has been
+        dropped, which happens when the client disconnects from the server.
+    */
+    pub async fn write(&self, data: Bytes) -> LuaResult<()> {
+        if data.is_empty() {
+            return Ok(());
+        }
+        self.sender
+            .send(Ok(data))
+            .await
+            .map_err(|_| LuaError::runtime("Response body stream has been closed"))
+    }
+
+    /**
+        Aborts the body using the given error, instead of ending it normally.
+    */
+    pub fn abort(&self, error: impl Into<String>) {
+        // NOTE: The error must replace any chunk that has not yet been
+        // consumed, since waiting for the connection here is not possible
+        self.sender.force_send(Err(Error::other(error.into()))).ok();
+        self.close();
+    }
+
+    /**
+        Ends the body, after which any further writes will error.
+    */
+    pub fn close(&self) {
+        self.sender.close();
+    }
+
+    /**
+        Returns whether the body has been closed.
+    */
+    pub fn is_closed(&self) -> bool {
+        self.sender.is_closed()
+    }
+}
+
+impl LuaUserData for BodyWriter {
+    fn add_fields<F: LuaUserDataFields<Self>>(fields: &mut F) {
+        fields.add_field_method_get("closed", |_, this| Ok(this.is_closed()));
+    }
+
+    fn add_methods<M: LuaUserDataMethods<Self>>(methods: &mut M) {
+        methods.add_async_method("write", |_, this, data: ReadableBody| {
+            let this = this.clone();
+            async move { this.write(data.into_bytes()).await }
+        });
+        methods.add_async_method("event", |_, this, event: ServerSentEvent| {
+            let this = this.clone();
+            async move { this.write(Bytes::from(event.encode())).await }
+        });
+        methods.add_method("close", |_, this, (): ()| {
+            this.close();
+            Ok(())
+        });
+    }
+}
@@ -73,7 +73,7 @@ pub async fn fetch(
 
     // ... we can now safely continue and send the request
     let mut req = Request::from(request);
-    req.decompress = true;
+    req.options.decompress = true;
 
     let exec = Executor::new();
     let fut = fetch_inner(&exec, url, req);
@@ -111,7 +111,7 @@ async fn fetch_inner(
             continue;
         }
 
-        break Response::from_incoming(incoming, request.decompress)
+        break Response::from_incoming(incoming, request.options.decompress)
             .await
             .map_err(|e| e.to_string());
     }
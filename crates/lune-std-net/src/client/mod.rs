@@ -12,9 +12,11 @@ use crate::{
     shared::{request::Request, tcp::Tcp, websocket::Websocket},
 };
 
+pub mod proxy;
 pub mod rustls;
 pub mod stream;
 pub mod tcp;
+pub mod tls;
 
 mod fetch;
 mod send;
@@ -22,7 +24,7 @@ mod send;
 pub use self::fetch::fetch;
 pub use self::send::send;
 
-const MAX_REDIRECTS: usize = 10;
+pub const MAX_REDIRECTS: usize = 10;
 
 /**
     Connects to a websocket at the given URL.
@@ -54,8 +56,17 @@ fn try_follow_redirect(
     request: &mut Request,
     response: &HyperResponse<Incoming>,
 ) -> Result<bool, &'static str> {
+    // NOTE: A maximum of zero redirects means that redirects should
+    // not be followed at all, and the response is returned as-is
+    if request.options.max_redirects == 0 {
+        return Ok(false);
+    }
+
     if let Some((new_method, new_uri)) = check_redirect(request.inner.method().clone(), response) {
-        if request.redirects.is_some_and(|r| r >= MAX_REDIRECTS) {
+        if request
+            .redirects
+            .is_some_and(|r| r >= request.options.max_redirects)
+        {
             return Err("Too many redirects");
         }
 
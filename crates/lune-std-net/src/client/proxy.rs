@@ -0,0 +1,237 @@
+use std::{
+    borrow::Cow,
+    io::{Error, Result},
+    net::IpAddr,
+};
+
+use async_net::TcpStream;
+use futures_lite::prelude::*;
+use url::Url;
+
+use mlua::prelude::*;
+
+use lune_std_serde::BinaryTextEncoding;
+
+const MAX_CONNECT_RESPONSE_LEN: usize = 8192;
+
+/**
+    A kind of proxy that requests can be sent through.
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProxyKind {
+    Http,
+    Socks5,
+}
+
+/**
+    A proxy that requests can be sent through.
+
+    Both kinds of proxies are used to tunnel a TCP connection to the
+    target host, either using the HTTP `CONNECT` method, or SOCKS5.
+    The target host name is always resolved by the proxy itself.
+*/
+#[derive(Debug, Clone)]
+pub struct ProxyConfig {
+    kind: ProxyKind,
+    host: String,
+    port: u16,
+    credentials: Option<(String, String)>,
+}
+
+impl ProxyConfig {
+    /**
+        Connects to the given host and port through the proxy.
+    */
+    pub async fn connect(&self, host: &str, port: u16) -> Result<TcpStream> {
+        let mut stream = TcpStream::connect((self.host.as_str(), self.port)).await?;
+        match self.kind {
+            ProxyKind::Http => self.handshake_http(&mut stream, host, port).await?,
+            ProxyKind::Socks5 => self.handshake_socks5(&mut stream, host, port).await?,
+        }
+        Ok(stream)
+    }
+
+    async fn handshake_http(&self, stream: &mut TcpStream, host: &str, port: u16) -> Result<()> {
+        let auth = match &self.credentials {
+            Some((username, password)) => {
+                let auth = BinaryTextEncoding::Base64.encode(format!("{username}:{password}"));
+                format!("Proxy-Authorization: Basic {auth}\r\n")
+            }
+            None => String::new(),
+        };
+        let request =
+            format!("CONNECT {host}:{port} HTTP/1.1\r\nHost: {host}:{port}\r\n{auth}\r\n");
+        stream.write_all(request.as_bytes()).await?;
+
+        // NOTE: We must read the response one byte at a time, since any
+        // bytes after the response head belong to the tunneled connection
+        let mut response = Vec::new();
+        while !response.ends_with(b"\r\n\r\n") {
+            if response.len() >= MAX_CONNECT_RESPONSE_LEN {
+                return Err(proxy_error("response from proxy was too large"));
+            }
+            let mut byte = [0; 1];
+            if stream.read(&mut byte).await? == 0 {
+                return Err(proxy_error("proxy closed the connection"));
+            }
+            response.push(byte[0]);
+        }
+
+        let response = String::from_utf8_lossy(&response);
+        let status = response
+            .lines()
+            .next()
+            .and_then(|line| line.split_whitespace().nth(1))
+            .unwrap_or_default();
+        if status == "200" {
+            Ok(())
+        } else {
+            Err(proxy_error(format!(
+                "proxy responded with status '{status}' to CONNECT request"
+            )))
+        }
+    }
+
+    async fn handshake_socks5(&self, stream: &mut TcpStream, host: &str, port: u16) -> Result<()> {
+        // 1. Negotiate the authentication method
+        let method = if self.credentials.is_some() {
+            0x02
+        } else {
+            0x00
+        };
+        stream.write_all(&[0x05, 0x01, method]).await?;
+        let mut reply = [0; 2];
+        stream.read_exact(&mut reply).await?;
+        if reply[0] != 0x05 || reply[1] != method {
+            return Err(proxy_error(
+                "proxy does not support the authentication method",
+            ));
+        }
+
+        // 2. Authenticate using username and password, if necessary
+        if let Some((username, password)) = &self.credentials {
+            let (Ok(ulen), Ok(plen)) = (u8::try_from(username.len()), u8::try_from(password.len()))
+            else {
+                return Err(proxy_error("proxy username or password is too long"));
+            };
+            let mut auth = vec![0x01, ulen];
+            auth.extend_from_slice(username.as_bytes());
+            auth.push(plen);
+            auth.extend_from_slice(password.as_bytes());
+            stream.write_all(&auth).await?;
+            stream.read_exact(&mut reply).await?;
+            if reply[1] != 0x00 {
+                return Err(proxy_error("proxy rejected the username or password"));
+            }
+        }
+
+        // 3. Connect to the target host
+        let mut connect = vec![0x05, 0x01, 0x00];
+        match host.trim_start_matches('[').trim_end_matches(']').parse() {
+            Ok(IpAddr::V4(ip)) => {
+                connect.push(0x01);
+                connect.extend_from_slice(&ip.octets());
+            }
+            Ok(IpAddr::V6(ip)) => {
+                connect.push(0x04);
+                connect.extend_from_slice(&ip.octets());
+            }
+            Err(_) => {
+                let Ok(len) = u8::try_from(host.len()) else {
+                    return Err(proxy_error("host name is too long"));
+                };
+                connect.push(0x03);
+                connect.push(len);
+                connect.extend_from_slice(host.as_bytes());
+            }
+        }
+        connect.extend_from_slice(&port.to_be_bytes());
+        stream.write_all(&connect).await?;
+
+        // 4. Read the reply, including the bound address which we do not need
+        let mut head = [0; 4];
+        stream.read_exact(&mut head).await?;
+        if head[1] != 0x00 {
+            return Err(proxy_error(format!(
+                "proxy failed to connect to '{host}:{port}' (reply code {})",
+                head[1]
+            )));
+        }
+        let addr_len = match head[3] {
+            0x01 => 4,
+            0x04 => 16,
+            0x03 => {
+                let mut len = [0; 1];
+                stream.read_exact(&mut len).await?;
+                usize::from(len[0])
+            }
+            _ => return Err(proxy_error("proxy sent an invalid reply")),
+        };
+        let mut bound = vec![0; addr_len + 2];
+        stream.read_exact(&mut bound).await?;
+
+        Ok(())
+    }
+}
+
+fn proxy_error(message: impl Into<String>) -> Error {
+    Error::other(message.into())
+}
+
+impl FromLua for ProxyConfig {
+    fn from_lua(value: LuaValue, _: &Lua) -> LuaResult<Self> {
+        let LuaValue::String(s) = &value else {
+            return Err(LuaError::FromLuaConversionError {
+                from: value.type_name(),
+                to: "ProxyConfig".to_string(),
+                message: Some(format!(
+                    "Invalid proxy - expected string, got {}",
+                    value.type_name()
+                )),
+            });
+        };
+
+        let invalid = |message: &str| LuaError::FromLuaConversionError {
+            from: "string",
+            to: "ProxyConfig".to_string(),
+            message: Some(format!("Invalid proxy '{}' - {message}", s.display())),
+        };
+
+        let url = s
+            .to_str()?
+            .parse::<Url>()
+            .map_err(|e| invalid(&e.to_string()))?;
+        let (kind, default_port) = match url.scheme().to_ascii_lowercase().as_str() {
+            "http" => (ProxyKind::Http, 80),
+            "socks5" | "socks5h" => (ProxyKind::Socks5, 1080),
+            scheme => {
+                return Err(invalid(&format!(
+                    "unsupported scheme '{scheme}', valid schemes are:  http, socks5"
+                )));
+            }
+        };
+        let Some(host) = url.host_str() else {
+            return Err(invalid("missing host"));
+        };
+
+        let credentials = if url.username().is_empty() {
+            None
+        } else {
+            let decode = |s: &str| {
+                urlencoding::decode(s)
+                    .map(Cow::into_owned)
+                    .map_err(|e| invalid(&e.to_string()))
+            };
+            let username = decode(url.username())?;
+            let password = decode(url.password().unwrap_or_default())?;
+            Some((username, password))
+        };
+
+        Ok(Self {
+            kind,
+            host: host.to_string(),
+            port: url.port().unwrap_or(default_port),
+            credentials,
+        })
+    }
+}
@@ -1,10 +1,20 @@
-use std::sync::{
-    Arc, LazyLock,
-    atomic::{AtomicBool, Ordering},
+use std::{
+    fs,
+    path::PathBuf,
+    sync::{
+        Arc, LazyLock,
+        atomic::{AtomicBool, Ordering},
+    },
 };
 
+use blocking::unblock;
+use bstr::{BString, ByteSlice};
 use rustls::{ClientConfig, crypto::ring};
 
+use mlua::prelude::*;
+
+use crate::shared::request::HttpVersion;
+
 static PROVIDER_INITIALIZED: AtomicBool = AtomicBool::new(false);
 
 pub fn initialize_provider() {
@@ -15,12 +25,109 @@ pub fn initialize_provider() {
     }
 }
 
-pub static CLIENT_CONFIG: LazyLock<Arc<ClientConfig>> = LazyLock::new(|| {
+/**
+    Creates a root certificate store containing the default trusted roots.
+*/
+pub fn default_root_store() -> rustls::RootCertStore {
+    rustls::RootCertStore {
+        roots: webpki_roots::TLS_SERVER_ROOTS.to_vec(),
+    }
+}
+
+fn create_client_config(alpn_protocols: Vec<Vec<u8>>) -> Arc<ClientConfig> {
     initialize_provider();
-    rustls::ClientConfig::builder()
-        .with_root_certificates(rustls::RootCertStore {
-            roots: webpki_roots::TLS_SERVER_ROOTS.to_vec(),
-        })
-        .with_no_client_auth()
-        .into()
-});
+    let mut config = rustls::ClientConfig::builder()
+        .with_root_certificates(default_root_store())
+        .with_no_client_auth();
+    config.alpn_protocols = alpn_protocols;
+    config.into()
+}
+
+pub static CLIENT_CONFIG: LazyLock<Arc<ClientConfig>> =
+    LazyLock::new(|| create_client_config(Vec::new()));
+
+// NOTE: HTTP clients must advertise the protocols they support using ALPN, since
+// servers that support HTTP/2 are otherwise not allowed to use it over TLS, but
+// other clients such as web sockets and plain TCP streams must not advertise them
+
+static HTTP_CLIENT_CONFIG: LazyLock<Arc<ClientConfig>> =
+    LazyLock::new(|| create_client_config(http_alpn_protocols(None)));
+
+static HTTP1_CLIENT_CONFIG: LazyLock<Arc<ClientConfig>> =
+    LazyLock::new(|| create_client_config(http_alpn_protocols(Some(HttpVersion::Http1))));
+
+static HTTP2_CLIENT_CONFIG: LazyLock<Arc<ClientConfig>> =
+    LazyLock::new(|| create_client_config(http_alpn_protocols(Some(HttpVersion::Http2))));
+
+/**
+    Returns the protocols that HTTP clients should advertise using ALPN,
+    which may optionally be restricted to a specific HTTP version.
+*/
+pub fn http_alpn_protocols(version: Option<HttpVersion>) -> Vec<Vec<u8>> {
+    match version {
+        None => vec![b"h2".to_vec(), b"http/1.1".to_vec()],
+        Some(HttpVersion::Http1) => vec![b"http/1.1".to_vec()],
+        Some(HttpVersion::Http2) => vec![b"h2".to_vec()],
+    }
+}
+
+/**
+    Returns the client config to use for HTTP requests, which
+    may optionally be restricted to a specific HTTP version.
+*/
+pub fn http_client_config(version: Option<HttpVersion>) -> Arc<ClientConfig> {
+    match version {
+        None => Arc::clone(&HTTP_CLIENT_CONFIG),
+        Some(HttpVersion::Http1) => Arc::clone(&HTTP1_CLIENT_CONFIG),
+        Some(HttpVersion::Http2) => Arc::clone(&HTTP2_CLIENT_CONFIG),
+    }
+}
+
+/**
+    A certificate or key given in a TLS config.
+
+    Certificates and keys may be given either as PEM-encoded strings, or as
+    paths to PEM files. Files are not read when the config is created, but
+    only once it is used, and without blocking the scheduler thread.
+*/
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum PemSource {
+    Data(Vec<u8>),
+    File { key: &'static str, path: PathBuf },
+}
+
+impl PemSource {
+    /**
+        Gets the PEM source from the given table key, if any.
+    */
+    pub fn from_table(table: &LuaTable, key: &'static str) -> LuaResult<Option<Self>> {
+        let Some(value) = table.get::<Option<BString>>(key)? else {
+            return Ok(None);
+        };
+        if value.contains_str("-----BEGIN") {
+            return Ok(Some(Self::Data(value.into())));
+        }
+        let path = PathBuf::from(value.to_str_lossy().as_ref());
+        Ok(Some(Self::File { key, path }))
+    }
+
+    /**
+        Reads the PEM data, from its file if it was given as a path.
+    */
+    pub async fn read(&self) -> LuaResult<Vec<u8>> {
+        match self {
+            Self::Data(data) => Ok(data.clone()),
+            Self::File { key, path } => {
+                let path = path.clone();
+                unblock(move || fs::read(&path).map_err(|e| (path, e)))
+                    .await
+                    .map_err(|(path, e)| {
+                        LuaError::RuntimeError(format!(
+                            "Invalid tls config - failed to read {key} from '{}' - {e}",
+                            path.display()
+                        ))
+                    })
+            }
+        }
+    }
+}
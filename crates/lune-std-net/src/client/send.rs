@@ -1,19 +1,25 @@
+use std::{future::Future, sync::Arc, time::Duration};
+
+use async_io::Timer;
+
 use http_body_util::Full;
 use hyper::{
     Method, Request as HyperRequest,
-    client::conn::http1::handshake,
+    client::conn::{http1, http2},
     header::{ACCEPT, CONTENT_LENGTH, HOST, HeaderValue, USER_AGENT},
 };
 
 use mlua::prelude::*;
+use mlua_luau_scheduler::LuaSpawnExt;
 use url::Url;
 
 use crate::{
-    client::stream::HttpStream,
+    client::{rustls::http_client_config, stream::HttpStream},
     shared::{
+        futures::{Either, either},
         headers::create_user_agent_header,
-        hyper::{HyperExecutor, HyperIo},
-        request::Request,
+        hyper::{HyperExecutor, HyperIo, HyperLocalExecutor, HyperTimer},
+        request::{HttpVersion, Request},
         response::Response,
     },
 };
@@ -23,8 +29,26 @@ use crate::{
 
     This will follow any redirects returned by the server,
     modifying the request method and body as necessary.
+
+    If the request has a timeout, it applies to the entire request,
+    including any redirects and reading the full response body, unless
+    the response body is streamed, in which case it only applies until
+    the response head has been received.
 */
-pub async fn send(mut request: Request, lua: Lua) -> LuaResult<Response> {
+pub async fn send(request: Request, lua: Lua) -> LuaResult<Response> {
+    let timeout = request.options.timeout;
+    let uri = request.inner.uri().clone();
+    with_timeout(timeout, Box::pin(send_inner(request, lua)))
+        .await
+        .unwrap_or_else(|timeout| {
+            Err(LuaError::runtime(format!(
+                "Request to '{uri}' timed out after {}s",
+                timeout.as_secs_f64()
+            )))
+        })
+}
+
+async fn send_inner(mut request: Request, lua: Lua) -> LuaResult<Response> {
     let mut url = request
         .inner
         .uri()
@@ -50,21 +74,67 @@ pub async fn send(mut request: Request, lua: Lua) -> LuaResult<Response> {
     }
 
     // ... we can now safely continue and send the request
+    let version = request.options.http_version;
+    let config = match &request.options.tls {
+        Some(tls) => tls.http_client_config(version).await?,
+        None => http_client_config(version),
+    };
     loop {
-        let stream = HttpStream::connect_url(url.clone()).await?;
-
-        let (mut sender, conn) = handshake(HyperIo::from(stream)).await.into_lua_err()?;
+        let connect = HttpStream::connect_url_with_config(
+            url.clone(),
+            Arc::clone(&config),
+            request.options.proxy.as_ref(),
+        );
+        let stream = match with_timeout(request.options.connect_timeout, connect).await {
+            Ok(stream) => stream?,
+            Err(timeout) => {
+                return Err(LuaError::runtime(format!(
+                    "Connection to '{url}' timed out after {}s",
+                    timeout.as_secs_f64()
+                )));
+            }
+        };
 
-        HyperExecutor::execute(lua.clone(), conn);
+        // NOTE: HTTP/2 is used either when it was negotiated using ALPN,
+        // or when the request was restricted to HTTP/2 over plain HTTP,
+        // in which case the server must support it with prior knowledge
+        let use_http2 = match stream.alpn_protocol() {
+            Some(protocol) => protocol == b"h2",
+            None => request.options.http_version == Some(HttpVersion::Http2),
+        };
+        if request.options.http_version == Some(HttpVersion::Http2) && !use_http2 {
+            return Err(LuaError::runtime(format!(
+                "Server at '{url}' does not support HTTP/2"
+            )));
+        }
 
+        // HTTP/2 sends the host using the :authority pseudo-header instead
         let (mut parts, body) = request.clone_inner().into_parts();
-        if let Some(host) = parts.uri.host() {
+        if let Some(host) = parts.uri.host().filter(|_| !use_http2) {
             let host = HeaderValue::from_str(host).unwrap();
             parts.headers.insert(HOST, host);
         }
 
         let data = HyperRequest::from_parts(parts, Full::new(body.into_bytes()));
-        let incoming = sender.send_request(data).await.into_lua_err()?;
+        let incoming = if use_http2 {
+            let (mut sender, conn) = http2::Builder::new(HyperLocalExecutor::from(lua.clone()))
+                .timer(HyperTimer)
+                .handshake(HyperIo::from(stream))
+                .await
+                .into_lua_err()?;
+            lua.spawn_local(async move {
+                if let Err(_err) = conn.await {
+                    // TODO: Propagate error somehow
+                }
+            });
+            sender.send_request(data).await.into_lua_err()?
+        } else {
+            let (mut sender, conn) = http1::handshake(HyperIo::from(stream))
+                .await
+                .into_lua_err()?;
+            HyperExecutor::execute(lua.clone(), conn);
+            sender.send_request(data).await.into_lua_err()?
+        };
 
         if super::try_follow_redirect(&mut url, &mut request, &incoming)
             .map_err(LuaError::external)?
@@ -72,6 +142,24 @@ pub async fn send(mut request: Request, lua: Lua) -> LuaResult<Response> {
             continue;
         }
 
-        break Response::from_incoming(incoming, request.decompress).await;
+        if request.options.stream {
+            break Response::from_incoming_stream(incoming, request.options.decompress);
+        }
+
+        break Response::from_incoming(incoming, request.options.decompress).await;
+    }
+}
+
+/**
+    Runs the given future to completion, or until the
+    timeout elapses, in which case the timeout is returned.
+*/
+async fn with_timeout<F: Future>(timeout: Option<Duration>, fut: F) -> Result<F::Output, Duration> {
+    let Some(timeout) = timeout else {
+        return Ok(fut.await);
+    };
+    match either(fut, Timer::after(timeout)).await {
+        Either::Left(output) => Ok(output),
+        Either::Right(_) => Err(timeout),
     }
 }
@@ -14,10 +14,11 @@ use async_tungstenite::{
 use futures::Sink;
 use futures_lite::prelude::*;
 use futures_rustls::{TlsConnector, TlsStream};
+use rustls::ClientConfig;
 use rustls_pki_types::ServerName;
 use url::Url;
 
-use crate::client::rustls::CLIENT_CONFIG;
+use crate::client::{proxy::ProxyConfig, rustls::CLIENT_CONFIG};
 
 /**
     Type alias for differentiating between a [`MaybeTlsStream`]
@@ -48,11 +49,37 @@ impl MaybeTlsStream {
         The given `host` must be a valid DNS name, when using TLS.
     */
     pub async fn connect(host: &str, port: u16, tls: bool) -> Result<Self> {
+        let config = tls.then(|| Arc::clone(&CLIENT_CONFIG));
+        Self::connect_with_config(host, port, config).await
+    }
+
+    /**
+        Connects to a host and port, additionally using TLS with the given config if specified.
+
+        The given `host` must be a valid DNS name, when using TLS.
+    */
+    pub async fn connect_with_config(
+        host: &str,
+        port: u16,
+        tls: Option<Arc<ClientConfig>>,
+    ) -> Result<Self> {
         let stream = TcpStream::connect((host, port)).await?;
+        Self::from_tcp(stream, host, tls).await
+    }
+
+    /**
+        Wraps an already connected TCP stream, additionally using TLS with the given config if specified.
 
-        let stream = if tls {
+        The given `host` must be a valid DNS name, when using TLS.
+    */
+    pub async fn from_tcp(
+        stream: TcpStream,
+        host: &str,
+        tls: Option<Arc<ClientConfig>>,
+    ) -> Result<Self> {
+        let stream = if let Some(config) = tls {
             let servname = ServerName::try_from(host).map_err(Error::other)?.to_owned();
-            let connector = TlsConnector::from(Arc::clone(&CLIENT_CONFIG));
+            let connector = TlsConnector::from(config);
             let stream = connector.connect(servname, stream).await?;
             Self::Tls(Box::new(TlsStream::Client(stream)))
         } else {
@@ -68,6 +95,19 @@ impl MaybeTlsStream {
        Automatically determines whether or not to use TLS based on the URL scheme.
     */
     pub async fn connect_url(url: Url) -> Result<Self> {
+        Self::connect_url_with_config(url, Arc::clone(&CLIENT_CONFIG), None).await
+    }
+
+    /**
+       Connects to the given URL, using the given TLS config if the URL scheme requires TLS.
+
+       If a proxy is given, the connection will be tunneled through it.
+    */
+    pub async fn connect_url_with_config(
+        url: Url,
+        tls: Arc<ClientConfig>,
+        proxy: Option<&ProxyConfig>,
+    ) -> Result<Self> {
         let Some(host) = url.host() else {
             return Err(Error::other("unknown or missing host"));
         };
@@ -82,7 +122,11 @@ impl MaybeTlsStream {
         };
 
         let host = host.to_string();
-        Self::connect(&host, port, use_tls).await
+        let stream = match proxy {
+            Some(proxy) => proxy.connect(&host, port).await?,
+            None => TcpStream::connect((host.as_str(), port)).await?,
+        };
+        Self::from_tcp(stream, &host, use_tls.then_some(tls)).await
     }
 
     /**
@@ -99,6 +143,21 @@ impl MaybeTlsStream {
         self.as_ref().peer_addr()
     }
 
+    /**
+        Returns the protocol that was negotiated using ALPN, if any.
+
+        This is always `None` for streams that do not use TLS.
+    */
+    pub fn alpn_protocol(&self) -> Option<&[u8]> {
+        match self {
+            MaybeTlsStream::Plain(_) => None,
+            MaybeTlsStream::Tls(stream) => match stream.as_ref() {
+                TlsStream::Client(stream) => stream.get_ref().1.alpn_protocol(),
+                TlsStream::Server(stream) => stream.get_ref().1.alpn_protocol(),
+            },
+        }
+    }
+
     /**
         Sets the TTL (Time To Live) for packets in the stream.
 
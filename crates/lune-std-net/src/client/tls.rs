@@ -0,0 +1,258 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, LazyLock, Mutex},
+};
+
+use rustls::{
+    ClientConfig, DigitallySignedStruct, SignatureScheme,
+    client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier},
+    crypto::{CryptoProvider, verify_tls12_signature, verify_tls13_signature},
+};
+use rustls_pki_types::{CertificateDer, PrivateKeyDer, ServerName, UnixTime, pem::PemObject};
+
+use mlua::prelude::*;
+
+use crate::{
+    client::rustls::{PemSource, default_root_store, http_alpn_protocols, initialize_provider},
+    shared::request::HttpVersion,
+};
+
+/*
+    Client configs are cached using the options they were created from, so that
+    certificates and keys are only read once, and the root store is not rebuilt
+    for every request - the cache is cleared whenever it grows too large
+*/
+const MAX_CACHED_CLIENT_CONFIGS: usize = 32;
+
+type ClientConfigCache = HashMap<(RequestTlsConfig, Option<HttpVersion>), Arc<ClientConfig>>;
+
+static CLIENT_CONFIG_CACHE: LazyLock<Mutex<ClientConfigCache>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/**
+    TLS configuration for `net.request`.
+*/
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct RequestTlsConfig {
+    ca: Option<PemSource>,
+    client_auth: Option<(PemSource, PemSource)>,
+    insecure: bool,
+}
+
+impl RequestTlsConfig {
+    /**
+        Returns the client config to use for HTTP requests, which may
+        optionally be restricted to a specific HTTP version.
+
+        Certificates and keys are read and the config is created only the first
+        time this is called, after which the same config is reused for any
+        requests that use the same TLS configuration and HTTP version.
+
+        # Errors
+
+        Errors if any certificate or key could not be read, or is invalid.
+    */
+    pub async fn http_client_config(
+        &self,
+        version: Option<HttpVersion>,
+    ) -> LuaResult<Arc<ClientConfig>> {
+        let key = (self.clone(), version);
+        if let Some(config) = CLIENT_CONFIG_CACHE.lock().unwrap().get(&key) {
+            return Ok(Arc::clone(config));
+        }
+
+        let config = self.read_and_build(version).await?;
+
+        let mut cache = CLIENT_CONFIG_CACHE.lock().unwrap();
+        if cache.len() >= MAX_CACHED_CLIENT_CONFIGS {
+            cache.clear();
+        }
+        cache.insert(key, Arc::clone(&config));
+        Ok(config)
+    }
+
+    async fn read_and_build(&self, version: Option<HttpVersion>) -> LuaResult<Arc<ClientConfig>> {
+        let ca = match &self.ca {
+            Some(ca) => Some(ca.read().await?),
+            None => None,
+        };
+        let client_auth = match &self.client_auth {
+            Some((cert, key)) => Some((cert.read().await?, key.read().await?)),
+            None => None,
+        };
+
+        let mut config = Self::build(
+            ca.as_deref(),
+            client_auth
+                .as_ref()
+                .map(|(cert, key)| (cert.as_slice(), key.as_slice())),
+            self.insecure,
+        )
+        .map_err(|message| LuaError::RuntimeError(format!("Invalid tls config - {message}")))?;
+        config.alpn_protocols = http_alpn_protocols(version);
+
+        Ok(Arc::new(config))
+    }
+
+    fn build(
+        ca: Option<&[u8]>,
+        client_auth: Option<(&[u8], &[u8])>,
+        insecure: bool,
+    ) -> Result<ClientConfig, String> {
+        initialize_provider();
+
+        let builder = ClientConfig::builder();
+        let builder = if insecure {
+            builder
+                .dangerous()
+                .with_custom_certificate_verifier(Arc::new(NoServerCertVerifier::new()))
+        } else {
+            let mut roots = default_root_store();
+            if let Some(ca) = ca {
+                let certs = CertificateDer::pem_slice_iter(ca)
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(|e| format!("failed to parse CA - {e}"))?;
+                if certs.is_empty() {
+                    return Err("CA does not contain any PEM certificates".to_string());
+                }
+                for cert in certs {
+                    roots
+                        .add(cert)
+                        .map_err(|e| format!("invalid CA certificate - {e}"))?;
+                }
+            }
+            builder.with_root_certificates(roots)
+        };
+
+        if let Some((cert, key)) = client_auth {
+            let certs = CertificateDer::pem_slice_iter(cert)
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|e| format!("failed to parse certificate - {e}"))?;
+            if certs.is_empty() {
+                return Err("certificate does not contain any PEM certificates".to_string());
+            }
+            let key = PrivateKeyDer::from_pem_slice(key)
+                .map_err(|e| format!("failed to parse private key - {e}"))?;
+            builder
+                .with_client_auth_cert(certs, key)
+                .map_err(|e| format!("invalid certificate or private key - {e}"))
+        } else {
+            Ok(builder.with_no_client_auth())
+        }
+    }
+}
+
+impl FromLua for RequestTlsConfig {
+    fn from_lua(value: LuaValue, _: &Lua) -> LuaResult<Self> {
+        let LuaValue::Table(t) = &value else {
+            return Err(LuaError::FromLuaConversionError {
+                from: value.type_name(),
+                to: "RequestTlsConfig".to_string(),
+                message: Some(format!(
+                    "Invalid tls config - expected table, got {}",
+                    value.type_name()
+                )),
+            });
+        };
+
+        let ca = PemSource::from_table(t, "ca")?;
+        let cert = PemSource::from_table(t, "cert")?;
+        let key = PemSource::from_table(t, "key")?;
+        let client_auth = match (cert, key) {
+            (Some(cert), Some(key)) => Some((cert, key)),
+            (None, None) => None,
+            _ => {
+                return Err(LuaError::FromLuaConversionError {
+                    from: value.type_name(),
+                    to: "RequestTlsConfig".to_string(),
+                    message: Some(
+                        "Invalid tls config - 'cert' and 'key' must be given together".to_string(),
+                    ),
+                });
+            }
+        };
+        let insecure: Option<bool> = t.get("insecure")?;
+        if insecure == Some(true) && ca.is_some() {
+            return Err(LuaError::FromLuaConversionError {
+                from: value.type_name(),
+                to: "RequestTlsConfig".to_string(),
+                message: Some(
+                    "Invalid tls config - 'ca' can not be used with 'insecure'".to_string(),
+                ),
+            });
+        }
+
+        Ok(Self {
+            ca,
+            client_auth,
+            insecure: insecure.unwrap_or_default(),
+        })
+    }
+}
+
+/**
+    A server certificate verifier that accepts any certificate.
+
+    Handshake signatures are still verified, but since the certificate
+    itself is never verified, this must only be used for testing.
+*/
+#[derive(Debug)]
+struct NoServerCertVerifier {
+    provider: Arc<CryptoProvider>,
+}
+
+impl NoServerCertVerifier {
+    fn new() -> Self {
+        let provider = CryptoProvider::get_default()
+            .cloned()
+            .unwrap_or_else(|| Arc::new(rustls::crypto::ring::default_provider()));
+        Self { provider }
+    }
+}
+
+impl ServerCertVerifier for NoServerCertVerifier {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls12_signature(
+            message,
+            cert,
+            dss,
+            &self.provider.signature_verification_algorithms,
+        )
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls13_signature(
+            message,
+            cert,
+            dss,
+            &self.provider.signature_verification_algorithms,
+        )
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.provider
+            .signature_verification_algorithms
+            .supported_schemes()
+    }
+}
@@ -42,8 +42,7 @@ impl ServeHandle {
                     Err(LuaError::runtime("Server already stopped"))
                 } else {
                     shutdown.store(true, Ordering::SeqCst);
-                    sender.try_send(()).ok();
-                    sender.close();
+                    send_shutdown(&sender);
                     Ok(())
                 }
             })?
@@ -63,10 +62,22 @@ impl LuaUserData for ServeHandle {
                 Err(LuaError::runtime("Server already stopped"))
             } else {
                 this.shutdown.store(true, Ordering::SeqCst);
-                this.sender.try_send(()).ok();
-                this.sender.close();
+                send_shutdown(&this.sender);
                 Ok(())
             }
         });
     }
 }
+
+/**
+    Sends the shutdown signal to the server and all of its connections.
+
+    Each one listens using its own clone of the receiver, and every
+    message is only received once, so one must be sent per receiver.
+*/
+fn send_shutdown(sender: &Sender<()>) {
+    for _ in 0..sender.receiver_count() {
+        sender.try_send(()).ok();
+    }
+    sender.close();
+}
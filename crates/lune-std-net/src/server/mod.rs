@@ -1,32 +1,63 @@
-use std::{cell::Cell, net::SocketAddr, rc::Rc};
+use std::{cell::Cell, future::Future, io, net::SocketAddr, pin::Pin, rc::Rc, time::Duration};
 
+use async_channel::Receiver;
+use async_io::Timer;
 use async_net::TcpListener;
 use futures_lite::pin;
-use hyper::server::conn::http1::Builder as Http1Builder;
+use futures_rustls::{TlsAcceptor, TlsStream};
+use hyper::server::conn::{
+    http1::{self, Builder as Http1Builder},
+    http2::{self, Builder as Http2Builder},
+};
 
 use mlua::prelude::*;
 use mlua_luau_scheduler::LuaSpawnExt;
 
 use crate::{
-    server::{config::ServeConfig, handle::ServeHandle, service::Service},
+    client::stream::MaybeTlsStream,
+    server::{
+        config::ServeConfig,
+        handle::ServeHandle,
+        protocol::{PrefixedStream, Protocol, detect_protocol},
+        service::Service,
+    },
     shared::{
         futures::{Either, either},
-        hyper::{HyperIo, HyperTimer},
+        hyper::{HyperIo, HyperLocalExecutor, HyperTimer},
     },
 };
 
 pub mod config;
 pub mod handle;
+pub mod protocol;
 pub mod service;
+pub mod tls;
 pub mod upgrade;
 
+/**
+    How long clients may take to complete the TLS handshake, and to send enough
+    of their first request to detect its protocol, before they are disconnected.
+*/
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
 /**
     Starts an HTTP server using the given port and configuration.
 
+    If the configuration contains TLS options, the server will
+    instead accept HTTPS connections, and secure websockets.
+
+    Both HTTP/1.1 and HTTP/2 are supported, with HTTP/2 being negotiated
+    using ALPN over TLS, or used with prior knowledge over plain HTTP.
+
     Returns a `ServeHandle` that can be used to gracefully stop the server.
 */
 pub async fn serve(lua: Lua, port: u16, config: ServeConfig) -> LuaResult<ServeHandle> {
     let address = SocketAddr::from((config.address, port));
+    let acceptor = match &config.tls {
+        Some(tls) => Some(TlsAcceptor::from(tls.server_config().await?)),
+        None => None,
+    };
+
     let service = Service {
         lua: lua.clone(),
         address,
@@ -73,43 +104,50 @@ pub async fn serve(lua: Lua, port: u16, config: ServeConfig) -> LuaResult<ServeH
                 // 2. For each connection, spawn a new task to handle it
                 lua.spawn_local({
                     let rx = shutdown_rx.clone();
-                    let io = HyperIo::from(conn);
+                    let acceptor = acceptor.clone();
 
                     let mut svc = service.clone();
                     svc.address = addr;
 
+                    let lua = lua.clone();
                     let handle_dropped = Rc::clone(&handle_dropped);
                     async move {
-                        let conn = Http1Builder::new()
-                            .writev(false)
-                            .timer(HyperTimer)
-                            .keep_alive(true)
-                            .serve_connection(io, svc)
-                            .with_upgrades();
-                        if handle_dropped.get() {
-                            if let Err(_err) = conn.await {
-                                // TODO: Propagate error somehow
-                            }
-                        } else {
-                            // NOTE #2: Because we use keep_alive for websockets above, we need to
-                            // also manually poll this future and handle the graceful shutdown,
-                            // otherwise the already accepted connection will linger and run
-                            // even if the stop method has been called on the serve handle
-                            pin!(conn);
-                            match either(rx.recv(), conn.as_mut()).await {
-                                Either::Left(Ok(())) => conn.as_mut().graceful_shutdown(),
-                                Either::Left(Err(_)) => {
-                                    // Same as note #1
-                                    handle_dropped.set(true);
-                                    if let Err(_err) = conn.await {
-                                        // TODO: Propagate error somehow
-                                    }
-                                }
-                                Either::Right(Ok(())) => {}
-                                Either::Right(Err(_err)) => {
-                                    // TODO: Propagate error somehow
+                        // NOTE: The TLS handshake happens here, and not while accepting,
+                        // so that slow or misbehaving clients can not block other clients
+                        let stream = match acceptor {
+                            Some(acceptor) => {
+                                let accept = acceptor.accept(conn);
+                                match handshake(accept, &rx, &handle_dropped).await {
+                                    Some(stream) => MaybeTlsStream::from(TlsStream::Server(stream)),
+                                    None => return,
                                 }
                             }
+                            None => MaybeTlsStream::from(conn),
+                        };
+                        let detect = detect_protocol(stream);
+                        let Some((protocol, stream)) =
+                            handshake(detect, &rx, &handle_dropped).await
+                        else {
+                            return;
+                        };
+                        let io = HyperIo::from(stream);
+
+                        match protocol {
+                            Protocol::Http1 => {
+                                let conn = Http1Builder::new()
+                                    .writev(false)
+                                    .timer(HyperTimer)
+                                    .keep_alive(true)
+                                    .serve_connection(io, svc)
+                                    .with_upgrades();
+                                drive_connection(conn, rx, handle_dropped).await;
+                            }
+                            Protocol::Http2 => {
+                                let conn = Http2Builder::new(HyperLocalExecutor::from(lua))
+                                    .timer(HyperTimer)
+                                    .serve_connection(io, svc);
+                                drive_connection(conn, rx, handle_dropped).await;
+                            }
                         }
                     }
                 });
@@ -119,3 +157,101 @@ pub async fn serve(lua: Lua, port: u16, config: ServeConfig) -> LuaResult<ServeH
 
     Ok(handle)
 }
+
+/**
+    Waits for a step of setting up a connection, such as the TLS handshake, to complete.
+
+    Returns `None` if the step fails, if it takes longer than the handshake
+    timeout, or if the stop method is called on the serve handle meanwhile,
+    in which case the connection should be dropped.
+*/
+async fn handshake<T>(
+    fut: impl Future<Output = io::Result<T>>,
+    rx: &Receiver<()>,
+    handle_dropped: &Cell<bool>,
+) -> Option<T> {
+    let fut = either(fut, Timer::after(HANDSHAKE_TIMEOUT));
+    pin!(fut);
+
+    if !handle_dropped.get() {
+        match either(rx.recv(), fut.as_mut()).await {
+            Either::Left(Ok(())) => return None,
+            Either::Left(Err(_)) => {
+                // Same as note #1
+                handle_dropped.set(true);
+            }
+            Either::Right(result) => return handshake_result(result),
+        }
+    }
+
+    handshake_result(fut.await)
+}
+
+fn handshake_result<T, U>(result: Either<io::Result<T>, U>) -> Option<T> {
+    match result {
+        Either::Left(Ok(value)) => Some(value),
+        Either::Left(Err(_err)) => {
+            // TODO: Propagate error somehow
+            None
+        }
+        Either::Right(_) => None,
+    }
+}
+
+/**
+    A connection that can be gracefully shut down, such as
+    an HTTP/1 connection with upgrades or an HTTP/2 connection.
+*/
+trait GracefulConnection: Future<Output = hyper::Result<()>> {
+    fn graceful_shutdown(self: Pin<&mut Self>);
+}
+
+type ServerIo = HyperIo<PrefixedStream<MaybeTlsStream>>;
+
+impl GracefulConnection for http1::UpgradeableConnection<ServerIo, Service> {
+    fn graceful_shutdown(self: Pin<&mut Self>) {
+        http1::UpgradeableConnection::graceful_shutdown(self);
+    }
+}
+
+impl GracefulConnection for http2::Connection<ServerIo, Service, HyperLocalExecutor> {
+    fn graceful_shutdown(self: Pin<&mut Self>) {
+        http2::Connection::graceful_shutdown(self);
+    }
+}
+
+/**
+    Drives the given connection to completion, gracefully shutting it
+    down if the stop method is called on the serve handle meanwhile.
+*/
+async fn drive_connection(
+    conn: impl GracefulConnection,
+    rx: Receiver<()>,
+    handle_dropped: Rc<Cell<bool>>,
+) {
+    if handle_dropped.get() {
+        if let Err(_err) = conn.await {
+            // TODO: Propagate error somehow
+        }
+    } else {
+        // NOTE #2: Because we use keep_alive for websockets, we need to
+        // also manually poll this future and handle the graceful shutdown,
+        // otherwise the already accepted connection will linger and run
+        // even if the stop method has been called on the serve handle
+        pin!(conn);
+        match either(rx.recv(), conn.as_mut()).await {
+            Either::Left(Ok(())) => conn.as_mut().graceful_shutdown(),
+            Either::Left(Err(_)) => {
+                // Same as note #1
+                handle_dropped.set(true);
+                if let Err(_err) = conn.await {
+                    // TODO: Propagate error somehow
+                }
+            }
+            Either::Right(Ok(())) => {}
+            Either::Right(Err(_err)) => {
+                // TODO: Propagate error somehow
+            }
+        }
+    }
+}
@@ -0,0 +1,122 @@
+use std::{
+    io,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use futures_lite::prelude::*;
+
+use crate::client::stream::MaybeTlsStream;
+
+/**
+    The connection preface that all HTTP/2 clients
+    must send before anything else, as per RFC 9113.
+*/
+const HTTP2_PREFACE: &[u8] = b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n";
+
+/**
+    An HTTP protocol that a connection may be served using.
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Protocol {
+    Http1,
+    Http2,
+}
+
+/**
+    A stream that replays bytes that have already been read
+    from the inner stream, before continuing to read from it.
+*/
+#[derive(Debug)]
+pub struct PrefixedStream<T> {
+    prefix: Vec<u8>,
+    position: usize,
+    inner: T,
+}
+
+impl<T> PrefixedStream<T> {
+    fn new(prefix: Vec<u8>, inner: T) -> Self {
+        Self {
+            prefix,
+            position: 0,
+            inner,
+        }
+    }
+}
+
+impl<T: AsyncRead + Unpin> AsyncRead for PrefixedStream<T> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = &mut *self;
+        if this.position < this.prefix.len() {
+            let remaining = &this.prefix[this.position..];
+            let len = remaining.len().min(buf.len());
+            buf[..len].copy_from_slice(&remaining[..len]);
+            this.position += len;
+            return Poll::Ready(Ok(len));
+        }
+        Pin::new(&mut this.inner).poll_read(cx, buf)
+    }
+}
+
+impl<T: AsyncWrite + Unpin> AsyncWrite for PrefixedStream<T> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.inner).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_close(cx)
+    }
+}
+
+/**
+    Detects the HTTP protocol that the client wants to use for the given stream.
+
+    For TLS streams, this is the protocol negotiated using ALPN. For plain streams,
+    this reads just enough bytes to tell if the client sent the HTTP/2 preface, meaning
+    it connected with prior knowledge (h2c), and the bytes read are then replayed.
+*/
+pub async fn detect_protocol(
+    mut stream: MaybeTlsStream,
+) -> io::Result<(Protocol, PrefixedStream<MaybeTlsStream>)> {
+    if let MaybeTlsStream::Tls(_) = &stream {
+        let protocol = if stream.alpn_protocol() == Some(b"h2") {
+            Protocol::Http2
+        } else {
+            Protocol::Http1
+        };
+        return Ok((protocol, PrefixedStream::new(Vec::new(), stream)));
+    }
+
+    let mut prefix = vec![0; HTTP2_PREFACE.len()];
+    let mut len = 0;
+    while len < prefix.len() {
+        let n = stream.read(&mut prefix[len..]).await?;
+        if n == 0 {
+            break;
+        }
+        len += n;
+        if prefix[..len] != HTTP2_PREFACE[..len] {
+            break;
+        }
+    }
+    prefix.truncate(len);
+
+    let protocol = if prefix == HTTP2_PREFACE {
+        Protocol::Http2
+    } else {
+        Protocol::Http1
+    };
+    Ok((protocol, PrefixedStream::new(prefix, stream)))
+}
@@ -2,7 +2,9 @@ use std::{future::Future, net::SocketAddr, pin::Pin};
 
 use async_tungstenite::{WebSocketStream, tungstenite::protocol::Role};
 use hyper::{
-    Request as HyperRequest, Response as HyperResponse, StatusCode, body::Incoming,
+    Request as HyperRequest, Response as HyperResponse, StatusCode,
+    body::Incoming,
+    header::{HOST, HeaderValue},
     service::Service as HyperService,
 };
 
@@ -10,7 +12,7 @@ use mlua::prelude::*;
 use mlua_luau_scheduler::{LuaSchedulerExt, LuaSpawnExt};
 
 use crate::{
-    body::ReadableBody,
+    body::{BodyWriter, ReadableBody},
     server::{
         config::ServeConfig,
         upgrade::{is_upgrade_request, make_upgrade_response},
@@ -80,9 +82,16 @@ impl HyperService<HyperRequest<Incoming>> for Service {
 async fn handle_request(
     lua: Lua,
     handler: LuaFunction,
-    request: HyperRequest<Incoming>,
+    mut request: HyperRequest<Incoming>,
     address: SocketAddr,
 ) -> LuaResult<HyperResponse<ReadableBody>> {
+    // HTTP/2 clients send the host using the :authority pseudo-header instead
+    // of a host header, make sure handlers can still read it from the headers
+    if let Some(authority) = request.uri().authority() {
+        let host = HeaderValue::from_str(authority.as_str()).into_lua_err()?;
+        request.headers_mut().entry(HOST).or_insert(host);
+    }
+
     let request = Request::from_incoming(request, true)
         .await?
         .with_address(address);
@@ -95,10 +104,37 @@ async fn handle_request(
         .get_thread_result(thread_id)
         .expect("Missing handler thread result")?;
 
-    let response = Response::from_lua_multi(thread_res, &lua)?;
+    let mut response = Response::from_lua_multi(thread_res, &lua)?;
+    if let Some(producer) = response.producer.take() {
+        let (writer, body) = BodyWriter::channel();
+        spawn_body_producer(&lua, producer, writer)?;
+        *response.inner.body_mut() = body;
+    }
+
     Ok(response.into_inner())
 }
 
+fn spawn_body_producer(lua: &Lua, producer: LuaFunction, writer: BodyWriter) -> LuaResult<()> {
+    let thread_id = lua.push_thread_back(producer, writer.clone())?;
+    lua.track_thread(thread_id);
+
+    // NOTE: The writer may be kept alive by Lua after the producer
+    // has returned, so we must end the body explicitly here, and make
+    // sure that the client sees an aborted body if the producer errored
+    lua.spawn_local({
+        let lua = lua.clone();
+        async move {
+            lua.wait_for_thread(thread_id).await;
+            match lua.get_thread_result(thread_id) {
+                Some(Err(err)) => writer.abort(err.to_string()),
+                _ => writer.close(),
+            }
+        }
+    });
+
+    Ok(())
+}
+
 async fn handle_websocket(
     lua: Lua,
     handler: LuaFunction,
@@ -0,0 +1,137 @@
+use std::sync::Arc;
+
+use rustls::{RootCertStore, ServerConfig, server::WebPkiClientVerifier};
+use rustls_pki_types::{CertificateDer, PrivateKeyDer, pem::PemObject};
+
+use mlua::prelude::*;
+
+use crate::client::rustls::{PemSource, initialize_provider};
+
+/**
+    TLS configuration for `net.serve`.
+*/
+#[derive(Debug, Clone)]
+pub struct ServeTlsConfig {
+    cert: PemSource,
+    key: PemSource,
+    client_ca: Option<PemSource>,
+    require_client_cert: bool,
+}
+
+impl ServeTlsConfig {
+    /**
+        Reads all certificates and keys, and creates the server config.
+
+        # Errors
+
+        Errors if any certificate or key could not be read, or is invalid.
+    */
+    pub async fn server_config(&self) -> LuaResult<Arc<ServerConfig>> {
+        let cert = self.cert.read().await?;
+        let key = self.key.read().await?;
+        let client_ca = match &self.client_ca {
+            Some(client_ca) => Some(client_ca.read().await?),
+            None => None,
+        };
+
+        let config = Self::build(&cert, &key, client_ca.as_deref(), self.require_client_cert)
+            .map_err(|message| LuaError::RuntimeError(format!("Invalid tls config - {message}")))?;
+
+        Ok(Arc::new(config))
+    }
+
+    fn build(
+        cert: &[u8],
+        key: &[u8],
+        client_ca: Option<&[u8]>,
+        require_client_cert: bool,
+    ) -> Result<ServerConfig, String> {
+        initialize_provider();
+
+        let certs = CertificateDer::pem_slice_iter(cert)
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("failed to parse certificate - {e}"))?;
+        if certs.is_empty() {
+            return Err("certificate does not contain any PEM certificates".to_string());
+        }
+        let key = PrivateKeyDer::from_pem_slice(key)
+            .map_err(|e| format!("failed to parse private key - {e}"))?;
+
+        let builder = ServerConfig::builder();
+        let builder = if let Some(client_ca) = client_ca {
+            let mut roots = RootCertStore::empty();
+            for cert in CertificateDer::pem_slice_iter(client_ca) {
+                let cert = cert.map_err(|e| format!("failed to parse client CA - {e}"))?;
+                roots
+                    .add(cert)
+                    .map_err(|e| format!("invalid client CA certificate - {e}"))?;
+            }
+            if roots.is_empty() {
+                return Err("client CA does not contain any PEM certificates".to_string());
+            }
+
+            let verifier = WebPkiClientVerifier::builder(Arc::new(roots));
+            let verifier = if require_client_cert {
+                verifier
+            } else {
+                verifier.allow_unauthenticated()
+            };
+            builder.with_client_cert_verifier(verifier.build().map_err(|e| e.to_string())?)
+        } else {
+            builder.with_no_client_auth()
+        };
+
+        let mut config = builder
+            .with_single_cert(certs, key)
+            .map_err(|e| format!("invalid certificate or private key - {e}"))?;
+        config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+
+        Ok(config)
+    }
+}
+
+impl FromLua for ServeTlsConfig {
+    fn from_lua(value: LuaValue, _: &Lua) -> LuaResult<Self> {
+        let LuaValue::Table(t) = &value else {
+            return Err(LuaError::FromLuaConversionError {
+                from: value.type_name(),
+                to: "ServeTlsConfig".to_string(),
+                message: Some(format!(
+                    "Invalid tls config - expected table, got {}",
+                    value.type_name()
+                )),
+            });
+        };
+
+        let cert = PemSource::from_table(t, "cert")?;
+        let key = PemSource::from_table(t, "key")?;
+        let (Some(cert), Some(key)) = (cert, key) else {
+            return Err(LuaError::FromLuaConversionError {
+                from: value.type_name(),
+                to: "ServeTlsConfig".to_string(),
+                message: Some(
+                    "Invalid tls config - both 'cert' and 'key' must be given".to_string(),
+                ),
+            });
+        };
+        let client_ca = PemSource::from_table(t, "clientCa")?;
+        let require_client_cert: Option<bool> = t.get("requireClientCert")?;
+        if require_client_cert.is_some() && client_ca.is_none() {
+            return Err(LuaError::FromLuaConversionError {
+                from: value.type_name(),
+                to: "ServeTlsConfig".to_string(),
+                message: Some(
+                    "Invalid tls config - 'requireClientCert' can only be used with 'clientCa'"
+                        .to_string(),
+                ),
+            });
+        }
+
+        Ok(Self {
+            cert,
+            key,
+            client_ca,
+            require_client_cert: require_client_cert.unwrap_or(true),
+        })
+    }
+}
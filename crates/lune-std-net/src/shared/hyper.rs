@@ -49,6 +49,28 @@ where
     }
 }
 
+// Hyper executor that spawns thread-local futures onto our Lua scheduler,
+// necessary for HTTP/2 since our services and their futures are not Send
+
+#[derive(Debug, Clone)]
+pub struct HyperLocalExecutor {
+    lua: Lua,
+}
+
+impl From<Lua> for HyperLocalExecutor {
+    fn from(lua: Lua) -> Self {
+        Self { lua }
+    }
+}
+
+impl<Fut: Future + 'static> rt::Executor<Fut> for HyperLocalExecutor {
+    fn execute(&self, fut: Fut) {
+        self.lua.spawn_local(async move {
+            fut.await;
+        });
+    }
+}
+
 // Hyper timer & sleep future wrapper for async-io
 
 #[derive(Debug)]
@@ -4,5 +4,6 @@ pub mod hyper;
 pub mod lua;
 pub mod request;
 pub mod response;
+pub mod sse;
 pub mod tcp;
 pub mod websocket;
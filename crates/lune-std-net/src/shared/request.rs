@@ -1,4 +1,4 @@
-use std::{collections::HashMap, net::SocketAddr};
+use std::{collections::HashMap, net::SocketAddr, time::Duration};
 
 use url::Url;
 
@@ -8,20 +8,84 @@ use mlua::prelude::*;
 
 use crate::{
     body::{ReadableBody, handle_incoming_body},
+    client::{MAX_REDIRECTS, proxy::ProxyConfig, tls::RequestTlsConfig},
     shared::{
         headers::{hash_map_to_table, header_map_to_table},
         lua::{lua_table_to_header_map, lua_value_to_method},
     },
 };
 
+/**
+    An HTTP version that a request can be restricted to.
+
+    Requests that are not restricted to a version use HTTP/2 if the server
+    supports it, which is negotiated using ALPN, and HTTP/1.1 otherwise.
+    Requests restricted to HTTP/2 over plain HTTP use prior knowledge (h2c).
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HttpVersion {
+    Http1,
+    Http2,
+}
+
+impl FromLua for HttpVersion {
+    fn from_lua(value: LuaValue, _: &Lua) -> LuaResult<Self> {
+        if let LuaValue::String(s) = &value {
+            match s.to_string_lossy().to_ascii_lowercase().trim() {
+                "1.1" | "http/1.1" => Ok(Self::Http1),
+                "2" | "h2" => Ok(Self::Http2),
+                kind => Err(LuaError::FromLuaConversionError {
+                    from: value.type_name(),
+                    to: "HttpVersion".to_string(),
+                    message: Some(format!(
+                        "Invalid http version '{kind}', valid versions are:  1.1, 2"
+                    )),
+                }),
+            }
+        } else {
+            Err(LuaError::FromLuaConversionError {
+                from: value.type_name(),
+                to: "HttpVersion".to_string(),
+                message: None,
+            })
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct RequestOptions {
     pub decompress: bool,
+    pub stream: bool,
+    pub http_version: Option<HttpVersion>,
+    pub timeout: Option<Duration>,
+    pub connect_timeout: Option<Duration>,
+    pub max_redirects: usize,
+    pub proxy: Option<ProxyConfig>,
+    pub tls: Option<RequestTlsConfig>,
 }
 
 impl Default for RequestOptions {
     fn default() -> Self {
-        Self { decompress: true }
+        Self {
+            decompress: true,
+            stream: false,
+            http_version: None,
+            timeout: None,
+            connect_timeout: None,
+            max_redirects: MAX_REDIRECTS,
+            proxy: None,
+            tls: None,
+        }
+    }
+}
+
+fn get_timeout_option(tab: &LuaTable, key: &str) -> LuaResult<Option<Duration>> {
+    match tab.get::<Option<f64>>(key) {
+        Ok(None) => Ok(None),
+        Ok(Some(secs)) if secs.is_finite() && secs > 0.0 => Ok(Some(Duration::from_secs_f64(secs))),
+        _ => Err(LuaError::RuntimeError(format!(
+            "Invalid option value for '{key}' in request options - expected a positive number"
+        ))),
     }
 }
 
@@ -38,7 +102,28 @@ impl FromLua for RequestOptions {
                     "Invalid option value for 'decompress' in request options".to_string(),
                 )),
             }?;
-            Ok(Self { decompress })
+            let stream = match tab.get::<Option<bool>>("stream") {
+                Ok(stream) => Ok(stream.unwrap_or_default()),
+                Err(_) => Err(LuaError::RuntimeError(
+                    "Invalid option value for 'stream' in request options".to_string(),
+                )),
+            }?;
+            let max_redirects = match tab.get::<Option<usize>>("maxRedirects") {
+                Ok(max) => Ok(max.unwrap_or(MAX_REDIRECTS)),
+                Err(_) => Err(LuaError::RuntimeError(
+                    "Invalid option value for 'maxRedirects' in request options".to_string(),
+                )),
+            }?;
+            Ok(Self {
+                decompress,
+                stream,
+                http_version: tab.get("httpVersion")?,
+                timeout: get_timeout_option(&tab, "timeout")?,
+                connect_timeout: get_timeout_option(&tab, "connectTimeout")?,
+                max_redirects,
+                proxy: tab.get("proxy")?,
+                tls: tab.get("tls")?,
+            })
         } else {
             // Anything else is invalid
             Err(LuaError::FromLuaConversionError {
@@ -58,7 +143,7 @@ pub struct Request {
     pub(crate) inner: HyperRequest<ReadableBody>,
     pub(crate) address: Option<SocketAddr>,
     pub(crate) redirects: Option<usize>,
-    pub(crate) decompress: bool,
+    pub(crate) options: RequestOptions,
 }
 
 impl Request {
@@ -77,7 +162,10 @@ impl Request {
             inner: HyperRequest::from_parts(parts, ReadableBody::from(body)),
             address: None,
             redirects: None,
-            decompress,
+            options: RequestOptions {
+                decompress,
+                ..RequestOptions::default()
+            },
         })
     }
 
@@ -163,7 +251,10 @@ impl<B: Into<ReadableBody>> From<HyperRequest<B>> for Request {
             inner: HyperRequest::from_parts(parts, body.into()),
             address: None,
             redirects: None,
-            decompress: false,
+            options: RequestOptions {
+                decompress: false,
+                ..RequestOptions::default()
+            },
         }
     }
 }
@@ -183,7 +274,7 @@ impl FromLua for Request {
                 inner: request,
                 address: None,
                 redirects: None,
-                decompress: RequestOptions::default().decompress,
+                options: RequestOptions::default(),
             })
         } else if let LuaValue::Table(tab) = value {
             // If we got a table we are able to configure the
@@ -231,7 +322,7 @@ impl FromLua for Request {
                 inner: request,
                 address: None,
                 redirects: None,
-                decompress: options.decompress,
+                options,
             })
         } else {
             // Anything else is invalid
@@ -261,7 +352,7 @@ impl LuaUserData for Request {
             hash_map_to_table(lua, this.query(), false)
         });
         fields.add_field_method_get("headers", |lua, this| {
-            header_map_to_table(lua, this.headers().clone(), this.decompress)
+            header_map_to_table(lua, this.headers().clone(), this.options.decompress)
         });
         fields.add_field_method_get("body", |lua, this| lua.create_string(this.body()));
     }
@@ -7,7 +7,7 @@ use hyper::{
 use mlua::prelude::*;
 
 use crate::{
-    body::{ReadableBody, handle_incoming_body},
+    body::{IncomingBodyStream, ReadableBody, handle_incoming_body, incoming_body_decompressor},
     shared::{headers::header_map_to_table, lua::lua_table_to_header_map},
 };
 
@@ -15,6 +15,8 @@ use crate::{
 pub struct Response {
     pub(crate) inner: HyperResponse<ReadableBody>,
     pub(crate) decompressed: bool,
+    pub(crate) stream: Option<IncomingBodyStream>,
+    pub(crate) producer: Option<LuaFunction>,
 }
 
 impl Response {
@@ -32,6 +34,34 @@ impl Response {
         Ok(Self {
             inner: HyperResponse::from_parts(parts, ReadableBody::from(body)),
             decompressed,
+            stream: None,
+            producer: None,
+        })
+    }
+
+    /**
+        Creates a new response from a raw incoming response,
+        without receiving the body until it is read from Lua.
+
+        If `decompress` is `true`, the body is decompressed as it is being read.
+    */
+    pub fn from_incoming_stream(
+        incoming: HyperResponse<Incoming>,
+        decompress: bool,
+    ) -> LuaResult<Self> {
+        let (parts, body) = incoming.into_parts();
+
+        let decoder = if decompress {
+            incoming_body_decompressor(&parts.headers)?
+        } else {
+            None
+        };
+
+        Ok(Self {
+            inner: HyperResponse::from_parts(parts, ReadableBody::empty()),
+            decompressed: decoder.is_some(),
+            stream: Some(IncomingBodyStream::new(body, decoder)),
+            producer: None,
         })
     }
 
@@ -65,6 +95,8 @@ impl Response {
 
     /**
         Returns the body of the response.
+
+        This will be empty if the body is being streamed.
     */
     pub fn body(&self) -> &[u8] {
         self.inner.body().as_slice()
@@ -98,6 +130,8 @@ impl FromLua for Response {
             Ok(Self {
                 inner: response,
                 decompressed: false,
+                stream: None,
+                producer: None,
             })
         } else if let LuaValue::Table(tab) = value {
             // Extract status (required)
@@ -111,8 +145,11 @@ impl FromLua for Response {
                 .transpose()?
                 .unwrap_or_default();
 
-            // Extract body
-            let body = tab.get::<ReadableBody>("body")?;
+            // Extract body, which may also be a function producing the body over time
+            let (body, producer) = match tab.get::<LuaValue>("body")? {
+                LuaValue::Function(f) => (ReadableBody::empty(), Some(f)),
+                v => (ReadableBody::from_lua(v, lua)?, None),
+            };
 
             // Build the full response
             let mut response = HyperResponse::new(body);
@@ -123,6 +160,8 @@ impl FromLua for Response {
             Ok(Self {
                 inner: response,
                 decompressed: false,
+                stream: None,
+                producer,
             })
         } else {
             // Anything else is invalid
@@ -148,6 +187,9 @@ impl LuaUserData for Response {
         fields.add_field_method_get("headers", |lua, this| {
             header_map_to_table(lua, this.headers().clone(), this.decompressed)
         });
-        fields.add_field_method_get("body", |lua, this| lua.create_string(this.body()));
+        fields.add_field_method_get("body", |lua, this| match &this.stream {
+            Some(stream) => stream.clone().into_lua(lua),
+            None => lua.create_string(this.body())?.into_lua(lua),
+        });
     }
 }
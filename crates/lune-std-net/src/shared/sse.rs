@@ -0,0 +1,108 @@
+use std::fmt::Write as _;
+
+use bstr::{BString, ByteSlice};
+use mlua::prelude::*;
+
+/**
+    A single server-sent event, as used by `text/event-stream` responses.
+
+    See the [HTML specification](https://html.spec.whatwg.org/multipage/server-sent-events.html)
+    for more information about the format and how clients interpret each field.
+*/
+#[derive(Debug, Clone, Default)]
+pub struct ServerSentEvent {
+    pub event: Option<String>,
+    pub data: Option<BString>,
+    pub id: Option<String>,
+    pub retry: Option<u64>,
+    pub comment: Option<String>,
+}
+
+impl ServerSentEvent {
+    /**
+        Encodes the event into its `text/event-stream` representation,
+        including the trailing blank line that dispatches the event.
+    */
+    pub fn encode(&self) -> String {
+        let mut out = String::new();
+
+        if let Some(comment) = &self.comment {
+            for line in normalize_newlines(comment).split('\n') {
+                writeln!(out, ": {line}").unwrap();
+            }
+        }
+        if let Some(event) = &self.event {
+            writeln!(out, "event: {event}").unwrap();
+        }
+        if let Some(id) = &self.id {
+            writeln!(out, "id: {id}").unwrap();
+        }
+        if let Some(retry) = self.retry {
+            writeln!(out, "retry: {retry}").unwrap();
+        }
+        if let Some(data) = &self.data {
+            // NOTE: Each line of data must be sent as its own field,
+            // clients will then join them back together using newlines
+            let data = data.to_str_lossy();
+            for line in normalize_newlines(&data).split('\n') {
+                writeln!(out, "data: {line}").unwrap();
+            }
+        }
+
+        out.push('\n');
+        out
+    }
+}
+
+/**
+    Normalizes all of the line endings recognized by clients, meaning
+    `\r\n`, `\r` and `\n`, to `\n`, so that text can be split into lines
+    without any line ending being left over to start a new field.
+*/
+fn normalize_newlines(text: &str) -> String {
+    text.replace("\r\n", "\n").replace('\r', "\n")
+}
+
+fn get_single_line(tab: &LuaTable, key: &str) -> LuaResult<Option<String>> {
+    let value = tab.get::<Option<String>>(key)?;
+    if value
+        .as_ref()
+        .is_some_and(|v| v.contains(['\r', '\n', '\0']))
+    {
+        return Err(LuaError::RuntimeError(format!(
+            "Invalid server-sent event - '{key}' must not contain newlines or null characters"
+        )));
+    }
+    Ok(value)
+}
+
+impl FromLua for ServerSentEvent {
+    fn from_lua(value: LuaValue, _: &Lua) -> LuaResult<Self> {
+        match value {
+            // A plain string or buffer is an unnamed event containing only data
+            LuaValue::String(s) => Ok(Self {
+                data: Some(BString::from(s.as_bytes().to_vec())),
+                ..Default::default()
+            }),
+            LuaValue::Buffer(b) => Ok(Self {
+                data: Some(BString::from(b.to_vec())),
+                ..Default::default()
+            }),
+            LuaValue::Table(tab) => Ok(Self {
+                event: get_single_line(&tab, "event")?,
+                data: tab.get("data")?,
+                id: get_single_line(&tab, "id")?,
+                retry: tab.get("retry")?,
+                comment: tab.get("comment")?,
+            }),
+            v => Err(LuaError::FromLuaConversionError {
+                from: v.type_name(),
+                to: "ServerSentEvent".to_string(),
+                message: Some(format!(
+                    "Invalid server-sent event - expected table, string or buffer, got {}",
+                    v.type_name()
+                )),
+            }),
+        }
+    }
+}
@@ -0,0 +1,115 @@
+#![allow(clippy::cargo_common_metadata)]
+
+use std::path::{Component, MAIN_SEPARATOR_STR, Path, PathBuf};
+
+use mlua::prelude::*;
+
+use lune_utils::{
+    TableBuilder,
+    path::{clean_path, clean_path_and_make_absolute, relative_path_between},
+};
+
+const TYPEDEFS: &str = include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/types.d.luau"));
+
+/**
+    Returns a string containing type definitions for the `path` standard library.
+*/
+#[must_use]
+pub fn typedefs() -> String {
+    TYPEDEFS.to_string()
+}
+
+/**
+    Creates the `path` standard library module.
+
+    # Errors
+
+    Errors when out of memory.
+*/
+pub fn module(lua: Lua) -> LuaResult<LuaTable> {
+    TableBuilder::new(lua)?
+        .with_value("separator", MAIN_SEPARATOR_STR)?
+        .with_function("join", path_join)?
+        .with_function("normalize", path_normalize)?
+        .with_function("absolute", path_absolute)?
+        .with_function("relative", path_relative)?
+        .with_function("parent", path_parent)?
+        .with_function("name", path_name)?
+        .with_function("stem", path_stem)?
+        .with_function("extension", path_extension)?
+        .with_function("components", path_components)?
+        .with_function("isAbsolute", path_is_absolute)?
+        .build_readonly()
+}
+
+fn path_to_string(path: impl AsRef<Path>) -> String {
+    path.as_ref().to_string_lossy().into_owned()
+}
+
+fn path_join(_: &Lua, parts: LuaVariadic<String>) -> LuaResult<String> {
+    let joined = parts.iter().collect::<PathBuf>();
+    Ok(path_to_string(clean_path(joined)))
+}
+
+fn path_normalize(_: &Lua, path: String) -> LuaResult<String> {
+    Ok(path_to_string(clean_path(path)))
+}
+
+fn path_absolute(_: &Lua, path: String) -> LuaResult<String> {
+    Ok(path_to_string(clean_path_and_make_absolute(path)))
+}
+
+fn path_relative(_: &Lua, (path, base): (String, Option<String>)) -> LuaResult<String> {
+    let base = base.unwrap_or_else(|| String::from("."));
+    match relative_path_between(&path, &base) {
+        Some(rel) => Ok(path_to_string(rel)),
+        None => Err(LuaError::RuntimeError(format!(
+            "The path '{path}' can not be made relative to '{base}' since they have different roots"
+        ))),
+    }
+}
+
+fn path_parent(_: &Lua, path: String) -> LuaResult<Option<String>> {
+    Ok(Path::new(&path)
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+        .map(path_to_string))
+}
+
+fn path_name(_: &Lua, path: String) -> LuaResult<Option<String>> {
+    Ok(Path::new(&path).file_name().map(path_to_string))
+}
+
+fn path_stem(_: &Lua, path: String) -> LuaResult<Option<String>> {
+    Ok(Path::new(&path).file_stem().map(path_to_string))
+}
+
+fn path_extension(_: &Lua, path: String) -> LuaResult<Option<String>> {
+    Ok(Path::new(&path).extension().map(path_to_string))
+}
+
+fn path_components(_: &Lua, path: String) -> LuaResult<Vec<String>> {
+    let mut components = Vec::new();
+    let mut has_prefix = false;
+    for component in Path::new(&path).components() {
+        match component {
+            // Prefixes on Windows such as drive letters may be followed by a
+            // root, and we want to keep those together as a single component
+            Component::Prefix(prefix) => {
+                has_prefix = true;
+                components.push(path_to_string(prefix.as_os_str()));
+            }
+            Component::RootDir if has_prefix => {
+                if let Some(prefix) = components.last_mut() {
+                    prefix.push_str(MAIN_SEPARATOR_STR);
+                }
+            }
+            other => components.push(path_to_string(other.as_os_str())),
+        }
+    }
+    Ok(components)
+}
+
+fn path_is_absolute(_: &Lua, path: String) -> LuaResult<bool> {
+    Ok(Path::new(&path).is_absolute())
+}
@@ -0,0 +1,163 @@
+use std::fmt;
+
+use data_encoding::{
+    BASE32, BASE64, BASE64_NOPAD, BASE64URL, BASE64URL_NOPAD, DecodeKind, Encoding,
+    HEXLOWER_PERMISSIVE,
+};
+use mlua::prelude::*;
+
+/**
+    A binary-to-text encoding supported by Lune.
+
+    These are used to represent arbitrary binary data using only
+    printable characters, for example in HTTP headers and URLs.
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryTextEncoding {
+    Base64,
+    Base64NoPad,
+    Base64Url,
+    Base64UrlNoPad,
+    Base32,
+    Base58,
+    Hex,
+}
+
+impl BinaryTextEncoding {
+    pub const ALL: [Self; 7] = [
+        Self::Base64,
+        Self::Base64NoPad,
+        Self::Base64Url,
+        Self::Base64UrlNoPad,
+        Self::Base32,
+        Self::Base58,
+        Self::Hex,
+    ];
+
+    #[must_use]
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Base64 => "base64",
+            Self::Base64NoPad => "base64-nopad",
+            Self::Base64Url => "base64url",
+            Self::Base64UrlNoPad => "base64url-nopad",
+            Self::Base32 => "base32",
+            Self::Base58 => "base58",
+            Self::Hex => "hex",
+        }
+    }
+
+    /**
+        Parses an encoding from its name, ignoring case and
+        treating dashes and underscores as equivalent.
+    */
+    #[must_use]
+    pub fn from_name(name: &str) -> Option<Self> {
+        Some(match name.to_ascii_lowercase().replace('_', "-").as_str() {
+            "base64" => Self::Base64,
+            "base64-nopad" => Self::Base64NoPad,
+            "base64url" | "base64-url" => Self::Base64Url,
+            "base64url-nopad" | "base64-url-nopad" => Self::Base64UrlNoPad,
+            "base32" => Self::Base32,
+            "base58" => Self::Base58,
+            "hex" | "base16" => Self::Hex,
+            _ => return None,
+        })
+    }
+
+    fn encoding(self) -> Option<Encoding> {
+        match self {
+            Self::Base64 => Some(BASE64),
+            Self::Base64NoPad => Some(BASE64_NOPAD),
+            Self::Base64Url => Some(BASE64URL),
+            Self::Base64UrlNoPad => Some(BASE64URL_NOPAD),
+            Self::Base32 => Some(BASE32),
+            Self::Hex => Some(HEXLOWER_PERMISSIVE),
+            Self::Base58 => None,
+        }
+    }
+
+    /**
+        Encodes the given bytes into a string.
+
+        Hex is always encoded using lowercase letters.
+    */
+    #[must_use]
+    pub fn encode(self, bytes: impl AsRef<[u8]>) -> String {
+        let bytes = bytes.as_ref();
+        match self.encoding() {
+            Some(encoding) => encoding.encode(bytes),
+            None => bs58::encode(bytes).into_string(),
+        }
+    }
+
+    /**
+        Decodes the given string into bytes.
+
+        Decoding is strict, meaning that padding must be present if and
+        only if the encoding uses it, and that whitespace is not allowed.
+        Hex may be decoded from both lowercase and uppercase letters.
+
+        # Errors
+
+        Errors if the input contains invalid characters, has an invalid
+        length or padding, or has non-zero trailing bits.
+    */
+    pub fn decode(self, encoded: impl AsRef<[u8]>) -> LuaResult<Vec<u8>> {
+        let encoded = encoded.as_ref();
+        match self.encoding() {
+            Some(encoding) => encoding.decode(encoded).map_err(|e| {
+                let reason = match e.kind {
+                    DecodeKind::Length => "invalid length".to_string(),
+                    DecodeKind::Symbol => format!(
+                        "invalid character {} at position {}",
+                        DisplayByte(encoded[e.position]),
+                        e.position + 1
+                    ),
+                    DecodeKind::Trailing => {
+                        format!("non-zero trailing bits at position {}", e.position + 1)
+                    }
+                    DecodeKind::Padding => {
+                        format!("invalid padding at position {}", e.position + 1)
+                    }
+                };
+                self.decode_error(&reason)
+            }),
+            None => bs58::decode(encoded).into_vec().map_err(|e| match e {
+                bs58::decode::Error::InvalidCharacter { index, .. }
+                | bs58::decode::Error::NonAsciiCharacter { index } => self.decode_error(&format!(
+                    "invalid character {} at position {}",
+                    DisplayByte(encoded[index]),
+                    index + 1
+                )),
+                e => self.decode_error(&e.to_string()),
+            }),
+        }
+    }
+
+    fn decode_error(self, reason: &str) -> LuaError {
+        LuaError::RuntimeError(format!("Failed to decode {} - {reason}", self.name()))
+    }
+}
+
+impl fmt::Display for BinaryTextEncoding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/**
+    Displays a single byte of input in an error message, quoted if it is
+    a printable character, or as an escaped hex value if it is not.
+*/
+struct DisplayByte(u8);
+
+impl fmt::Display for DisplayByte {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.0.is_ascii_graphic() {
+            write!(f, "'{}'", char::from(self.0))
+        } else {
+            write!(f, "'\\x{:02X}'", self.0)
+        }
+    }
+}
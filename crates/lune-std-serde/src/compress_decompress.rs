@@ -1,5 +1,7 @@
+use std::fmt;
 use std::io::{Cursor, Read as _, Write as _, copy as copy_std};
 
+use bstr::BString;
 use mlua::prelude::*;
 
 use blocking::unblock;
@@ -10,7 +12,8 @@ use async_compression::{
     Level::Best as CompressionQuality,
     Level::Precise as PreciseCompressionQuality,
     futures::bufread::{
-        BrotliDecoder, BrotliEncoder, GzipDecoder, GzipEncoder, ZlibDecoder, ZlibEncoder,
+        BrotliDecoder, BrotliEncoder, GzipDecoder, GzipEncoder, XzDecoder, XzEncoder, ZlibDecoder,
+        ZlibEncoder, ZstdDecoder, ZstdEncoder,
     },
 };
 
@@ -23,6 +26,8 @@ pub enum CompressDecompressFormat {
     GZip,
     LZ4,
     ZLib,
+    Zstd,
+    Xz,
 }
 
 #[allow(dead_code)]
@@ -51,6 +56,12 @@ impl CompressDecompressFormat {
             {
                 Some(Self::Brotli)
             }
+            // https://github.com/facebook/zstd/blob/dev/doc/zstd_compression_format.md#zstandard-frames
+            b if b.len() >= 4 && matches!(b[0..4], [0x28, 0xB5, 0x2F, 0xFD]) => Some(Self::Zstd),
+            // https://tukaani.org/xz/xz-file-format.txt
+            b if b.len() >= 6 && matches!(b[0..6], [0xFD, 0x37, 0x7A, 0x58, 0x5A, 0x00]) => {
+                Some(Self::Xz)
+            }
             // https://github.com/rust-lang/flate2-rs/blob/main/src/gz/mod.rs#L135
             b if b.len() >= 3 && matches!(b[0..3], [0x1F, 0x8B, 0x08]) => Some(Self::GZip),
             // https://stackoverflow.com/a/43170354
@@ -77,11 +88,26 @@ impl CompressDecompressFormat {
             "br" | "brotli" => Some(Self::Brotli),
             "deflate" => Some(Self::ZLib),
             "gz" | "gzip" => Some(Self::GZip),
+            "zstd" => Some(Self::Zstd),
             _ => None,
         }
     }
 }
 
+impl fmt::Display for CompressDecompressFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            Self::Brotli => "brotli",
+            Self::GZip => "gzip",
+            Self::LZ4 => "lz4",
+            Self::ZLib => "zlib",
+            Self::Zstd => "zstd",
+            Self::Xz => "xz",
+        };
+        f.write_str(s)
+    }
+}
+
 impl FromLua for CompressDecompressFormat {
     fn from_lua(value: LuaValue, _: &Lua) -> LuaResult<Self> {
         if let LuaValue::String(s) = &value {
@@ -90,11 +116,13 @@ impl FromLua for CompressDecompressFormat {
                 "gzip" => Ok(Self::GZip),
                 "lz4" => Ok(Self::LZ4),
                 "zlib" => Ok(Self::ZLib),
+                "zstd" => Ok(Self::Zstd),
+                "xz" => Ok(Self::Xz),
                 kind => Err(LuaError::FromLuaConversionError {
                     from: value.type_name(),
                     to: "CompressDecompressFormat".to_string(),
                     message: Some(format!(
-                        "Invalid format '{kind}', valid formats are:  brotli, gzip, lz4, zlib"
+                        "Invalid format '{kind}', valid formats are:  brotli, gzip, lz4, zlib, zstd, xz"
                     )),
                 }),
             }
@@ -108,6 +136,70 @@ impl FromLua for CompressDecompressFormat {
     }
 }
 
+/**
+    Options for compressing and decompressing data.
+
+    The compression level is only used when compressing, and
+    dictionaries are currently only supported for the zstd format.
+*/
+#[derive(Debug, Clone, Default)]
+pub struct CompressDecompressOptions {
+    pub level: Option<i32>,
+    pub dictionary: Option<Vec<u8>>,
+}
+
+impl CompressDecompressOptions {
+    pub(crate) fn quality(&self) -> async_compression::Level {
+        match self.level {
+            Some(l) => PreciseCompressionQuality(l),
+            None => CompressionQuality,
+        }
+    }
+
+    pub(crate) fn ensure_supported(&self, format: CompressDecompressFormat) -> LuaResult<()> {
+        if self.dictionary.is_some() && !matches!(format, CompressDecompressFormat::Zstd) {
+            return Err(LuaError::RuntimeError(format!(
+                "Dictionaries are only supported for the zstd format, got '{format}'"
+            )));
+        }
+        Ok(())
+    }
+}
+
+impl FromLua for CompressDecompressOptions {
+    fn from_lua(value: LuaValue, _: &Lua) -> LuaResult<Self> {
+        Ok(match value {
+            LuaValue::Nil => Self::default(),
+            LuaValue::Integer(i) => Self {
+                level: Some(i32::try_from(i).into_lua_err()?),
+                dictionary: None,
+            },
+            #[allow(clippy::cast_possible_truncation)]
+            LuaValue::Number(n) => Self {
+                level: Some(n as i32),
+                dictionary: None,
+            },
+            LuaValue::Table(t) => {
+                let dictionary: Option<BString> = t.get("dictionary")?;
+                Self {
+                    level: t.get("level")?,
+                    dictionary: dictionary.map(Vec::from),
+                }
+            }
+            _ => {
+                return Err(LuaError::FromLuaConversionError {
+                    from: value.type_name(),
+                    to: "CompressDecompressOptions".to_string(),
+                    message: Some(format!(
+                        "Invalid compression options - expected number, table or nil, got {}",
+                        value.type_name()
+                    )),
+                });
+            }
+        })
+    }
+}
+
 /**
     Compresses the given bytes using the specified format.
 
@@ -120,6 +212,27 @@ pub async fn compress(
     format: CompressDecompressFormat,
     level: Option<i32>,
 ) -> LuaResult<Vec<u8>> {
+    let options = CompressDecompressOptions {
+        level,
+        dictionary: None,
+    };
+    compress_with_options(source, format, &options).await
+}
+
+/**
+    Compresses the given bytes using the specified format and options.
+
+    # Errors
+
+    Errors when the compression fails, or if the options are not supported by the format.
+*/
+pub async fn compress_with_options(
+    source: impl AsRef<[u8]>,
+    format: CompressDecompressFormat,
+    options: &CompressDecompressOptions,
+) -> LuaResult<Vec<u8>> {
+    options.ensure_supported(format)?;
+
     if let CompressDecompressFormat::LZ4 = format {
         let source = source.as_ref().to_vec();
         return unblock(move || compress_lz4(source)).await.into_lua_err();
@@ -127,10 +240,7 @@ pub async fn compress(
 
     let mut bytes = Vec::new();
     let reader = BufReader::new(source.as_ref());
-    let compression_quality = match level {
-        Some(l) => PreciseCompressionQuality(l),
-        None => CompressionQuality,
-    };
+    let compression_quality = options.quality();
 
     match format {
         CompressDecompressFormat::Brotli => {
@@ -145,6 +255,17 @@ pub async fn compress(
             let mut encoder = ZlibEncoder::with_quality(reader, compression_quality);
             copy(&mut encoder, &mut bytes).await?;
         }
+        CompressDecompressFormat::Zstd => {
+            let mut encoder = match &options.dictionary {
+                Some(dict) => ZstdEncoder::with_dict(reader, compression_quality, dict)?,
+                None => ZstdEncoder::with_quality(reader, compression_quality),
+            };
+            copy(&mut encoder, &mut bytes).await?;
+        }
+        CompressDecompressFormat::Xz => {
+            let mut encoder = XzEncoder::with_quality(reader, compression_quality);
+            copy(&mut encoder, &mut bytes).await?;
+        }
         CompressDecompressFormat::LZ4 => unreachable!(),
     }
 
@@ -162,6 +283,23 @@ pub async fn decompress(
     source: impl AsRef<[u8]>,
     format: CompressDecompressFormat,
 ) -> LuaResult<Vec<u8>> {
+    decompress_with_options(source, format, &CompressDecompressOptions::default()).await
+}
+
+/**
+    Decompresses the given bytes using the specified format and options.
+
+    # Errors
+
+    Errors when the decompression fails, or if the options are not supported by the format.
+*/
+pub async fn decompress_with_options(
+    source: impl AsRef<[u8]>,
+    format: CompressDecompressFormat,
+    options: &CompressDecompressOptions,
+) -> LuaResult<Vec<u8>> {
+    options.ensure_supported(format)?;
+
     if let CompressDecompressFormat::LZ4 = format {
         let source = source.as_ref().to_vec();
         return unblock(move || decompress_lz4(source)).await.into_lua_err();
@@ -183,6 +321,17 @@ pub async fn decompress(
             let mut decoder = ZlibDecoder::new(reader);
             copy(&mut decoder, &mut bytes).await?;
         }
+        CompressDecompressFormat::Zstd => {
+            let mut decoder = match &options.dictionary {
+                Some(dict) => ZstdDecoder::with_dict(reader, dict)?,
+                None => ZstdDecoder::new(reader),
+            };
+            copy(&mut decoder, &mut bytes).await?;
+        }
+        CompressDecompressFormat::Xz => {
+            let mut decoder = XzDecoder::new(reader);
+            copy(&mut decoder, &mut bytes).await?;
+        }
         CompressDecompressFormat::LZ4 => unreachable!(),
     }
 
@@ -0,0 +1,257 @@
+use std::fmt;
+use std::io;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+
+use async_compression::futures::write::{
+    BrotliDecoder, BrotliEncoder, GzipDecoder, GzipEncoder, XzDecoder, XzEncoder, ZlibDecoder,
+    ZlibEncoder, ZstdDecoder, ZstdEncoder,
+};
+use async_lock::Mutex as AsyncMutex;
+use bstr::BString;
+use futures_lite::io::{AsyncWrite, AsyncWriteExt};
+use mlua::prelude::*;
+
+use crate::compress_decompress::{CompressDecompressFormat, CompressDecompressOptions};
+
+/**
+    A writer that collects all output of a streaming
+    compressor or decompressor, until it is taken out.
+*/
+#[derive(Debug, Clone, Default)]
+struct OutputSink {
+    buffer: Arc<Mutex<Vec<u8>>>,
+}
+
+impl OutputSink {
+    fn take(&self) -> Vec<u8> {
+        std::mem::take(&mut *self.buffer.lock().expect("poisoned lock"))
+    }
+}
+
+impl AsyncWrite for OutputSink {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        _: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        self.buffer
+            .lock()
+            .expect("poisoned lock")
+            .extend_from_slice(buf);
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+type BoxedWriter = Box<dyn AsyncWrite + Send + Unpin>;
+
+fn unsupported_lz4() -> LuaError {
+    // NOTE: Our lz4 format is prefixed with the total uncompressed size,
+    // which is not known up front when compressing data in chunks
+    LuaError::runtime("Streaming is not supported for the lz4 format")
+}
+
+// Inner (plumbing) implementation
+
+struct StreamWriterInner {
+    writer: Option<BoxedWriter>,
+    output: OutputSink,
+    flush_on_write: bool,
+}
+
+impl StreamWriterInner {
+    fn new_encoder(
+        format: CompressDecompressFormat,
+        options: &CompressDecompressOptions,
+    ) -> LuaResult<Self> {
+        options.ensure_supported(format)?;
+
+        let output = OutputSink::default();
+        let sink = output.clone();
+        let quality = options.quality();
+        let writer: BoxedWriter = match format {
+            CompressDecompressFormat::Brotli => {
+                Box::new(BrotliEncoder::with_quality(sink, quality))
+            }
+            CompressDecompressFormat::GZip => Box::new(GzipEncoder::with_quality(sink, quality)),
+            CompressDecompressFormat::ZLib => Box::new(ZlibEncoder::with_quality(sink, quality)),
+            CompressDecompressFormat::Zstd => match &options.dictionary {
+                Some(dict) => Box::new(ZstdEncoder::with_dict(sink, quality, dict)?),
+                None => Box::new(ZstdEncoder::with_quality(sink, quality)),
+            },
+            CompressDecompressFormat::Xz => Box::new(XzEncoder::with_quality(sink, quality)),
+            CompressDecompressFormat::LZ4 => return Err(unsupported_lz4()),
+        };
+
+        // NOTE: Flushing an encoder ends the current block, which makes
+        // compression worse, so we let the encoder decide when to output
+        Ok(Self {
+            writer: Some(writer),
+            output,
+            flush_on_write: false,
+        })
+    }
+
+    fn new_decoder(
+        format: CompressDecompressFormat,
+        options: &CompressDecompressOptions,
+    ) -> LuaResult<Self> {
+        options.ensure_supported(format)?;
+
+        let output = OutputSink::default();
+        let sink = output.clone();
+        let writer: BoxedWriter = match format {
+            CompressDecompressFormat::Brotli => Box::new(BrotliDecoder::new(sink)),
+            CompressDecompressFormat::GZip => Box::new(GzipDecoder::new(sink)),
+            CompressDecompressFormat::ZLib => Box::new(ZlibDecoder::new(sink)),
+            CompressDecompressFormat::Zstd => match &options.dictionary {
+                Some(dict) => Box::new(ZstdDecoder::with_dict(sink, dict)?),
+                None => Box::new(ZstdDecoder::new(sink)),
+            },
+            CompressDecompressFormat::Xz => Box::new(XzDecoder::new(sink)),
+            CompressDecompressFormat::LZ4 => return Err(unsupported_lz4()),
+        };
+
+        Ok(Self {
+            writer: Some(writer),
+            output,
+            flush_on_write: true,
+        })
+    }
+
+    fn writer(&mut self) -> LuaResult<&mut BoxedWriter> {
+        self.writer
+            .as_mut()
+            .ok_or_else(|| LuaError::runtime("Stream has already been finished"))
+    }
+
+    async fn write(&mut self, chunk: &[u8]) -> LuaResult<Vec<u8>> {
+        let flush = self.flush_on_write;
+        let writer = self.writer()?;
+        writer.write_all(chunk).await?;
+        if flush {
+            writer.flush().await?;
+        }
+        Ok(self.output.take())
+    }
+
+    async fn finish(&mut self) -> LuaResult<Vec<u8>> {
+        let mut writer = self
+            .writer
+            .take()
+            .ok_or_else(|| LuaError::runtime("Stream has already been finished"))?;
+        writer.close().await?;
+        Ok(self.output.take())
+    }
+}
+
+// Outer (lua-accessible, clonable) implementation
+
+/**
+    A streaming compressor or decompressor, created
+    using `serde.compressor` or `serde.decompressor`.
+
+    Input is written in chunks, and any output that is
+    ready is returned from each call to `write`, with
+    the remaining output being returned from `finish`.
+*/
+#[derive(Clone)]
+pub struct CompressDecompressStream {
+    inner: Arc<AsyncMutex<StreamWriterInner>>,
+}
+
+impl CompressDecompressStream {
+    /**
+        Creates a new streaming compressor for the given format.
+
+        # Errors
+
+        Errors if the format does not support streaming, or if the options are invalid.
+    */
+    pub fn compressor(
+        format: CompressDecompressFormat,
+        options: &CompressDecompressOptions,
+    ) -> LuaResult<Self> {
+        let inner = StreamWriterInner::new_encoder(format, options)?;
+        Ok(Self {
+            inner: Arc::new(AsyncMutex::new(inner)),
+        })
+    }
+
+    /**
+        Creates a new streaming decompressor for the given format.
+
+        # Errors
+
+        Errors if the format does not support streaming, or if the options are invalid.
+    */
+    pub fn decompressor(
+        format: CompressDecompressFormat,
+        options: &CompressDecompressOptions,
+    ) -> LuaResult<Self> {
+        let inner = StreamWriterInner::new_decoder(format, options)?;
+        Ok(Self {
+            inner: Arc::new(AsyncMutex::new(inner)),
+        })
+    }
+
+    /**
+        Writes a chunk of input, returning any output that is ready.
+
+        # Errors
+
+        Errors if the stream has already been finished, or if the input is invalid.
+    */
+    pub async fn write(&self, chunk: &[u8]) -> LuaResult<Vec<u8>> {
+        self.inner.lock().await.write(chunk).await
+    }
+
+    /**
+        Finishes the stream, returning all of the remaining output.
+
+        # Errors
+
+        Errors if the stream has already been finished, or if the input was incomplete.
+    */
+    pub async fn finish(&self) -> LuaResult<Vec<u8>> {
+        self.inner.lock().await.finish().await
+    }
+}
+
+impl fmt::Debug for CompressDecompressStream {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CompressDecompressStream")
+            .finish_non_exhaustive()
+    }
+}
+
+impl LuaUserData for CompressDecompressStream {
+    fn add_fields<F: LuaUserDataFields<Self>>(fields: &mut F) {
+        fields.add_field_method_get("finished", |_, this| {
+            Ok(this
+                .inner
+                .try_lock()
+                .is_some_and(|inner| inner.writer.is_none()))
+        });
+    }
+
+    fn add_methods<M: LuaUserDataMethods<Self>>(methods: &mut M) {
+        methods.add_async_method("write", |lua, this, chunk: BString| {
+            let this = this.clone();
+            async move { lua.create_string(this.write(&chunk).await?) }
+        });
+        methods.add_async_method("finish", |lua, this, (): ()| {
+            let this = this.clone();
+            async move { lua.create_string(this.finish().await?) }
+        });
+    }
+}
@@ -0,0 +1,512 @@
+use std::collections::BTreeSet;
+use std::sync::{Arc, Mutex};
+
+use bstr::{BString, ByteSlice};
+use csv::{QuoteStyle, WriterBuilder};
+use csv_core::{ReadRecordResult, Reader as CoreReader, ReaderBuilder as CoreReaderBuilder};
+use mlua::prelude::*;
+
+const INITIAL_FIELDS_CAPACITY: usize = 1024;
+const INITIAL_ENDS_CAPACITY: usize = 16;
+
+/**
+    A quoting style to use when encoding CSV.
+*/
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CsvQuoteStyle {
+    #[default]
+    Necessary,
+    Always,
+    NonNumeric,
+    Never,
+}
+
+impl FromLua for CsvQuoteStyle {
+    fn from_lua(value: LuaValue, _: &Lua) -> LuaResult<Self> {
+        if let LuaValue::String(s) = &value {
+            match s.to_string_lossy().to_ascii_lowercase().trim() {
+                "necessary" => Ok(Self::Necessary),
+                "always" => Ok(Self::Always),
+                "nonnumeric" => Ok(Self::NonNumeric),
+                "never" => Ok(Self::Never),
+                kind => Err(LuaError::FromLuaConversionError {
+                    from: value.type_name(),
+                    to: "CsvQuoteStyle".to_string(),
+                    message: Some(format!(
+                        "Invalid quote style '{kind}', valid quote styles are:  necessary, always, nonnumeric, never"
+                    )),
+                }),
+            }
+        } else {
+            Err(LuaError::FromLuaConversionError {
+                from: value.type_name(),
+                to: "CsvQuoteStyle".to_string(),
+                message: None,
+            })
+        }
+    }
+}
+
+impl From<CsvQuoteStyle> for QuoteStyle {
+    fn from(value: CsvQuoteStyle) -> Self {
+        match value {
+            CsvQuoteStyle::Necessary => Self::Necessary,
+            CsvQuoteStyle::Always => Self::Always,
+            CsvQuoteStyle::NonNumeric => Self::NonNumeric,
+            CsvQuoteStyle::Never => Self::Never,
+        }
+    }
+}
+
+/**
+    Options for encoding and decoding CSV.
+*/
+#[derive(Debug, Clone)]
+pub struct CsvOptions {
+    pub delimiter: u8,
+    pub quote: u8,
+    pub escape: Option<u8>,
+    pub headers: bool,
+    pub flexible: bool,
+    pub quote_style: CsvQuoteStyle,
+    pub columns: Option<Vec<String>>,
+}
+
+impl Default for CsvOptions {
+    fn default() -> Self {
+        Self {
+            delimiter: b',',
+            quote: b'"',
+            escape: None,
+            headers: true,
+            flexible: false,
+            quote_style: CsvQuoteStyle::default(),
+            columns: None,
+        }
+    }
+}
+
+fn single_byte_option(table: &LuaTable, key: &str) -> LuaResult<Option<u8>> {
+    match table.get::<Option<BString>>(key)? {
+        None => Ok(None),
+        Some(s) if s.len() == 1 => Ok(Some(s[0])),
+        Some(s) => Err(LuaError::RuntimeError(format!(
+            "Invalid csv options - {key} must be a single character, got '{s}'"
+        ))),
+    }
+}
+
+impl FromLua for CsvOptions {
+    fn from_lua(value: LuaValue, _: &Lua) -> LuaResult<Self> {
+        Ok(match value {
+            LuaValue::Nil => Self::default(),
+            LuaValue::Table(t) => {
+                let defaults = Self::default();
+                let headers: Option<bool> = t.get("headers")?;
+                let flexible: Option<bool> = t.get("flexible")?;
+                let quote_style: Option<CsvQuoteStyle> = t.get("quoteStyle")?;
+                Self {
+                    delimiter: single_byte_option(&t, "delimiter")?.unwrap_or(defaults.delimiter),
+                    quote: single_byte_option(&t, "quote")?.unwrap_or(defaults.quote),
+                    escape: single_byte_option(&t, "escape")?,
+                    headers: headers.unwrap_or(defaults.headers),
+                    flexible: flexible.unwrap_or(defaults.flexible),
+                    quote_style: quote_style.unwrap_or(defaults.quote_style),
+                    columns: t.get("columns")?,
+                }
+            }
+            _ => {
+                return Err(LuaError::FromLuaConversionError {
+                    from: value.type_name(),
+                    to: "CsvOptions".to_string(),
+                    message: Some(format!(
+                        "Invalid csv options - expected table or nil, got {}",
+                        value.type_name()
+                    )),
+                });
+            }
+        })
+    }
+}
+
+/**
+    Converts a single lua value into the bytes of a CSV field.
+*/
+fn field_from_lua(lua: &Lua, value: LuaValue) -> LuaResult<Vec<u8>> {
+    Ok(match value {
+        LuaValue::Nil => Vec::new(),
+        LuaValue::Boolean(b) => b.to_string().into_bytes(),
+        LuaValue::String(s) => s.as_bytes().to_vec(),
+        LuaValue::Buffer(b) => b.to_vec(),
+        LuaValue::Integer(_) | LuaValue::Number(_) => match lua.coerce_string(value)? {
+            Some(s) => s.as_bytes().to_vec(),
+            None => Vec::new(),
+        },
+        value => {
+            return Err(LuaError::RuntimeError(format!(
+                "Invalid csv field - expected string, number, boolean or nil, got {}",
+                value.type_name()
+            )));
+        }
+    })
+}
+
+/**
+    Encodes the given rows into CSV, using the given options.
+
+    With headers enabled, rows must be dictionaries, and the header
+    row is either the given columns, or all keys found in the rows,
+    sorted. Otherwise, rows must be arrays of values.
+
+    # Errors
+
+    Errors if the rows are not a list of tables, or if any value can not be written as a field.
+*/
+pub fn encode(lua: &Lua, rows: LuaValue, options: &CsvOptions) -> LuaResult<LuaString> {
+    let LuaValue::Table(rows) = rows else {
+        return Err(LuaError::RuntimeError(format!(
+            "Invalid csv rows - expected table, got {}",
+            rows.type_name()
+        )));
+    };
+    let rows = rows
+        .sequence_values::<LuaTable>()
+        .collect::<LuaResult<Vec<_>>>()?;
+
+    let mut builder = WriterBuilder::new();
+    builder
+        .delimiter(options.delimiter)
+        .quote(options.quote)
+        .quote_style(options.quote_style.into())
+        .flexible(options.flexible);
+    if let Some(escape) = options.escape {
+        builder.escape(escape).double_quote(false);
+    }
+    let mut writer = builder.from_writer(Vec::new());
+
+    if options.headers {
+        let columns = if let Some(columns) = &options.columns {
+            columns.clone()
+        } else {
+            let mut keys = BTreeSet::new();
+            for row in &rows {
+                for pair in row.pairs::<LuaValue, LuaValue>() {
+                    let (key, _) = pair?;
+                    if let LuaValue::String(key) = key {
+                        keys.insert(key.to_str()?.to_string());
+                    }
+                }
+            }
+            keys.into_iter().collect()
+        };
+        writer.write_record(&columns).into_lua_err()?;
+        for row in rows {
+            let mut record = Vec::with_capacity(columns.len());
+            for column in &columns {
+                record.push(field_from_lua(lua, row.get(column.as_str())?)?);
+            }
+            writer.write_record(record).into_lua_err()?;
+        }
+    } else {
+        for row in rows {
+            let mut record = Vec::new();
+            for value in row.sequence_values::<LuaValue>() {
+                record.push(field_from_lua(lua, value?)?);
+            }
+            writer.write_record(record).into_lua_err()?;
+        }
+    }
+
+    let bytes = writer
+        .into_inner()
+        .map_err(csv::IntoInnerError::into_error)
+        .into_lua_err()?;
+    lua.create_string(bytes)
+}
+
+/**
+    A single step of the incremental decoder.
+*/
+enum CsvDecodeStep {
+    Row(Vec<Vec<u8>>),
+    NeedInput,
+    End,
+}
+
+/**
+    An incremental CSV decoder, which is fed input in chunks
+    of any size, and yields complete records as they are found.
+*/
+struct CsvDecoder {
+    core: CoreReader,
+    input: Vec<u8>,
+    input_pos: usize,
+    input_done: bool,
+    fields: Vec<u8>,
+    fields_len: usize,
+    ends: Vec<usize>,
+    ends_len: usize,
+    headers: bool,
+    header_row: Option<Vec<Vec<u8>>>,
+    flexible: bool,
+    expected_len: Option<usize>,
+}
+
+impl CsvDecoder {
+    fn new(options: &CsvOptions) -> Self {
+        let mut builder = CoreReaderBuilder::new();
+        builder.delimiter(options.delimiter).quote(options.quote);
+        if let Some(escape) = options.escape {
+            builder.escape(Some(escape)).double_quote(false);
+        }
+        Self {
+            core: builder.build(),
+            input: Vec::new(),
+            input_pos: 0,
+            input_done: false,
+            fields: vec![0; INITIAL_FIELDS_CAPACITY],
+            fields_len: 0,
+            ends: vec![0; INITIAL_ENDS_CAPACITY],
+            ends_len: 0,
+            headers: options.headers,
+            header_row: None,
+            flexible: options.flexible,
+            expected_len: None,
+        }
+    }
+
+    fn push(&mut self, chunk: &[u8]) {
+        // Drop any input that has already been consumed, so
+        // that we never hold more than a single chunk in memory
+        self.input.drain(..self.input_pos);
+        self.input_pos = 0;
+        self.input.extend_from_slice(chunk);
+    }
+
+    fn finish(&mut self) {
+        self.input_done = true;
+    }
+
+    fn next_record(&mut self) -> LuaResult<CsvDecodeStep> {
+        loop {
+            let input = &self.input[self.input_pos..];
+            if input.is_empty() && !self.input_done {
+                return Ok(CsvDecodeStep::NeedInput);
+            }
+
+            let (result, nin, nout, nend) = self.core.read_record(
+                input,
+                &mut self.fields[self.fields_len..],
+                &mut self.ends[self.ends_len..],
+            );
+            self.input_pos += nin;
+            self.fields_len += nout;
+            self.ends_len += nend;
+
+            match result {
+                ReadRecordResult::InputEmpty => {
+                    // An empty input signals the end of all input, so
+                    // the reader should never ask for more after it
+                    if input.is_empty() {
+                        return Ok(CsvDecodeStep::End);
+                    }
+                }
+                ReadRecordResult::OutputFull => {
+                    self.fields.resize(self.fields.len() * 2, 0);
+                }
+                ReadRecordResult::OutputEndsFull => {
+                    self.ends.resize(self.ends.len() * 2, 0);
+                }
+                ReadRecordResult::Record => {
+                    let mut start = 0;
+                    let mut row = Vec::with_capacity(self.ends_len);
+                    for &end in &self.ends[..self.ends_len] {
+                        row.push(self.fields[start..end].to_vec());
+                        start = end;
+                    }
+                    self.fields_len = 0;
+                    self.ends_len = 0;
+
+                    if !self.flexible {
+                        match self.expected_len {
+                            None => self.expected_len = Some(row.len()),
+                            Some(expected) if expected != row.len() => {
+                                return Err(LuaError::RuntimeError(format!(
+                                    "Invalid csv - record on line {} has {} fields, but previous records have {}",
+                                    self.core.line().saturating_sub(1),
+                                    row.len(),
+                                    expected
+                                )));
+                            }
+                            Some(_) => {}
+                        }
+                    }
+
+                    if self.headers && self.header_row.is_none() {
+                        self.header_row = Some(row);
+                        continue;
+                    }
+                    return Ok(CsvDecodeStep::Row(row));
+                }
+                ReadRecordResult::End => return Ok(CsvDecodeStep::End),
+            }
+        }
+    }
+
+    fn row_into_lua(&self, lua: &Lua, row: Vec<Vec<u8>>) -> LuaResult<LuaTable> {
+        if let Some(headers) = &self.header_row {
+            let tab = lua.create_table_with_capacity(0, headers.len())?;
+            for (header, field) in headers.iter().zip(row) {
+                tab.raw_set(lua.create_string(header)?, lua.create_string(field)?)?;
+            }
+            Ok(tab)
+        } else {
+            let tab = lua.create_table_with_capacity(row.len(), 0)?;
+            for field in row {
+                tab.raw_push(lua.create_string(field)?)?;
+            }
+            Ok(tab)
+        }
+    }
+
+    fn headers_into_lua(&self, lua: &Lua) -> LuaResult<Option<LuaTable>> {
+        self.header_row
+            .as_ref()
+            .map(|headers| {
+                lua.create_sequence_from(
+                    headers
+                        .iter()
+                        .map(|header| lua.create_string(header))
+                        .collect::<LuaResult<Vec<_>>>()?,
+                )
+            })
+            .transpose()
+    }
+}
+
+/**
+    Decodes the given CSV into a list of rows, using the given options.
+
+    With headers enabled, the first record is used as keys for all
+    other rows, and rows are returned as dictionaries of strings.
+    Otherwise, rows are returned as arrays of strings.
+
+    # Errors
+
+    Errors if the CSV is invalid, or if records have differing lengths and `flexible` is not set.
+*/
+pub fn decode(lua: &Lua, bytes: impl AsRef<[u8]>, options: &CsvOptions) -> LuaResult<LuaTable> {
+    let mut decoder = CsvDecoder::new(options);
+    decoder.push(bytes.as_ref());
+    decoder.finish();
+
+    let rows = lua.create_table()?;
+    while let CsvDecodeStep::Row(row) = decoder.next_record()? {
+        rows.raw_push(decoder.row_into_lua(lua, row)?)?;
+    }
+    Ok(rows)
+}
+
+/**
+    A source of CSV data for a `CsvReader`.
+*/
+#[derive(Debug, Clone)]
+pub enum CsvSource {
+    Bytes(Vec<u8>),
+    Function(LuaFunction),
+}
+
+impl FromLua for CsvSource {
+    fn from_lua(value: LuaValue, lua: &Lua) -> LuaResult<Self> {
+        match value {
+            LuaValue::Function(f) => Ok(Self::Function(f)),
+            LuaValue::String(_) | LuaValue::Buffer(_) => {
+                Ok(Self::Bytes(BString::from_lua(value, lua)?.into()))
+            }
+            _ => Err(LuaError::FromLuaConversionError {
+                from: value.type_name(),
+                to: "CsvSource".to_string(),
+                message: Some(format!(
+                    "Invalid csv source - expected string, buffer or function, got {}",
+                    value.type_name()
+                )),
+            }),
+        }
+    }
+}
+
+/**
+    A reader that decodes CSV row-by-row, created using `serde.csv.reader`.
+
+    When given a function as its source, the function is called for more
+    input whenever it is needed, so that large files never have to be
+    fully read into memory, and returning `nil` signals the end of input.
+*/
+#[derive(Clone)]
+pub struct CsvReader {
+    decoder: Arc<Mutex<CsvDecoder>>,
+    source: Option<LuaFunction>,
+}
+
+impl CsvReader {
+    #[must_use]
+    pub fn new(source: CsvSource, options: &CsvOptions) -> Self {
+        let mut decoder = CsvDecoder::new(options);
+        let source = match source {
+            CsvSource::Bytes(bytes) => {
+                decoder.push(&bytes);
+                decoder.finish();
+                None
+            }
+            CsvSource::Function(f) => Some(f),
+        };
+        Self {
+            decoder: Arc::new(Mutex::new(decoder)),
+            source,
+        }
+    }
+
+    async fn next(&self, lua: &Lua) -> LuaResult<Option<LuaTable>> {
+        loop {
+            // NOTE: We must not hold the lock while calling the source
+            // function, since it may yield or use this reader again
+            let step = self.decoder.lock().expect("poisoned lock").next_record()?;
+            match step {
+                CsvDecodeStep::Row(row) => {
+                    let decoder = self.decoder.lock().expect("poisoned lock");
+                    return decoder.row_into_lua(lua, row).map(Some);
+                }
+                CsvDecodeStep::End => return Ok(None),
+                CsvDecodeStep::NeedInput => {
+                    let chunk = match &self.source {
+                        Some(source) => source.call_async::<Option<BString>>(()).await?,
+                        None => None,
+                    };
+                    let mut decoder = self.decoder.lock().expect("poisoned lock");
+                    match chunk {
+                        Some(chunk) if !chunk.is_empty() => decoder.push(chunk.as_bytes()),
+                        _ => decoder.finish(),
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl LuaUserData for CsvReader {
+    fn add_fields<F: LuaUserDataFields<Self>>(fields: &mut F) {
+        fields.add_field_method_get("headers", |lua, this| {
+            this.decoder
+                .lock()
+                .expect("poisoned lock")
+                .headers_into_lua(lua)
+        });
+    }
+
+    fn add_methods<M: LuaUserDataMethods<Self>>(methods: &mut M) {
+        methods.add_async_method("next", |lua, this, (): ()| {
+            let this = this.clone();
+            async move { this.next(&lua).await }
+        });
+    }
+}
@@ -0,0 +1,148 @@
+use blake3::Hasher as Blake3;
+use digest::OutputSizeUser;
+use hkdf::{Hkdf, SimpleHkdf};
+use hmac::{Hmac, SimpleHmac};
+use md5::Md5;
+use mlua::prelude::*;
+use sha1::Sha1;
+use sha2::{Sha224, Sha256, Sha384, Sha512};
+use sha3::{Sha3_224, Sha3_256, Sha3_384, Sha3_512};
+
+use crate::hash::HashAlgorithm;
+
+/*
+    These macros exist for the same reason as the ones used for HMAC - the
+    `Hmac` struct only supports block-based hash functions, and blake3 needs
+    to use `SimpleHmac` instead, which is reflected in the `hkdf` crate too.
+*/
+macro_rules! dispatch {
+    ($algorithm:expr, $with:ident, $with_no_blocks:ident) => {
+        match $algorithm {
+            HashAlgorithm::Md5 => $with!(Md5),
+            HashAlgorithm::Sha1 => $with!(Sha1),
+
+            HashAlgorithm::Sha2_224 => $with!(Sha224),
+            HashAlgorithm::Sha2_256 => $with!(Sha256),
+            HashAlgorithm::Sha2_384 => $with!(Sha384),
+            HashAlgorithm::Sha2_512 => $with!(Sha512),
+
+            HashAlgorithm::Sha3_224 => $with!(Sha3_224),
+            HashAlgorithm::Sha3_256 => $with!(Sha3_256),
+            HashAlgorithm::Sha3_384 => $with!(Sha3_384),
+            HashAlgorithm::Sha3_512 => $with!(Sha3_512),
+
+            HashAlgorithm::Blake3 => $with_no_blocks!(Blake3),
+
+            HashAlgorithm::Crc32
+            | HashAlgorithm::Adler32
+            | HashAlgorithm::Xxh64
+            | HashAlgorithm::Xxh3
+            | HashAlgorithm::SipHash => unreachable!("algorithm was checked to be cryptographic"),
+        }
+    };
+}
+
+/**
+    The maximum length of a key derived using PBKDF2, which is far
+    longer than any key should be, but keeps the output allocation sane.
+*/
+const MAX_PBKDF2_KEY_LENGTH: usize = 1024 * 1024;
+
+fn ensure_valid_length(length: usize, max_length: usize, kdf: &str) -> LuaResult<()> {
+    if length == 0 {
+        return Err(LuaError::RuntimeError(
+            "Derived key length must be greater than zero".to_string(),
+        ));
+    }
+    if length > max_length {
+        return Err(LuaError::RuntimeError(format!(
+            "{kdf} key length {length} is too long, the maximum is {max_length}"
+        )));
+    }
+    Ok(())
+}
+
+/**
+    Derives a key of the given length from a password and salt using
+    PBKDF2, with HMAC using the given hash algorithm as its PRF.
+
+    # Errors
+
+    Errors if the algorithm is not a cryptographic hash function, if the
+    number of iterations or the key length is zero, or if the key is too long.
+*/
+pub fn pbkdf2(
+    algorithm: HashAlgorithm,
+    password: &[u8],
+    salt: &[u8],
+    iterations: u32,
+    length: usize,
+) -> LuaResult<Vec<u8>> {
+    algorithm.ensure_cryptographic("PBKDF2")?;
+    ensure_valid_length(length, MAX_PBKDF2_KEY_LENGTH, "PBKDF2")?;
+    if iterations == 0 {
+        return Err(LuaError::RuntimeError(
+            "PBKDF2 iterations must be greater than zero".to_string(),
+        ));
+    }
+
+    let mut output = vec![0; length];
+    macro_rules! pbkdf2 {
+        ($Type:ty) => {
+            pbkdf2::pbkdf2::<Hmac<$Type>>(password, salt, iterations, &mut output)
+        };
+    }
+    macro_rules! pbkdf2_no_blocks {
+        ($Type:ty) => {
+            pbkdf2::pbkdf2::<SimpleHmac<$Type>>(password, salt, iterations, &mut output)
+        };
+    }
+    dispatch!(algorithm, pbkdf2, pbkdf2_no_blocks).into_lua_err()?;
+
+    Ok(output)
+}
+
+/**
+    Derives a key of the given length from input keying material using
+    HKDF, with the given hash algorithm, and an optional salt and info.
+
+    # Errors
+
+    Errors if the algorithm is not a cryptographic hash function, or if the
+    key length is zero or longer than 255 times the output size of the hash.
+*/
+pub fn hkdf(
+    algorithm: HashAlgorithm,
+    key: &[u8],
+    salt: Option<&[u8]>,
+    info: &[u8],
+    length: usize,
+) -> LuaResult<Vec<u8>> {
+    algorithm.ensure_cryptographic("HKDF")?;
+
+    // NOTE: The length must be checked before allocating the output,
+    // since HKDF itself would only reject it after the allocation
+    macro_rules! output_size {
+        ($Type:ty) => {
+            <$Type as OutputSizeUser>::output_size()
+        };
+    }
+    let output_size = dispatch!(algorithm, output_size, output_size);
+    ensure_valid_length(length, 255 * output_size, "HKDF")?;
+
+    let mut output = vec![0; length];
+    macro_rules! hkdf {
+        ($Type:ty) => {
+            Hkdf::<$Type>::new(salt, key).expand(info, &mut output)
+        };
+    }
+    macro_rules! hkdf_no_blocks {
+        ($Type:ty) => {
+            SimpleHkdf::<$Type>::new(salt, key).expand(info, &mut output)
+        };
+    }
+    dispatch!(algorithm, hkdf, hkdf_no_blocks)
+        .map_err(|e| LuaError::RuntimeError(format!("HKDF failed to expand key - {e}")))?;
+
+    Ok(output)
+}
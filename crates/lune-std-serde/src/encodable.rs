@@ -0,0 +1,225 @@
+use std::cell::RefCell;
+use std::cmp::Ordering;
+use std::collections::HashSet;
+use std::ffi::c_void;
+
+use mlua::prelude::*;
+use serde::ser::{Error as _, Serialize, SerializeMap, SerializeSeq, Serializer};
+
+use crate::markers::{Markers, TableKind};
+
+/**
+    Shared state for encoding a single Lua value, and all of its children.
+*/
+pub(crate) struct EncodeContext {
+    lua: Lua,
+    markers: Markers,
+    binary: bool,
+    msgpack: bool,
+    visited: RefCell<HashSet<*const c_void>>,
+}
+
+impl EncodeContext {
+    /**
+        Creates a new context for encoding a Lua value.
+
+        If `binary` is `false`, buffers and strings that are not valid
+        UTF-8 can not be encoded, since the format only supports text.
+
+        If `msgpack` is `true`, decoded msgpack extension values are
+        encoded as extensions, otherwise they are encoded as plain objects.
+    */
+    pub(crate) fn new(lua: &Lua, binary: bool, msgpack: bool) -> LuaResult<Self> {
+        Ok(Self {
+            lua: lua.clone(),
+            markers: Markers::get(lua)?,
+            binary,
+            msgpack,
+            visited: RefCell::new(HashSet::new()),
+        })
+    }
+
+    pub(crate) fn encodable<'a>(&'a self, value: &'a LuaValue) -> Encodable<'a> {
+        Encodable {
+            value,
+            context: self,
+        }
+    }
+
+    fn is_visited(&self, value: &LuaValue) -> bool {
+        match value {
+            LuaValue::Table(t) => self.visited.borrow().contains(&t.to_pointer()),
+            _ => false,
+        }
+    }
+}
+
+/**
+    A Lua value that can be serialized into any format.
+
+    This mostly follows the same rules as mlua does, with some additions:
+
+    - Tables marked using `serde.array` are always arrays, even when empty,
+      and tables marked using `serde.object` are always objects
+    - `serde.null` is encoded as an explicit null value
+    - Tables with a sequence part are arrays, and any other keys are dropped
+    - Object keys are encoded in their original order if it was recorded
+      when decoding, otherwise they are sorted, so output is deterministic
+
+    Recursive tables are skipped, and any other unsupported values error.
+*/
+pub(crate) struct Encodable<'a> {
+    value: &'a LuaValue,
+    context: &'a EncodeContext,
+}
+
+impl Serialize for Encodable<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self.value {
+            LuaValue::Nil => serializer.serialize_unit(),
+            LuaValue::LightUserData(ud) if ud.0.is_null() => serializer.serialize_none(),
+            LuaValue::Boolean(b) => serializer.serialize_bool(*b),
+            LuaValue::Integer(i) => serializer.serialize_i64(*i),
+            LuaValue::Number(n) => serializer.serialize_f64(*n),
+            LuaValue::Vector(v) => v.serialize(serializer),
+            LuaValue::String(s) => match s.to_str() {
+                Ok(s) => serializer.serialize_str(&s),
+                Err(_) if self.context.binary => serializer.serialize_bytes(&s.as_bytes()),
+                Err(_) => Err(S::Error::custom(
+                    "strings that are not valid utf-8 can not be encoded in this format",
+                )),
+            },
+            LuaValue::Buffer(b) if self.context.binary => serializer.serialize_bytes(&b.to_vec()),
+            LuaValue::Buffer(_) => Err(S::Error::custom(
+                "buffers can not be encoded in this format",
+            )),
+            LuaValue::Table(t) => self.serialize_table(t, serializer),
+            value => Err(S::Error::custom(format!(
+                "values of type '{}' can not be encoded",
+                value.type_name()
+            ))),
+        }
+    }
+}
+
+impl Encodable<'_> {
+    fn serialize_table<S: Serializer>(
+        &self,
+        t: &LuaTable,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        if self.context.msgpack && self.context.markers.is_msgpack_ext(t) {
+            return serialize_msgpack_ext(t, serializer);
+        }
+
+        let ptr = t.to_pointer();
+        self.context.visited.borrow_mut().insert(ptr);
+        let result = match self.context.markers.kind(t) {
+            TableKind::Array => self.serialize_array(t, serializer),
+            TableKind::Unmarked if t.raw_len() > 0 => self.serialize_array(t, serializer),
+            TableKind::Object | TableKind::Unmarked => self.serialize_object(t, serializer),
+        };
+        self.context.visited.borrow_mut().remove(&ptr);
+        result
+    }
+
+    fn serialize_array<S: Serializer>(
+        &self,
+        t: &LuaTable,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        let len = t.raw_len();
+        let mut seq = serializer.serialize_seq(Some(len))?;
+        for index in 1..=len {
+            let value: LuaValue = t.raw_get(index).map_err(S::Error::custom)?;
+            if !self.context.is_visited(&value) {
+                seq.serialize_element(&self.context.encodable(&value))?;
+            }
+        }
+        seq.end()
+    }
+
+    fn serialize_object<S: Serializer>(
+        &self,
+        t: &LuaTable,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        let mut pairs = t
+            .pairs::<LuaValue, LuaValue>()
+            .filter(|pair| !pair.as_ref().is_ok_and(|(_, v)| self.context.is_visited(v)))
+            .collect::<LuaResult<Vec<_>>>()
+            .map_err(S::Error::custom)?;
+        pairs.sort_by(|(a, _), (b, _)| sort_cmp(a, b));
+
+        self.context
+            .markers
+            .restore_key_order(&self.context.lua, t, &mut pairs)
+            .map_err(S::Error::custom)?;
+
+        let mut map = serializer.serialize_map(Some(pairs.len()))?;
+        for (key, value) in &pairs {
+            map.serialize_entry(&self.context.encodable(key), &self.context.encodable(value))?;
+        }
+        map.end()
+    }
+}
+
+/*
+    Serializes a decoded MessagePack extension value using the special
+    newtype struct that rmpv recognizes as an extension, with its type
+    code and data, so that it is written back out exactly as it was read
+*/
+fn serialize_msgpack_ext<S: Serializer>(t: &LuaTable, serializer: S) -> Result<S::Ok, S::Error> {
+    struct Data(Vec<u8>);
+
+    impl Serialize for Data {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.serialize_bytes(&self.0)
+        }
+    }
+
+    let kind: i8 = t.raw_get("type").map_err(|_| {
+        S::Error::custom("msgpack extension type must be an integer between -128 and 127")
+    })?;
+    let data = match t.raw_get("data").map_err(S::Error::custom)? {
+        LuaValue::Buffer(b) => b.to_vec(),
+        LuaValue::String(s) => s.as_bytes().to_vec(),
+        value => {
+            return Err(S::Error::custom(format!(
+                "msgpack extension data must be a buffer or string, got {}",
+                value.type_name()
+            )));
+        }
+    };
+
+    serializer.serialize_newtype_struct(rmpv::MSGPACK_EXT_STRUCT_NAME, &(kind, Data(data)))
+}
+
+/*
+    Sorts keys the same way that mlua does - by type, then by value,
+    which for the common case of string keys means lexicographically
+*/
+fn sort_cmp(a: &LuaValue, b: &LuaValue) -> Ordering {
+    fn rank(value: &LuaValue) -> u8 {
+        match value {
+            LuaValue::Boolean(_) => 0,
+            LuaValue::Integer(_) | LuaValue::Number(_) => 1,
+            LuaValue::Vector(_) => 2,
+            LuaValue::String(_) => 3,
+            _ => 4,
+        }
+    }
+
+    #[allow(clippy::cast_precision_loss)]
+    match (a, b) {
+        (LuaValue::Boolean(a), LuaValue::Boolean(b)) => a.cmp(b),
+        (LuaValue::Integer(a), LuaValue::Integer(b)) => a.cmp(b),
+        (LuaValue::Integer(a), LuaValue::Number(b)) => (*a as f64).total_cmp(b),
+        (LuaValue::Number(a), LuaValue::Integer(b)) => a.total_cmp(&(*b as f64)),
+        (LuaValue::Number(a), LuaValue::Number(b)) => a.total_cmp(b),
+        (LuaValue::String(a), LuaValue::String(b)) => a.as_bytes().cmp(&b.as_bytes()),
+        (a, b) => rank(a)
+            .cmp(&rank(b))
+            .then_with(|| a.to_pointer().cmp(&b.to_pointer())),
+    }
+}
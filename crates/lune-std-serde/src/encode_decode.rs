@@ -1,20 +1,27 @@
 use mlua::prelude::*;
 
+use ciborium::Value as CborValue;
+use rmpv::Value as MsgPackValue;
 use serde_json::Value as JsonValue;
 use serde_yaml::Value as YamlValue;
 use toml::Value as TomlValue;
 
+use crate::binary_text::BinaryTextEncoding;
+use crate::csv_codec::{self, CsvOptions};
+use crate::encodable::EncodeContext;
+use crate::markers::{Markers, TableKind};
+
 // NOTE: These are options for going from other format -> lua ("serializing" lua values)
-const LUA_SERIALIZE_OPTIONS: LuaSerializeOptions = LuaSerializeOptions::new()
+pub(crate) const LUA_SERIALIZE_OPTIONS: LuaSerializeOptions = LuaSerializeOptions::new()
     .set_array_metatable(false)
     .serialize_none_to_null(false)
     .serialize_unit_to_null(false);
 
-// NOTE: These are options for going from lua -> other format ("deserializing" lua values)
-const LUA_DESERIALIZE_OPTIONS: LuaDeserializeOptions = LuaDeserializeOptions::new()
-    .sort_keys(true)
-    .deny_recursive_tables(false)
-    .deny_unsupported_types(true);
+/*
+    NOTE: Going from lua -> other format ("deserializing" lua values) does not
+    use mlua directly, since it does not know about our markers for objects and
+    key order - see `Encodable` for the rules that are used for this instead
+*/
 
 /**
     An encoding and decoding format supported by Lune.
@@ -26,6 +33,10 @@ pub enum EncodeDecodeFormat {
     Json,
     Yaml,
     Toml,
+    MsgPack,
+    Cbor,
+    Csv,
+    BinaryText(BinaryTextEncoding),
 }
 
 impl FromLua for EncodeDecodeFormat {
@@ -35,13 +46,22 @@ impl FromLua for EncodeDecodeFormat {
                 "json" => Ok(Self::Json),
                 "yaml" => Ok(Self::Yaml),
                 "toml" => Ok(Self::Toml),
-                kind => Err(LuaError::FromLuaConversionError {
-                    from: value.type_name(),
-                    to: "EncodeDecodeFormat".to_string(),
-                    message: Some(format!(
-                        "Invalid format '{kind}', valid formats are:  json, yaml, toml"
-                    )),
-                }),
+                "msgpack" => Ok(Self::MsgPack),
+                "cbor" => Ok(Self::Cbor),
+                "csv" => Ok(Self::Csv),
+                kind => match BinaryTextEncoding::from_name(kind) {
+                    Some(encoding) => Ok(Self::BinaryText(encoding)),
+                    None => Err(LuaError::FromLuaConversionError {
+                        from: value.type_name(),
+                        to: "EncodeDecodeFormat".to_string(),
+                        message: Some(format!(
+                            "Invalid format '{kind}', valid formats are:  json, yaml, toml, msgpack, cbor, csv, {}",
+                            BinaryTextEncoding::ALL
+                                .map(BinaryTextEncoding::name)
+                                .join(", ")
+                        )),
+                    }),
+                },
             }
         } else {
             Err(LuaError::FromLuaConversionError {
@@ -90,9 +110,16 @@ impl From<(EncodeDecodeFormat, bool)> for EncodeDecodeConfig {
     Errors when the encoding fails.
 */
 pub fn encode(value: LuaValue, lua: &Lua, config: EncodeDecodeConfig) -> LuaResult<LuaString> {
+    let binary = matches!(
+        config.format,
+        EncodeDecodeFormat::MsgPack | EncodeDecodeFormat::Cbor
+    );
+    let msgpack = matches!(config.format, EncodeDecodeFormat::MsgPack);
+    let context = EncodeContext::new(lua, binary, msgpack)?;
+    let encodable = context.encodable(&value);
     let bytes = match config.format {
         EncodeDecodeFormat::Json => {
-            let serialized: JsonValue = lua.from_value_with(value, LUA_DESERIALIZE_OPTIONS)?;
+            let serialized: JsonValue = serde_json::to_value(&encodable).into_lua_err()?;
             if config.pretty {
                 serde_json::to_vec_pretty(&serialized).into_lua_err()?
             } else {
@@ -100,13 +127,13 @@ pub fn encode(value: LuaValue, lua: &Lua, config: EncodeDecodeConfig) -> LuaResu
             }
         }
         EncodeDecodeFormat::Yaml => {
-            let serialized: YamlValue = lua.from_value_with(value, LUA_DESERIALIZE_OPTIONS)?;
+            let serialized: YamlValue = serde_yaml::to_value(&encodable).into_lua_err()?;
             let mut writer = Vec::with_capacity(128);
             serde_yaml::to_writer(&mut writer, &serialized).into_lua_err()?;
             writer
         }
         EncodeDecodeFormat::Toml => {
-            let serialized: TomlValue = lua.from_value_with(value, LUA_DESERIALIZE_OPTIONS)?;
+            let serialized = TomlValue::try_from(&encodable).into_lua_err()?;
             let s = if config.pretty {
                 toml::to_string_pretty(&serialized).into_lua_err()?
             } else {
@@ -114,10 +141,73 @@ pub fn encode(value: LuaValue, lua: &Lua, config: EncodeDecodeConfig) -> LuaResu
             };
             s.as_bytes().to_vec()
         }
+        EncodeDecodeFormat::MsgPack => {
+            let serialized: MsgPackValue = rmpv::ext::to_value(&encodable).into_lua_err()?;
+            let mut writer = Vec::with_capacity(128);
+            rmpv::encode::write_value(&mut writer, &serialized).into_lua_err()?;
+            writer
+        }
+        EncodeDecodeFormat::Cbor => {
+            let serialized = CborValue::serialized(&encodable).into_lua_err()?;
+            let mut writer = Vec::with_capacity(128);
+            ciborium::into_writer(&serialized, &mut writer).into_lua_err()?;
+            writer
+        }
+        EncodeDecodeFormat::Csv => {
+            return csv_codec::encode(lua, value, &CsvOptions::default());
+        }
+        EncodeDecodeFormat::BinaryText(encoding) => match value {
+            LuaValue::String(s) => encoding.encode(s.as_bytes()).into_bytes(),
+            LuaValue::Buffer(b) => encoding.encode(b.to_vec()).into_bytes(),
+            value => {
+                return Err(LuaError::RuntimeError(format!(
+                    "Expected a string or buffer to encode as {encoding}, got {}",
+                    value.type_name()
+                )));
+            }
+        },
     };
     lua.create_string(bytes)
 }
 
+/**
+    Options for decoding values.
+
+    By default, null values are decoded as `nil`, and decoded tables are
+    not marked, which means that some information is lost when decoding.
+    These options can be used to keep that information, so that decoding
+    and then encoding a value again produces the same output.
+*/
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DecodeOptions {
+    pub preserve_nulls: bool,
+    pub mark_tables: bool,
+    pub preserve_order: bool,
+}
+
+impl FromLua for DecodeOptions {
+    fn from_lua(value: LuaValue, _: &Lua) -> LuaResult<Self> {
+        Ok(match value {
+            LuaValue::Nil => Self::default(),
+            LuaValue::Table(t) => Self {
+                preserve_nulls: t.get::<Option<bool>>("preserveNulls")?.unwrap_or_default(),
+                mark_tables: t.get::<Option<bool>>("markTables")?.unwrap_or_default(),
+                preserve_order: t.get::<Option<bool>>("preserveOrder")?.unwrap_or_default(),
+            },
+            _ => {
+                return Err(LuaError::FromLuaConversionError {
+                    from: value.type_name(),
+                    to: "DecodeOptions".to_string(),
+                    message: Some(format!(
+                        "Invalid decode options - expected table or nil, got {}",
+                        value.type_name()
+                    )),
+                });
+            }
+        })
+    }
+}
+
 /**
     Decodes / deserializes the given string into a value, using the specified configuration.
 
@@ -129,26 +219,229 @@ pub fn decode(
     bytes: impl AsRef<[u8]>,
     lua: &Lua,
     config: EncodeDecodeConfig,
+) -> LuaResult<LuaValue> {
+    decode_with_options(bytes, lua, config, &DecodeOptions::default())
+}
+
+/**
+    Decodes / deserializes the given string into a value, using the specified configuration and options.
+
+    # Errors
+
+    Errors when the decoding fails.
+*/
+pub fn decode_with_options(
+    bytes: impl AsRef<[u8]>,
+    lua: &Lua,
+    config: EncodeDecodeConfig,
+    options: &DecodeOptions,
 ) -> LuaResult<LuaValue> {
     let bytes = bytes.as_ref();
+    let decoder = Decoder::new(lua, options)?;
     match config.format {
         EncodeDecodeFormat::Json => {
             let value: JsonValue = serde_json::from_slice(bytes).into_lua_err()?;
-            lua.to_value_with(&value, LUA_SERIALIZE_OPTIONS)
+            decoder.json_to_lua(value)
         }
         EncodeDecodeFormat::Yaml => {
             let value: YamlValue = serde_yaml::from_slice(bytes).into_lua_err()?;
-            lua.to_value_with(&value, LUA_SERIALIZE_OPTIONS)
+            decoder.yaml_to_lua(value)
         }
         EncodeDecodeFormat::Toml => {
             if let Ok(s) = String::from_utf8(bytes.to_vec()) {
                 let value: TomlValue = toml::from_str(&s).into_lua_err()?;
-                lua.to_value_with(&value, LUA_SERIALIZE_OPTIONS)
+                decoder.toml_to_lua(value)
             } else {
                 Err(LuaError::RuntimeError(
                     "TOML must be valid utf-8".to_string(),
                 ))
             }
         }
+        EncodeDecodeFormat::MsgPack => {
+            let mut reader = bytes;
+            let value = rmpv::decode::read_value(&mut reader).into_lua_err()?;
+            ensure_fully_read("MessagePack", reader)?;
+            decoder.msgpack_to_lua(value)
+        }
+        EncodeDecodeFormat::Cbor => {
+            let mut reader = bytes;
+            let value: CborValue = ciborium::from_reader(&mut reader).into_lua_err()?;
+            ensure_fully_read("CBOR", reader)?;
+            decoder.cbor_to_lua(value)
+        }
+        EncodeDecodeFormat::Csv => {
+            csv_codec::decode(lua, bytes, &CsvOptions::default()).map(LuaValue::Table)
+        }
+        EncodeDecodeFormat::BinaryText(encoding) => {
+            let bytes = encoding.decode(bytes)?;
+            lua.create_string(bytes).map(LuaValue::String)
+        }
+    }
+}
+
+fn ensure_fully_read(format: &str, remaining: &[u8]) -> LuaResult<()> {
+    if remaining.is_empty() {
+        Ok(())
+    } else {
+        Err(LuaError::RuntimeError(format!(
+            "{format} contained {} trailing bytes after the encoded value",
+            remaining.len()
+        )))
+    }
+}
+
+/*
+    NOTE: We walk through arrays and maps ourselves, and only let mlua convert
+    any other values, so that we can keep nulls, mark tables, and record the
+    order of keys, depending on the options given. Binary formats also have a
+    dedicated type for binary data, which we want to decode into buffers rather
+    than strings, so that binary data round-trips faithfully - mlua always
+    serializes bytes into strings.
+*/
+
+pub(crate) struct Decoder<'a> {
+    lua: &'a Lua,
+    options: &'a DecodeOptions,
+    markers: Option<Markers>,
+}
+
+impl<'a> Decoder<'a> {
+    pub(crate) fn new(lua: &'a Lua, options: &'a DecodeOptions) -> LuaResult<Self> {
+        let markers = if options.mark_tables || options.preserve_order {
+            Some(Markers::get(lua)?)
+        } else {
+            None
+        };
+        Ok(Self {
+            lua,
+            options,
+            markers,
+        })
+    }
+
+    fn null(&self) -> LuaValue {
+        if self.options.preserve_nulls {
+            LuaValue::NULL
+        } else {
+            LuaValue::Nil
+        }
+    }
+
+    fn array<T>(
+        &self,
+        values: Vec<T>,
+        convert: impl Fn(&Self, T) -> LuaResult<LuaValue>,
+    ) -> LuaResult<LuaValue> {
+        let tab = self.lua.create_table_with_capacity(values.len(), 0)?;
+        for (index, value) in values.into_iter().enumerate() {
+            tab.raw_set(index + 1, convert(self, value)?)?;
+        }
+        if let Some(markers) = self.markers.as_ref().filter(|_| self.options.mark_tables) {
+            markers.mark(&tab, TableKind::Array)?;
+        }
+        Ok(LuaValue::Table(tab))
+    }
+
+    fn object<K, V>(
+        &self,
+        pairs: impl ExactSizeIterator<Item = (K, V)>,
+        convert_key: impl Fn(&Self, K) -> LuaResult<LuaValue>,
+        convert_value: impl Fn(&Self, V) -> LuaResult<LuaValue>,
+    ) -> LuaResult<LuaValue> {
+        let tab = self.lua.create_table_with_capacity(0, pairs.len())?;
+        let order = if self.options.preserve_order {
+            Some(self.lua.create_table_with_capacity(pairs.len(), 0)?)
+        } else {
+            None
+        };
+        for (key, value) in pairs {
+            let key = convert_key(self, key)?;
+            if key.is_nil() || key.is_null() {
+                continue;
+            }
+            if let Some(order) = &order {
+                order.raw_push(key.clone())?;
+            }
+            tab.raw_set(key, convert_value(self, value)?)?;
+        }
+        if let Some(markers) = &self.markers {
+            if self.options.mark_tables {
+                markers.mark(&tab, TableKind::Object)?;
+            }
+            if let Some(order) = order {
+                markers.set_key_order(&tab, order)?;
+            }
+        }
+        Ok(LuaValue::Table(tab))
+    }
+
+    pub(crate) fn json_to_lua(&self, value: JsonValue) -> LuaResult<LuaValue> {
+        match value {
+            JsonValue::Null => Ok(self.null()),
+            JsonValue::Array(values) => self.array(values, Self::json_to_lua),
+            JsonValue::Object(pairs) => self.object(
+                pairs.into_iter(),
+                |this, key| this.lua.create_string(key).map(LuaValue::String),
+                Self::json_to_lua,
+            ),
+            value => self.lua.to_value_with(&value, LUA_SERIALIZE_OPTIONS),
+        }
+    }
+
+    fn yaml_to_lua(&self, value: YamlValue) -> LuaResult<LuaValue> {
+        match value {
+            YamlValue::Null => Ok(self.null()),
+            YamlValue::Sequence(values) => self.array(values, Self::yaml_to_lua),
+            YamlValue::Mapping(pairs) => {
+                self.object(pairs.into_iter(), Self::yaml_to_lua, Self::yaml_to_lua)
+            }
+            value => self.lua.to_value_with(&value, LUA_SERIALIZE_OPTIONS),
+        }
+    }
+
+    fn toml_to_lua(&self, value: TomlValue) -> LuaResult<LuaValue> {
+        match value {
+            TomlValue::Array(values) => self.array(values, Self::toml_to_lua),
+            TomlValue::Table(pairs) => self.object(
+                pairs.into_iter(),
+                |this, key| this.lua.create_string(key).map(LuaValue::String),
+                Self::toml_to_lua,
+            ),
+            value => self.lua.to_value_with(&value, LUA_SERIALIZE_OPTIONS),
+        }
+    }
+
+    fn msgpack_to_lua(&self, value: MsgPackValue) -> LuaResult<LuaValue> {
+        match value {
+            MsgPackValue::Nil => Ok(self.null()),
+            MsgPackValue::Binary(bytes) => self.lua.create_buffer(bytes).map(LuaValue::Buffer),
+            MsgPackValue::Ext(kind, bytes) => {
+                let tab = self.lua.create_table_with_capacity(0, 2)?;
+                tab.raw_set("type", kind)?;
+                tab.raw_set("data", self.lua.create_buffer(bytes)?)?;
+                Markers::get(self.lua)?.mark_msgpack_ext(&tab)?;
+                Ok(LuaValue::Table(tab))
+            }
+            MsgPackValue::Array(values) => self.array(values, Self::msgpack_to_lua),
+            MsgPackValue::Map(pairs) => self.object(
+                pairs.into_iter(),
+                Self::msgpack_to_lua,
+                Self::msgpack_to_lua,
+            ),
+            value => self.lua.to_value_with(&value, LUA_SERIALIZE_OPTIONS),
+        }
+    }
+
+    fn cbor_to_lua(&self, value: CborValue) -> LuaResult<LuaValue> {
+        match value {
+            CborValue::Null => Ok(self.null()),
+            CborValue::Bytes(bytes) => self.lua.create_buffer(bytes).map(LuaValue::Buffer),
+            CborValue::Tag(_, value) => self.cbor_to_lua(*value),
+            CborValue::Array(values) => self.array(values, Self::cbor_to_lua),
+            CborValue::Map(pairs) => {
+                self.object(pairs.into_iter(), Self::cbor_to_lua, Self::cbor_to_lua)
+            }
+            value => self.lua.to_value_with(&value, LUA_SERIALIZE_OPTIONS),
+        }
     }
 }
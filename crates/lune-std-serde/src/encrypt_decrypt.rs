@@ -0,0 +1,218 @@
+use aes_gcm::Aes256Gcm;
+use aes_gcm::aead::{Aead, KeyInit, Payload};
+use bstr::BString;
+use chacha20poly1305::ChaCha20Poly1305;
+use mlua::prelude::*;
+use rand_core::{OsRng, RngCore};
+
+const KEY_SIZE: usize = 32;
+const NONCE_SIZE: usize = 12;
+const TAG_SIZE: usize = 16;
+
+/**
+    An authenticated encryption algorithm supported by Lune.
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EncryptDecryptAlgorithm {
+    Aes256Gcm,
+    ChaCha20Poly1305,
+}
+
+impl EncryptDecryptAlgorithm {
+    #[must_use]
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Aes256Gcm => "aes-256-gcm",
+            Self::ChaCha20Poly1305 => "chacha20-poly1305",
+        }
+    }
+
+    fn ensure_valid_key(self, key: &[u8]) -> LuaResult<()> {
+        if key.len() != KEY_SIZE {
+            return Err(LuaError::RuntimeError(format!(
+                "Invalid key for '{}' - expected {KEY_SIZE} bytes, got {} bytes",
+                self.name(),
+                key.len()
+            )));
+        }
+        Ok(())
+    }
+
+    fn encrypt(self, key: &[u8], nonce: &[u8], payload: Payload) -> Option<Vec<u8>> {
+        match self {
+            Self::Aes256Gcm => encrypt_with::<Aes256Gcm>(key, nonce, payload),
+            Self::ChaCha20Poly1305 => encrypt_with::<ChaCha20Poly1305>(key, nonce, payload),
+        }
+    }
+
+    fn decrypt(self, key: &[u8], nonce: &[u8], payload: Payload) -> Option<Vec<u8>> {
+        match self {
+            Self::Aes256Gcm => decrypt_with::<Aes256Gcm>(key, nonce, payload),
+            Self::ChaCha20Poly1305 => decrypt_with::<ChaCha20Poly1305>(key, nonce, payload),
+        }
+    }
+}
+
+impl FromLua for EncryptDecryptAlgorithm {
+    fn from_lua(value: LuaValue, _: &Lua) -> LuaResult<Self> {
+        if let LuaValue::String(s) = &value {
+            match s.to_string_lossy().to_ascii_lowercase().trim() {
+                "aes-256-gcm" | "aes256gcm" => Ok(Self::Aes256Gcm),
+                "chacha20-poly1305" | "chacha20poly1305" => Ok(Self::ChaCha20Poly1305),
+                kind => Err(LuaError::FromLuaConversionError {
+                    from: value.type_name(),
+                    to: "EncryptDecryptAlgorithm".to_string(),
+                    message: Some(format!(
+                        "Invalid algorithm '{kind}', valid algorithms are:  aes-256-gcm, chacha20-poly1305"
+                    )),
+                }),
+            }
+        } else {
+            Err(LuaError::FromLuaConversionError {
+                from: value.type_name(),
+                to: "EncryptDecryptAlgorithm".to_string(),
+                message: None,
+            })
+        }
+    }
+}
+
+fn encrypt_with<C: Aead + KeyInit>(key: &[u8], nonce: &[u8], payload: Payload) -> Option<Vec<u8>> {
+    let cipher = C::new_from_slice(key).ok()?;
+    cipher.encrypt(nonce.into(), payload).ok()
+}
+
+fn decrypt_with<C: Aead + KeyInit>(key: &[u8], nonce: &[u8], payload: Payload) -> Option<Vec<u8>> {
+    let cipher = C::new_from_slice(key).ok()?;
+    cipher.decrypt(nonce.into(), payload).ok()
+}
+
+/**
+    Options for encrypting and decrypting data.
+
+    A nonce may only be given when encrypting, and a random one is
+    generated otherwise. Associated data must match when decrypting.
+*/
+#[derive(Debug, Clone, Default)]
+pub struct EncryptDecryptOptions {
+    pub nonce: Option<Vec<u8>>,
+    pub aad: Option<Vec<u8>>,
+}
+
+impl FromLua for EncryptDecryptOptions {
+    fn from_lua(value: LuaValue, _: &Lua) -> LuaResult<Self> {
+        Ok(match value {
+            LuaValue::Nil => Self::default(),
+            LuaValue::Table(t) => {
+                let nonce: Option<BString> = t.get("nonce")?;
+                let aad: Option<BString> = t.get("aad")?;
+                Self {
+                    nonce: nonce.map(Vec::from),
+                    aad: aad.map(Vec::from),
+                }
+            }
+            _ => {
+                return Err(LuaError::FromLuaConversionError {
+                    from: value.type_name(),
+                    to: "EncryptDecryptOptions".to_string(),
+                    message: Some(format!(
+                        "Invalid encryption options - expected table or nil, got {}",
+                        value.type_name()
+                    )),
+                });
+            }
+        })
+    }
+}
+
+/**
+    Encrypts the given plaintext using the specified algorithm and key.
+
+    The returned bytes are the nonce, followed by the ciphertext and the
+    authentication tag, which is exactly what `decrypt` expects as input.
+
+    # Errors
+
+    Errors if the key or nonce have the wrong length, or if encryption fails.
+*/
+pub fn encrypt(
+    algorithm: EncryptDecryptAlgorithm,
+    key: &[u8],
+    plaintext: &[u8],
+    options: &EncryptDecryptOptions,
+) -> LuaResult<Vec<u8>> {
+    algorithm.ensure_valid_key(key)?;
+
+    let nonce = match &options.nonce {
+        Some(nonce) if nonce.len() == NONCE_SIZE => nonce.clone(),
+        Some(nonce) => {
+            return Err(LuaError::RuntimeError(format!(
+                "Invalid nonce for '{}' - expected {NONCE_SIZE} bytes, got {} bytes",
+                algorithm.name(),
+                nonce.len()
+            )));
+        }
+        None => {
+            let mut nonce = vec![0; NONCE_SIZE];
+            OsRng.fill_bytes(&mut nonce);
+            nonce
+        }
+    };
+
+    let payload = Payload {
+        msg: plaintext,
+        aad: options.aad.as_deref().unwrap_or_default(),
+    };
+    let ciphertext = algorithm
+        .encrypt(key, &nonce, payload)
+        .ok_or_else(|| LuaError::runtime("Failed to encrypt"))?;
+
+    let mut output = nonce;
+    output.extend_from_slice(&ciphertext);
+    Ok(output)
+}
+
+/**
+    Decrypts the given data using the specified algorithm and key.
+
+    The data must be the nonce, followed by the ciphertext
+    and the authentication tag, as returned by `encrypt`.
+
+    # Errors
+
+    Errors if the key has the wrong length, if the data is too short, or if the
+    data could not be authenticated, meaning it was tampered with or is otherwise wrong.
+*/
+pub fn decrypt(
+    algorithm: EncryptDecryptAlgorithm,
+    key: &[u8],
+    data: &[u8],
+    options: &EncryptDecryptOptions,
+) -> LuaResult<Vec<u8>> {
+    algorithm.ensure_valid_key(key)?;
+
+    if options.nonce.is_some() {
+        return Err(LuaError::runtime(
+            "A nonce can not be given when decrypting - it is read from the encrypted data",
+        ));
+    }
+    if data.len() < NONCE_SIZE + TAG_SIZE {
+        return Err(LuaError::RuntimeError(format!(
+            "Invalid data for '{}' - expected at least {} bytes, got {} bytes",
+            algorithm.name(),
+            NONCE_SIZE + TAG_SIZE,
+            data.len()
+        )));
+    }
+
+    let (nonce, ciphertext) = data.split_at(NONCE_SIZE);
+    let payload = Payload {
+        msg: ciphertext,
+        aad: options.aad.as_deref().unwrap_or_default(),
+    };
+    algorithm.decrypt(key, nonce, payload).ok_or_else(|| {
+        LuaError::runtime(
+            "Failed to decrypt - the key or associated data is wrong, or the data has been tampered with",
+        )
+    })
+}
@@ -1,13 +1,19 @@
 use std::fmt::Write;
+use std::hash::Hasher as _;
 
 use bstr::BString;
 use md5::Md5;
 use mlua::prelude::*;
 
+use adler2::Adler32;
 use blake3::Hasher as Blake3;
+use crc32fast::Hasher as Crc32;
 use sha1::Sha1;
 use sha2::{Sha224, Sha256, Sha384, Sha512};
 use sha3::{Sha3_224, Sha3_256, Sha3_384, Sha3_512};
+use siphasher::sip::SipHasher24;
+use xxhash_rust::xxh3::Xxh3Default;
+use xxhash_rust::xxh64::Xxh64;
 
 pub struct HashOptions {
     algorithm: HashAlgorithm,
@@ -16,8 +22,8 @@ pub struct HashOptions {
     // seed: Option<BString>,
 }
 
-#[derive(Debug, Clone, Copy)]
-enum HashAlgorithm {
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HashAlgorithm {
     Md5,
     Sha1,
     // SHA-2 variants
@@ -32,10 +38,16 @@ enum HashAlgorithm {
     Sha3_512,
     // Blake3
     Blake3,
+    // Non-cryptographic checksums & hashes
+    Crc32,
+    Adler32,
+    Xxh64,
+    Xxh3,
+    SipHash,
 }
 
 impl HashAlgorithm {
-    pub const ALL: [Self; 11] = [
+    pub const ALL: [Self; 16] = [
         Self::Md5,
         Self::Sha1,
         Self::Sha2_224,
@@ -47,8 +59,14 @@ impl HashAlgorithm {
         Self::Sha3_384,
         Self::Sha3_512,
         Self::Blake3,
+        Self::Crc32,
+        Self::Adler32,
+        Self::Xxh64,
+        Self::Xxh3,
+        Self::SipHash,
     ];
 
+    #[must_use]
     pub const fn name(self) -> &'static str {
         match self {
             Self::Md5 => "md5",
@@ -62,72 +80,97 @@ impl HashAlgorithm {
             Self::Sha3_384 => "sha3-384",
             Self::Sha3_512 => "sha3-512",
             Self::Blake3 => "blake3",
+            Self::Crc32 => "crc32",
+            Self::Adler32 => "adler32",
+            Self::Xxh64 => "xxh64",
+            Self::Xxh3 => "xxh3",
+            Self::SipHash => "siphash",
         }
     }
-}
 
-impl HashOptions {
     /**
-        Computes the hash for the `message` using whatever `algorithm` is
-        contained within this struct and returns it as a string of hex digits.
+        Returns `true` if the algorithm is a cryptographic hash function,
+        meaning that it can also be used to compute an HMAC.
     */
-    #[inline]
-    #[must_use = "hashing a message is useless without using the resulting hash"]
-    pub fn hash(self) -> String {
-        use digest::Digest;
-
-        let message = self.message;
-        let bytes = match self.algorithm {
-            HashAlgorithm::Md5 => Md5::digest(message).to_vec(),
-            HashAlgorithm::Sha1 => Sha1::digest(message).to_vec(),
-            HashAlgorithm::Sha2_224 => Sha224::digest(message).to_vec(),
-            HashAlgorithm::Sha2_256 => Sha256::digest(message).to_vec(),
-            HashAlgorithm::Sha2_384 => Sha384::digest(message).to_vec(),
-            HashAlgorithm::Sha2_512 => Sha512::digest(message).to_vec(),
-
-            HashAlgorithm::Sha3_224 => Sha3_224::digest(message).to_vec(),
-            HashAlgorithm::Sha3_256 => Sha3_256::digest(message).to_vec(),
-            HashAlgorithm::Sha3_384 => Sha3_384::digest(message).to_vec(),
-            HashAlgorithm::Sha3_512 => Sha3_512::digest(message).to_vec(),
-
-            HashAlgorithm::Blake3 => Blake3::digest(message).to_vec(),
-        };
+    #[must_use]
+    pub const fn is_cryptographic(self) -> bool {
+        !matches!(
+            self,
+            Self::Crc32 | Self::Adler32 | Self::Xxh64 | Self::Xxh3 | Self::SipHash
+        )
+    }
 
-        // We don't want to return raw binary data generally, since that's not
-        // what most people want a hash for. So we have to make a hex string.
-        bytes
-            .iter()
-            .fold(String::with_capacity(bytes.len() * 2), |mut output, b| {
-                let _ = write!(output, "{b:02x}");
-                output
-            })
+    /**
+        Creates a new incremental hasher for this algorithm.
+
+        `SipHash` is a keyed hash function, and uses a key of all zeroes
+        here - use [`HashAlgorithm::keyed_hasher`] to give it a key.
+    */
+    #[must_use]
+    pub fn hasher(self) -> Box<dyn IncrementalHash> {
+        match self {
+            Self::Md5 => Box::new(DigestHash(Md5::default())),
+            Self::Sha1 => Box::new(DigestHash(Sha1::default())),
+
+            Self::Sha2_224 => Box::new(DigestHash(Sha224::default())),
+            Self::Sha2_256 => Box::new(DigestHash(Sha256::default())),
+            Self::Sha2_384 => Box::new(DigestHash(Sha384::default())),
+            Self::Sha2_512 => Box::new(DigestHash(Sha512::default())),
+
+            Self::Sha3_224 => Box::new(DigestHash(Sha3_224::default())),
+            Self::Sha3_256 => Box::new(DigestHash(Sha3_256::default())),
+            Self::Sha3_384 => Box::new(DigestHash(Sha3_384::default())),
+            Self::Sha3_512 => Box::new(DigestHash(Sha3_512::default())),
+
+            Self::Blake3 => Box::new(DigestHash(Blake3::default())),
+
+            Self::Crc32 => Box::new(Crc32::new()),
+            Self::Adler32 => Box::new(Adler32::new()),
+            Self::Xxh64 => Box::new(Xxh64::new(0)),
+            Self::Xxh3 => Box::new(Xxh3Default::new()),
+            Self::SipHash => Box::new(SipHasher24::new_with_key(&[0; 16])),
+        }
     }
 
     /**
-        Computes the HMAC for the `message` using whatever `algorithm` and
-        `secret` are contained within this struct. The computed value is
-        returned as a string of hex digits.
+        Creates a new incremental hasher for this algorithm, using the given key.
+
+        Only `SipHash` is a keyed hash function, and its key must be exactly 16 bytes long.
 
         # Errors
 
-        If the `secret` is not provided or is otherwise invalid.
+        Errors if the algorithm is not a keyed hash function, or if the key is not 16 bytes long.
     */
-    #[inline]
-    pub fn hmac(self) -> LuaResult<String> {
-        use hmac::{Hmac, Mac, SimpleHmac};
+    pub fn keyed_hasher(self, key: &[u8]) -> LuaResult<Box<dyn IncrementalHash>> {
+        if self != Self::SipHash {
+            return Err(LuaError::RuntimeError(format!(
+                "Hashing algorithm '{}' is not a keyed hash function",
+                self.name()
+            )));
+        }
+        let key: [u8; 16] = key.try_into().map_err(|_| {
+            LuaError::RuntimeError(format!(
+                "SipHash key must be exactly 16 bytes long, got {} bytes",
+                key.len()
+            ))
+        })?;
+        Ok(Box::new(SipHasher24::new_with_key(&key)))
+    }
 
-        let secret = self
-            .secret
-            .ok_or_else(|| LuaError::FromLuaConversionError {
-                from: "nil",
-                to: "string or buffer".to_string(),
-                message: Some("Argument #3 missing or nil".to_string()),
-            })?;
+    /**
+        Creates a new incremental HMAC hasher for this algorithm, using the given secret.
+
+        # Errors
+
+        Errors if the algorithm is not a cryptographic hash function, or if the secret is invalid.
+    */
+    pub fn hmac_hasher(self, secret: &[u8]) -> LuaResult<Box<dyn IncrementalHash>> {
+        use hmac::{Hmac, Mac, SimpleHmac};
 
         /*
             These macros exist to remove what would ultimately be dozens of
             repeating lines. Essentially, there's several step to processing
-            HMacs, which expands into the 3 lines you see below. However,
+            HMacs, which expands into the lines you see below. However,
             the Hmac struct is specialized towards eager block-based processes.
             In order to support anything else, like blake3, there's a second
             type named `SimpleHmac`. This results in duplicate macros like
@@ -135,42 +178,226 @@ impl HashOptions {
         */
         macro_rules! hmac {
             ($Type:ty) => {{
-                let mut mac: Hmac<$Type> = Hmac::new_from_slice(&secret).into_lua_err()?;
-                mac.update(&self.message);
-                mac.finalize().into_bytes().to_vec()
+                let mac: Hmac<$Type> = Hmac::new_from_slice(secret).into_lua_err()?;
+                Box::new(MacHash(mac))
             }};
         }
         macro_rules! hmac_no_blocks {
             ($Type:ty) => {{
-                let mut mac: SimpleHmac<$Type> =
-                    SimpleHmac::new_from_slice(&secret).into_lua_err()?;
-                mac.update(&self.message);
-                mac.finalize().into_bytes().to_vec()
+                let mac: SimpleHmac<$Type> = SimpleHmac::new_from_slice(secret).into_lua_err()?;
+                Box::new(MacHash(mac))
             }};
         }
 
-        let bytes = match self.algorithm {
-            HashAlgorithm::Md5 => hmac!(Md5),
-            HashAlgorithm::Sha1 => hmac!(Sha1),
+        Ok(match self {
+            Self::Md5 => hmac!(Md5),
+            Self::Sha1 => hmac!(Sha1),
 
-            HashAlgorithm::Sha2_224 => hmac!(Sha224),
-            HashAlgorithm::Sha2_256 => hmac!(Sha256),
-            HashAlgorithm::Sha2_384 => hmac!(Sha384),
-            HashAlgorithm::Sha2_512 => hmac!(Sha512),
+            Self::Sha2_224 => hmac!(Sha224),
+            Self::Sha2_256 => hmac!(Sha256),
+            Self::Sha2_384 => hmac!(Sha384),
+            Self::Sha2_512 => hmac!(Sha512),
 
-            HashAlgorithm::Sha3_224 => hmac!(Sha3_224),
-            HashAlgorithm::Sha3_256 => hmac!(Sha3_256),
-            HashAlgorithm::Sha3_384 => hmac!(Sha3_384),
-            HashAlgorithm::Sha3_512 => hmac!(Sha3_512),
+            Self::Sha3_224 => hmac!(Sha3_224),
+            Self::Sha3_256 => hmac!(Sha3_256),
+            Self::Sha3_384 => hmac!(Sha3_384),
+            Self::Sha3_512 => hmac!(Sha3_512),
 
-            HashAlgorithm::Blake3 => hmac_no_blocks!(Blake3),
-        };
-        Ok(bytes
-            .iter()
-            .fold(String::with_capacity(bytes.len() * 2), |mut output, b| {
-                let _ = write!(output, "{b:02x}");
-                output
-            }))
+            Self::Blake3 => hmac_no_blocks!(Blake3),
+
+            Self::Crc32 | Self::Adler32 | Self::Xxh64 | Self::Xxh3 | Self::SipHash => {
+                return Err(self.non_cryptographic_error("HMAC"));
+            }
+        })
+    }
+
+    /**
+        Ensures that this is a cryptographic hash function, which is required
+        for HMAC and anything built on top of it, such as key derivation.
+
+        # Errors
+
+        Errors if the algorithm is not a cryptographic hash function.
+    */
+    pub fn ensure_cryptographic(self, usage: &str) -> LuaResult<()> {
+        if self.is_cryptographic() {
+            Ok(())
+        } else {
+            Err(self.non_cryptographic_error(usage))
+        }
+    }
+
+    fn non_cryptographic_error(self, usage: &str) -> LuaError {
+        LuaError::RuntimeError(format!(
+            "{usage} is not supported for the non-cryptographic algorithm '{}'",
+            self.name()
+        ))
+    }
+}
+
+/**
+    A hash function that can be fed its message incrementally, and
+    that can produce a digest at any point without being consumed.
+
+    Non-cryptographic checksums and hashes produce their digests
+    as big-endian bytes, matching the output of common tools.
+*/
+pub trait IncrementalHash {
+    fn update(&mut self, data: &[u8]);
+    fn digest(&self) -> Vec<u8>;
+}
+
+struct DigestHash<D>(D);
+
+impl<D: digest::Digest + Clone> IncrementalHash for DigestHash<D> {
+    fn update(&mut self, data: &[u8]) {
+        self.0.update(data);
+    }
+
+    fn digest(&self) -> Vec<u8> {
+        self.0.clone().finalize().to_vec()
+    }
+}
+
+struct MacHash<M>(M);
+
+impl<M: hmac::Mac + Clone> IncrementalHash for MacHash<M> {
+    fn update(&mut self, data: &[u8]) {
+        self.0.update(data);
+    }
+
+    fn digest(&self) -> Vec<u8> {
+        self.0.clone().finalize().into_bytes().to_vec()
+    }
+}
+
+impl IncrementalHash for Crc32 {
+    fn update(&mut self, data: &[u8]) {
+        Crc32::update(self, data);
+    }
+
+    fn digest(&self) -> Vec<u8> {
+        self.clone().finalize().to_be_bytes().to_vec()
+    }
+}
+
+impl IncrementalHash for Adler32 {
+    fn update(&mut self, data: &[u8]) {
+        self.write_slice(data);
+    }
+
+    fn digest(&self) -> Vec<u8> {
+        self.checksum().to_be_bytes().to_vec()
+    }
+}
+
+impl IncrementalHash for Xxh64 {
+    fn update(&mut self, data: &[u8]) {
+        Xxh64::update(self, data);
+    }
+
+    fn digest(&self) -> Vec<u8> {
+        Xxh64::digest(self).to_be_bytes().to_vec()
+    }
+}
+
+impl IncrementalHash for Xxh3Default {
+    fn update(&mut self, data: &[u8]) {
+        Xxh3Default::update(self, data);
+    }
+
+    fn digest(&self) -> Vec<u8> {
+        Xxh3Default::digest(self).to_be_bytes().to_vec()
+    }
+}
+
+impl IncrementalHash for SipHasher24 {
+    fn update(&mut self, data: &[u8]) {
+        self.write(data);
+    }
+
+    fn digest(&self) -> Vec<u8> {
+        self.finish().to_be_bytes().to_vec()
+    }
+}
+
+/**
+    Converts the given bytes into a string of lowercase hex digits.
+*/
+pub(crate) fn to_hex(bytes: &[u8]) -> String {
+    // We don't want to return raw binary data generally, since that's not
+    // what most people want a hash for. So we have to make a hex string.
+    bytes
+        .iter()
+        .fold(String::with_capacity(bytes.len() * 2), |mut output, b| {
+            let _ = write!(output, "{b:02x}");
+            output
+        })
+}
+
+impl HashOptions {
+    /**
+        Computes the hash for the `message` using whatever `algorithm` is
+        contained within this struct and returns it as a string of hex digits.
+
+        The `secret` is not used, meaning that `SipHash` uses a key of all
+        zeroes - use [`HashOptions::keyed_hash`] to use the secret as its key.
+    */
+    #[inline]
+    #[must_use]
+    pub fn hash(self) -> String {
+        let mut hasher = self.algorithm.hasher();
+        hasher.update(&self.message);
+        to_hex(&hasher.digest())
+    }
+
+    /**
+        Computes the hash for the `message` using whatever `algorithm` is
+        contained within this struct, using the `secret` as its key if the
+        algorithm is a keyed hash function, such as `SipHash`.
+
+        For any other algorithm, or if no `secret` is provided,
+        this is the same as [`HashOptions::hash`].
+
+        # Errors
+
+        If the algorithm is a keyed hash function and the `secret` is not a valid key.
+    */
+    #[inline]
+    pub fn keyed_hash(self) -> LuaResult<String> {
+        match (self.algorithm, &self.secret) {
+            (HashAlgorithm::SipHash, Some(key)) => {
+                let mut hasher = self.algorithm.keyed_hasher(key)?;
+                hasher.update(&self.message);
+                Ok(to_hex(&hasher.digest()))
+            }
+            _ => Ok(self.hash()),
+        }
+    }
+
+    /**
+        Computes the HMAC for the `message` using whatever `algorithm` and
+        `secret` are contained within this struct. The computed value is
+        returned as a string of hex digits.
+
+        # Errors
+
+        If the `secret` is not provided or is otherwise invalid,
+        or if the algorithm is not a cryptographic hash function.
+    */
+    #[inline]
+    pub fn hmac(self) -> LuaResult<String> {
+        let secret = self
+            .secret
+            .ok_or_else(|| LuaError::FromLuaConversionError {
+                from: "nil",
+                to: "string or buffer".to_string(),
+                message: Some("Argument #3 missing or nil".to_string()),
+            })?;
+
+        let mut hasher = self.algorithm.hmac_hasher(&secret)?;
+        hasher.update(&self.message);
+        Ok(to_hex(&hasher.digest()))
     }
 }
 
@@ -198,6 +425,12 @@ impl FromLua for HashAlgorithm {
 
                 "blake3" => Ok(Self::Blake3),
 
+                "crc32" | "crc-32" => Ok(Self::Crc32),
+                "adler32" | "adler-32" => Ok(Self::Adler32),
+                "xxh64" | "xxhash64" => Ok(Self::Xxh64),
+                "xxh3" | "xxh3-64" | "xxh3_64" => Ok(Self::Xxh3),
+                "siphash" | "siphash-2-4" | "siphash24" => Ok(Self::SipHash),
+
                 _ => Err(LuaError::FromLuaConversionError {
                     from: "string",
                     to: "HashAlgorithm".to_string(),
@@ -258,3 +491,90 @@ impl FromLuaMulti for HashOptions {
         })
     }
 }
+
+/**
+    The format to return a digest in.
+*/
+#[derive(Debug, Clone, Copy, Default)]
+pub enum HashDigestFormat {
+    #[default]
+    Hex,
+    Binary,
+}
+
+impl FromLua for HashDigestFormat {
+    fn from_lua(value: LuaValue, _: &Lua) -> LuaResult<Self> {
+        match &value {
+            LuaValue::Nil => Ok(Self::default()),
+            LuaValue::String(s) => match s.to_string_lossy().to_ascii_lowercase().trim() {
+                "hex" => Ok(Self::Hex),
+                "binary" => Ok(Self::Binary),
+                kind => Err(LuaError::FromLuaConversionError {
+                    from: value.type_name(),
+                    to: "HashDigestFormat".to_string(),
+                    message: Some(format!(
+                        "Invalid digest format '{kind}', valid formats are:  hex, binary"
+                    )),
+                }),
+            },
+            _ => Err(LuaError::FromLuaConversionError {
+                from: value.type_name(),
+                to: "HashDigestFormat".to_string(),
+                message: None,
+            }),
+        }
+    }
+}
+
+/**
+    An incremental hasher, created using `serde.hasher`.
+
+    Messages can be fed to the hasher in chunks of any size, meaning
+    large files can be hashed without reading them fully into memory.
+*/
+pub struct Hasher {
+    algorithm: HashAlgorithm,
+    inner: Box<dyn IncrementalHash>,
+}
+
+impl Hasher {
+    /**
+        Creates a new incremental hasher for the given algorithm.
+
+        If a secret is given, an HMAC is computed for cryptographic
+        algorithms, and it is used as the key for `SipHash`.
+
+        # Errors
+
+        Errors if the secret is invalid, or if a secret is given
+        for any other non-cryptographic algorithm.
+    */
+    pub fn new(algorithm: HashAlgorithm, secret: Option<&[u8]>) -> LuaResult<Self> {
+        let inner = match secret {
+            None => algorithm.hasher(),
+            Some(key) if algorithm == HashAlgorithm::SipHash => algorithm.keyed_hasher(key)?,
+            Some(secret) => algorithm.hmac_hasher(secret)?,
+        };
+        Ok(Self { algorithm, inner })
+    }
+}
+
+impl LuaUserData for Hasher {
+    fn add_fields<F: LuaUserDataFields<Self>>(fields: &mut F) {
+        fields.add_field_method_get("algorithm", |_, this| Ok(this.algorithm.name()));
+    }
+
+    fn add_methods<M: LuaUserDataMethods<Self>>(methods: &mut M) {
+        methods.add_method_mut("update", |_, this, data: BString| {
+            this.inner.update(&data);
+            Ok(())
+        });
+        methods.add_method("digest", |lua, this, format: HashDigestFormat| {
+            let digest = this.inner.digest();
+            match format {
+                HashDigestFormat::Hex => lua.create_string(to_hex(&digest)),
+                HashDigestFormat::Binary => lua.create_string(digest),
+            }
+        });
+    }
+}
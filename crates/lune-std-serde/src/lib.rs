@@ -1,17 +1,49 @@
 #![allow(clippy::cargo_common_metadata)]
 
+use blocking::unblock;
 use bstr::BString;
 use mlua::prelude::*;
 
 use lune_utils::TableBuilder;
 
+use self::markers::{Markers, TableKind};
+
+mod binary_text;
 mod compress_decompress;
+mod compress_stream;
+mod csv_codec;
+mod derive_key;
+mod encodable;
 mod encode_decode;
+mod encrypt_decrypt;
 mod hash;
+mod markers;
+mod password;
+mod random;
+mod sign_verify;
+mod stream_decode;
+mod xml_codec;
 
-pub use self::compress_decompress::{CompressDecompressFormat, compress, decompress};
-pub use self::encode_decode::{EncodeDecodeConfig, EncodeDecodeFormat, decode, encode};
-pub use self::hash::HashOptions;
+pub use self::binary_text::BinaryTextEncoding;
+pub use self::compress_decompress::{
+    CompressDecompressFormat, CompressDecompressOptions, compress, compress_with_options,
+    decompress, decompress_with_options,
+};
+pub use self::compress_stream::CompressDecompressStream;
+pub use self::csv_codec::{CsvOptions, CsvQuoteStyle, CsvReader, CsvSource};
+pub use self::derive_key::{hkdf, pbkdf2};
+pub use self::encode_decode::{
+    DecodeOptions, EncodeDecodeConfig, EncodeDecodeFormat, decode, decode_with_options, encode,
+};
+pub use self::encrypt_decrypt::{EncryptDecryptAlgorithm, EncryptDecryptOptions, decrypt, encrypt};
+pub use self::hash::{HashAlgorithm, HashDigestFormat, HashOptions, Hasher, IncrementalHash};
+pub use self::password::{
+    PasswordHashAlgorithm, PasswordHashOptions, hash_password, verify_password,
+};
+pub use self::random::{UuidVersion, random_bytes, uuid};
+pub use self::sign_verify::{KeyFormat, PrivateKey, PublicKey, SignVerifyAlgorithm};
+pub use self::stream_decode::{StreamDecodeFormat, StreamDecoder};
+pub use self::xml_codec::{XmlDecodeOptions, XmlEncodeOptions};
 
 const TYPEDEFS: &str = include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/types.d.luau"));
 
@@ -31,13 +63,52 @@ pub fn typedefs() -> String {
     Errors when out of memory.
 */
 pub fn module(lua: Lua) -> LuaResult<LuaTable> {
+    let csv = csv_module(lua.clone())?;
+    let xml = xml_module(lua.clone())?;
     TableBuilder::new(lua)?
         .with_function("encode", serde_encode)?
         .with_function("decode", serde_decode)?
+        .with_function("decoder", serde_decoder)?
+        .with_value("null", LuaValue::NULL)?
+        .with_function("array", serde_array)?
+        .with_function("object", serde_object)?
         .with_async_function("compress", serde_compress)?
         .with_async_function("decompress", serde_decompress)?
+        .with_function("compressor", serde_compressor)?
+        .with_function("decompressor", serde_decompressor)?
         .with_function("hash", hash_message)?
         .with_function("hmac", hmac_message)?
+        .with_function("hasher", create_hasher)?
+        .with_function("encrypt", serde_encrypt)?
+        .with_function("decrypt", serde_decrypt)?
+        .with_function("generateKeyPair", serde_generate_key_pair)?
+        .with_function("exportPrivateKey", serde_export_private_key)?
+        .with_function("exportPublicKey", serde_export_public_key)?
+        .with_function("sign", serde_sign)?
+        .with_function("verify", serde_verify)?
+        .with_function("randomBytes", serde_random_bytes)?
+        .with_function("uuid", serde_uuid)?
+        .with_async_function("hashPassword", serde_hash_password)?
+        .with_async_function("verifyPassword", serde_verify_password)?
+        .with_async_function("pbkdf2", serde_pbkdf2)?
+        .with_function("hkdf", serde_hkdf)?
+        .with_value("csv", csv)?
+        .with_value("xml", xml)?
+        .build_readonly()
+}
+
+fn csv_module(lua: Lua) -> LuaResult<LuaTable> {
+    TableBuilder::new(lua)?
+        .with_function("encode", csv_encode)?
+        .with_function("decode", csv_decode)?
+        .with_function("reader", csv_reader)?
+        .build_readonly()
+}
+
+fn xml_module(lua: Lua) -> LuaResult<LuaTable> {
+    TableBuilder::new(lua)?
+        .with_function("encode", xml_encode)?
+        .with_function("decode", xml_decode)?
         .build_readonly()
 }
 
@@ -49,31 +120,218 @@ fn serde_encode(
     encode(value, lua, config)
 }
 
-fn serde_decode(lua: &Lua, (format, bs): (EncodeDecodeFormat, BString)) -> LuaResult<LuaValue> {
+fn serde_decode(
+    lua: &Lua,
+    (format, bs, options): (EncodeDecodeFormat, BString, DecodeOptions),
+) -> LuaResult<LuaValue> {
     let config = EncodeDecodeConfig::from(format);
-    decode(bs, lua, config)
+    decode_with_options(bs, lua, config, &options)
+}
+
+fn serde_array(lua: &Lua, tab: Option<LuaTable>) -> LuaResult<LuaTable> {
+    mark_table(lua, tab, TableKind::Array)
+}
+
+fn serde_object(lua: &Lua, tab: Option<LuaTable>) -> LuaResult<LuaTable> {
+    mark_table(lua, tab, TableKind::Object)
+}
+
+fn mark_table(lua: &Lua, tab: Option<LuaTable>, kind: TableKind) -> LuaResult<LuaTable> {
+    let tab = match tab {
+        Some(tab) => tab,
+        None => lua.create_table()?,
+    };
+    Markers::get(lua)?.mark(&tab, kind)?;
+    Ok(tab)
+}
+
+fn serde_decoder(
+    _: &Lua,
+    (format, options): (StreamDecodeFormat, DecodeOptions),
+) -> LuaResult<StreamDecoder> {
+    Ok(StreamDecoder::new(format, options))
 }
 
 async fn serde_compress(
     lua: Lua,
-    (format, bs, level): (CompressDecompressFormat, BString, Option<i32>),
+    (format, bs, options): (CompressDecompressFormat, BString, CompressDecompressOptions),
 ) -> LuaResult<LuaString> {
-    let bytes = compress(bs, format, level).await?;
+    let bytes = compress_with_options(bs, format, &options).await?;
     lua.create_string(bytes)
 }
 
 async fn serde_decompress(
     lua: Lua,
-    (format, bs): (CompressDecompressFormat, BString),
+    (format, bs, options): (CompressDecompressFormat, BString, CompressDecompressOptions),
 ) -> LuaResult<LuaString> {
-    let bytes = decompress(bs, format).await?;
+    let bytes = decompress_with_options(bs, format, &options).await?;
     lua.create_string(bytes)
 }
 
+fn serde_compressor(
+    _: &Lua,
+    (format, options): (CompressDecompressFormat, CompressDecompressOptions),
+) -> LuaResult<CompressDecompressStream> {
+    CompressDecompressStream::compressor(format, &options)
+}
+
+fn serde_decompressor(
+    _: &Lua,
+    (format, options): (CompressDecompressFormat, CompressDecompressOptions),
+) -> LuaResult<CompressDecompressStream> {
+    CompressDecompressStream::decompressor(format, &options)
+}
+
+fn csv_encode(lua: &Lua, (rows, options): (LuaValue, CsvOptions)) -> LuaResult<LuaString> {
+    csv_codec::encode(lua, rows, &options)
+}
+
+fn csv_decode(lua: &Lua, (bs, options): (BString, CsvOptions)) -> LuaResult<LuaTable> {
+    csv_codec::decode(lua, bs, &options)
+}
+
+fn csv_reader(_: &Lua, (source, options): (CsvSource, CsvOptions)) -> LuaResult<CsvReader> {
+    Ok(CsvReader::new(source, &options))
+}
+
+fn xml_encode(lua: &Lua, (element, options): (LuaValue, XmlEncodeOptions)) -> LuaResult<LuaString> {
+    xml_codec::encode(lua, element, &options)
+}
+
+fn xml_decode(lua: &Lua, (bs, options): (BString, XmlDecodeOptions)) -> LuaResult<LuaTable> {
+    xml_codec::decode(lua, bs, &options)
+}
+
 fn hash_message(lua: &Lua, options: HashOptions) -> LuaResult<LuaString> {
-    lua.create_string(options.hash())
+    lua.create_string(options.keyed_hash()?)
 }
 
 fn hmac_message(lua: &Lua, options: HashOptions) -> LuaResult<LuaString> {
     lua.create_string(options.hmac()?)
 }
+
+fn create_hasher(
+    _: &Lua,
+    (algorithm, secret): (HashAlgorithm, Option<BString>),
+) -> LuaResult<Hasher> {
+    Hasher::new(algorithm, secret.as_deref().map(AsRef::as_ref))
+}
+
+fn serde_encrypt(
+    lua: &Lua,
+    (algorithm, key, plaintext, options): (
+        EncryptDecryptAlgorithm,
+        BString,
+        BString,
+        EncryptDecryptOptions,
+    ),
+) -> LuaResult<LuaString> {
+    lua.create_string(encrypt(algorithm, &key, &plaintext, &options)?)
+}
+
+fn serde_decrypt(
+    lua: &Lua,
+    (algorithm, key, data, options): (
+        EncryptDecryptAlgorithm,
+        BString,
+        BString,
+        EncryptDecryptOptions,
+    ),
+) -> LuaResult<LuaString> {
+    lua.create_string(decrypt(algorithm, &key, &data, &options)?)
+}
+
+fn serde_generate_key_pair(
+    lua: &Lua,
+    (algorithm, format): (SignVerifyAlgorithm, KeyFormat),
+) -> LuaResult<LuaTable> {
+    let private_key = PrivateKey::generate(algorithm);
+    let public_key = private_key.public_key();
+    let tab = lua.create_table_with_capacity(0, 2)?;
+    tab.set(
+        "privateKey",
+        lua.create_string(private_key.export(format)?)?,
+    )?;
+    tab.set("publicKey", lua.create_string(public_key.export(format)?)?)?;
+    tab.set_readonly(true);
+    Ok(tab)
+}
+
+fn serde_export_private_key(
+    lua: &Lua,
+    (algorithm, key, format): (SignVerifyAlgorithm, BString, KeyFormat),
+) -> LuaResult<LuaString> {
+    let key = PrivateKey::import(algorithm, &key)?;
+    lua.create_string(key.export(format)?)
+}
+
+fn serde_export_public_key(
+    lua: &Lua,
+    (algorithm, key, format): (SignVerifyAlgorithm, BString, KeyFormat),
+) -> LuaResult<LuaString> {
+    let key = PublicKey::import(algorithm, &key)?;
+    lua.create_string(key.export(format)?)
+}
+
+fn serde_sign(
+    lua: &Lua,
+    (algorithm, private_key, message): (SignVerifyAlgorithm, BString, BString),
+) -> LuaResult<LuaString> {
+    let key = PrivateKey::import(algorithm, &private_key)?;
+    lua.create_string(key.sign(&message))
+}
+
+fn serde_verify(
+    _: &Lua,
+    (algorithm, public_key, message, signature): (SignVerifyAlgorithm, BString, BString, BString),
+) -> LuaResult<bool> {
+    let key = PublicKey::import(algorithm, &public_key)?;
+    Ok(key.verify(&message, &signature))
+}
+
+fn serde_random_bytes(lua: &Lua, count: usize) -> LuaResult<LuaString> {
+    lua.create_string(random_bytes(count)?)
+}
+
+fn serde_uuid(_: &Lua, version: UuidVersion) -> LuaResult<String> {
+    Ok(uuid(version))
+}
+
+async fn serde_hash_password(
+    _: Lua,
+    (algorithm, password, options): (PasswordHashAlgorithm, BString, PasswordHashOptions),
+) -> LuaResult<String> {
+    hash_password(algorithm, password, &options).await
+}
+
+async fn serde_verify_password(_: Lua, (password, hash): (BString, LuaString)) -> LuaResult<bool> {
+    verify_password(password, hash.to_str()?).await
+}
+
+async fn serde_pbkdf2(
+    lua: Lua,
+    (algorithm, password, salt, iterations, length): (HashAlgorithm, BString, BString, u32, usize),
+) -> LuaResult<LuaString> {
+    let bytes = unblock(move || pbkdf2(algorithm, &password, &salt, iterations, length)).await?;
+    lua.create_string(bytes)
+}
+
+fn serde_hkdf(
+    lua: &Lua,
+    (algorithm, key, salt, info, length): (
+        HashAlgorithm,
+        BString,
+        Option<BString>,
+        Option<BString>,
+        usize,
+    ),
+) -> LuaResult<LuaString> {
+    let bytes = hkdf(
+        algorithm,
+        &key,
+        salt.as_deref().map(AsRef::as_ref),
+        info.as_deref().map_or(&[], AsRef::as_ref),
+        length,
+    )?;
+    lua.create_string(bytes)
+}
@@ -0,0 +1,159 @@
+use mlua::prelude::*;
+
+/**
+    The kind of a Lua table, as marked using `serde.array` or `serde.object`.
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum TableKind {
+    Array,
+    Object,
+    Unmarked,
+}
+
+/**
+    Shared state used to disambiguate Lua values when encoding and decoding.
+
+    Arrays are marked using the array metatable provided by mlua, so that they
+    are also recognized by anything else that uses it, while objects are marked
+    using a metatable that is specific to Lune. The original key order of
+    decoded objects is kept in a weak table, so that it can be restored when
+    the same objects are encoded again, without affecting their contents.
+
+    Msgpack extension values are decoded into tables that are marked
+    using their own metatable, so that they can be encoded as extensions again.
+*/
+#[derive(Debug, Clone)]
+pub(crate) struct Markers {
+    array: LuaTable,
+    object: LuaTable,
+    msgpack_ext: LuaTable,
+    key_order: LuaTable,
+}
+
+impl Markers {
+    pub(crate) fn get(lua: &Lua) -> LuaResult<Self> {
+        if let Some(markers) = lua.app_data_ref::<Self>() {
+            return Ok(markers.clone());
+        }
+
+        let object = lua.create_table()?;
+        object.set_readonly(true);
+
+        let msgpack_ext = lua.create_table()?;
+        msgpack_ext.set_readonly(true);
+
+        let key_order = lua.create_table()?;
+        let key_order_meta = lua.create_table()?;
+        key_order_meta.raw_set("__mode", "k")?;
+        key_order.set_metatable(Some(key_order_meta))?;
+
+        let markers = Self {
+            array: lua.array_metatable(),
+            object,
+            msgpack_ext,
+            key_order,
+        };
+        lua.set_app_data(markers.clone());
+        Ok(markers)
+    }
+
+    pub(crate) fn kind(&self, table: &LuaTable) -> TableKind {
+        match table.metatable() {
+            Some(meta) if meta == self.array => TableKind::Array,
+            Some(meta) if meta == self.object => TableKind::Object,
+            _ => TableKind::Unmarked,
+        }
+    }
+
+    /**
+        Marks the given table as either an array or an object.
+
+        # Errors
+
+        Errors if the table already has a metatable that is not one of the markers.
+    */
+    pub(crate) fn mark(&self, table: &LuaTable, kind: TableKind) -> LuaResult<()> {
+        let meta = match kind {
+            TableKind::Array => &self.array,
+            TableKind::Object => &self.object,
+            TableKind::Unmarked => return Ok(()),
+        };
+        if self.kind(table) == TableKind::Unmarked && table.metatable().is_some() {
+            return Err(LuaError::RuntimeError(
+                "Table already has a metatable and can not be marked as an array or object"
+                    .to_string(),
+            ));
+        }
+        table.set_metatable(Some(meta.clone()))
+    }
+
+    /**
+        Checks if the given table is a decoded msgpack extension value.
+    */
+    pub(crate) fn is_msgpack_ext(&self, table: &LuaTable) -> bool {
+        table
+            .metatable()
+            .is_some_and(|meta| meta == self.msgpack_ext)
+    }
+
+    /**
+        Marks the given table as a msgpack extension value.
+    */
+    pub(crate) fn mark_msgpack_ext(&self, table: &LuaTable) -> LuaResult<()> {
+        table.set_metatable(Some(self.msgpack_ext.clone()))
+    }
+
+    /**
+        Records the order of keys for a decoded object.
+    */
+    pub(crate) fn set_key_order(&self, table: &LuaTable, keys: LuaTable) -> LuaResult<()> {
+        self.key_order.raw_set(table, keys)
+    }
+
+    /**
+        Returns the recorded order of keys for an object, if any.
+    */
+    pub(crate) fn key_order(&self, table: &LuaTable) -> LuaResult<Option<LuaTable>> {
+        self.key_order.raw_get(table)
+    }
+
+    /**
+        Reorders the key-value pairs of an object using its recorded order of keys, if any.
+
+        Keys with a recorded order go first, in that order, followed by any new
+        keys, which keep the same relative order that they were given in.
+    */
+    pub(crate) fn restore_key_order<K, V>(
+        &self,
+        lua: &Lua,
+        table: &LuaTable,
+        pairs: &mut Vec<(K, V)>,
+    ) -> LuaResult<()>
+    where
+        K: IntoLua + Clone,
+    {
+        let Some(order) = self.key_order(table)? else {
+            return Ok(());
+        };
+
+        let positions = lua.create_table_with_capacity(0, order.raw_len())?;
+        for (position, key) in order.sequence_values::<LuaValue>().enumerate() {
+            let key = key?;
+            if positions.raw_get::<Option<usize>>(&key)?.is_none() {
+                positions.raw_set(key, position)?;
+            }
+        }
+
+        let mut ranked = pairs
+            .drain(..)
+            .map(|pair| {
+                let position = positions.raw_get::<Option<usize>>(pair.0.clone())?;
+                Ok((position.unwrap_or(usize::MAX), pair))
+            })
+            .collect::<LuaResult<Vec<_>>>()?;
+        ranked.sort_by_key(|(position, _)| *position);
+        pairs.extend(ranked.into_iter().map(|(_, pair)| pair));
+
+        Ok(())
+    }
+}
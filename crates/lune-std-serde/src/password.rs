@@ -0,0 +1,229 @@
+use argon2::password_hash::{self, PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::{Algorithm, Argon2, Params, Version};
+use blocking::unblock;
+use mlua::prelude::*;
+use rand_core::OsRng;
+
+const BCRYPT_MAX_PASSWORD_LENGTH: usize = 72;
+
+/**
+    A password hashing algorithm supported by Lune.
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PasswordHashAlgorithm {
+    Argon2id,
+    Bcrypt,
+}
+
+impl PasswordHashAlgorithm {
+    #[must_use]
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Argon2id => "argon2id",
+            Self::Bcrypt => "bcrypt",
+        }
+    }
+
+    /**
+        Detects the algorithm used to create the given password hash,
+        from the prefix of its PHC or modular crypt format string.
+    */
+    #[must_use]
+    pub fn detect(hash: &str) -> Option<Self> {
+        if hash.starts_with("$argon2") {
+            Some(Self::Argon2id)
+        } else if ["$2a$", "$2b$", "$2x$", "$2y$"]
+            .iter()
+            .any(|prefix| hash.starts_with(prefix))
+        {
+            Some(Self::Bcrypt)
+        } else {
+            None
+        }
+    }
+}
+
+impl FromLua for PasswordHashAlgorithm {
+    fn from_lua(value: LuaValue, _: &Lua) -> LuaResult<Self> {
+        if let LuaValue::String(s) = &value {
+            match s.to_string_lossy().to_ascii_lowercase().trim() {
+                "argon2id" | "argon2" => Ok(Self::Argon2id),
+                "bcrypt" => Ok(Self::Bcrypt),
+                kind => Err(LuaError::FromLuaConversionError {
+                    from: value.type_name(),
+                    to: "PasswordHashAlgorithm".to_string(),
+                    message: Some(format!(
+                        "Invalid algorithm '{kind}', valid algorithms are:  argon2id, bcrypt"
+                    )),
+                }),
+            }
+        } else {
+            Err(LuaError::FromLuaConversionError {
+                from: value.type_name(),
+                to: "PasswordHashAlgorithm".to_string(),
+                message: None,
+            })
+        }
+    }
+}
+
+/**
+    Options for hashing passwords.
+
+    The cost is only used by bcrypt, while the memory cost, time cost,
+    and parallelism are only used by Argon2id. Any options that are
+    not given use the recommended defaults for the algorithm.
+*/
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PasswordHashOptions {
+    pub cost: Option<u32>,
+    pub memory_cost: Option<u32>,
+    pub time_cost: Option<u32>,
+    pub parallelism: Option<u32>,
+}
+
+impl PasswordHashOptions {
+    fn ensure_supported(&self, algorithm: PasswordHashAlgorithm) -> LuaResult<()> {
+        let unsupported = match algorithm {
+            PasswordHashAlgorithm::Argon2id => self.cost.is_some().then_some("cost"),
+            PasswordHashAlgorithm::Bcrypt => {
+                if self.memory_cost.is_some() {
+                    Some("memoryCost")
+                } else if self.time_cost.is_some() {
+                    Some("timeCost")
+                } else if self.parallelism.is_some() {
+                    Some("parallelism")
+                } else {
+                    None
+                }
+            }
+        };
+        match unsupported {
+            Some(option) => Err(LuaError::RuntimeError(format!(
+                "The '{option}' option is not supported for the '{}' algorithm",
+                algorithm.name()
+            ))),
+            None => Ok(()),
+        }
+    }
+}
+
+impl FromLua for PasswordHashOptions {
+    fn from_lua(value: LuaValue, _: &Lua) -> LuaResult<Self> {
+        Ok(match value {
+            LuaValue::Nil => Self::default(),
+            LuaValue::Table(t) => Self {
+                cost: t.get("cost")?,
+                memory_cost: t.get("memoryCost")?,
+                time_cost: t.get("timeCost")?,
+                parallelism: t.get("parallelism")?,
+            },
+            _ => {
+                return Err(LuaError::FromLuaConversionError {
+                    from: value.type_name(),
+                    to: "PasswordHashOptions".to_string(),
+                    message: Some(format!(
+                        "Invalid password hashing options - expected table or nil, got {}",
+                        value.type_name()
+                    )),
+                });
+            }
+        })
+    }
+}
+
+/**
+    Hashes the given password using the specified algorithm and options,
+    with a randomly generated salt.
+
+    The returned hash is a PHC string for Argon2id, and a modular crypt
+    format string for bcrypt, both of which contain all of the parameters
+    necessary to verify the password later on using `verify_password`.
+
+    Password hashing is deliberately slow, so it runs on a background thread.
+
+    # Errors
+
+    Errors if the options are invalid or not supported by the algorithm,
+    or if the password is longer than 72 bytes when using bcrypt.
+*/
+pub async fn hash_password(
+    algorithm: PasswordHashAlgorithm,
+    password: impl AsRef<[u8]>,
+    options: &PasswordHashOptions,
+) -> LuaResult<String> {
+    options.ensure_supported(algorithm)?;
+
+    let password = password.as_ref().to_vec();
+    let options = *options;
+    unblock(move || match algorithm {
+        PasswordHashAlgorithm::Argon2id => {
+            let params = Params::new(
+                options.memory_cost.unwrap_or(Params::DEFAULT_M_COST),
+                options.time_cost.unwrap_or(Params::DEFAULT_T_COST),
+                options.parallelism.unwrap_or(Params::DEFAULT_P_COST),
+                None,
+            )
+            .map_err(|e| LuaError::RuntimeError(format!("Invalid Argon2id options - {e}")))?;
+            let salt = SaltString::generate(&mut OsRng);
+            Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+                .hash_password(&password, &salt)
+                .map(|hash| hash.to_string())
+                .map_err(|e| LuaError::RuntimeError(format!("Failed to hash password - {e}")))
+        }
+        PasswordHashAlgorithm::Bcrypt => {
+            let cost = options.cost.unwrap_or(bcrypt::DEFAULT_COST);
+            // NOTE: The length is checked here instead of using `bcrypt::non_truncating_hash`,
+            // since that also counts the NUL terminator and would reject 72 byte passwords
+            if password.len() > BCRYPT_MAX_PASSWORD_LENGTH {
+                return Err(LuaError::RuntimeError(format!(
+                    "Passwords hashed using bcrypt can be at most {BCRYPT_MAX_PASSWORD_LENGTH} bytes long, got {} bytes",
+                    password.len()
+                )));
+            }
+            bcrypt::hash(&password, cost)
+                .map_err(|e| LuaError::RuntimeError(format!("Failed to hash password - {e}")))
+        }
+    })
+    .await
+}
+
+/**
+    Verifies that the given password matches a hash created using `hash_password`,
+    or any other Argon2 or bcrypt hash in the standard string formats.
+
+    Password hashing is deliberately slow, so it runs on a background thread.
+
+    # Errors
+
+    Errors if the hash is malformed, or was not created using a supported algorithm.
+*/
+pub async fn verify_password(password: impl AsRef<[u8]>, hash: impl AsRef<str>) -> LuaResult<bool> {
+    let password = password.as_ref().to_vec();
+    let hash = hash.as_ref().to_string();
+    let Some(algorithm) = PasswordHashAlgorithm::detect(&hash) else {
+        return Err(LuaError::RuntimeError(
+            "Unrecognized password hash - expected an Argon2 or bcrypt hash".to_string(),
+        ));
+    };
+
+    unblock(move || match algorithm {
+        PasswordHashAlgorithm::Argon2id => {
+            let parsed = PasswordHash::new(&hash)
+                .map_err(|e| LuaError::RuntimeError(format!("Invalid Argon2 hash - {e}")))?;
+            if parsed.hash.is_none() {
+                return Err(LuaError::RuntimeError(
+                    "Invalid Argon2 hash - missing hash output".to_string(),
+                ));
+            }
+            match Argon2::default().verify_password(&password, &parsed) {
+                Ok(()) => Ok(true),
+                Err(password_hash::Error::Password) => Ok(false),
+                Err(e) => Err(LuaError::RuntimeError(format!("Invalid Argon2 hash - {e}"))),
+            }
+        }
+        PasswordHashAlgorithm::Bcrypt => bcrypt::verify(&password, &hash)
+            .map_err(|e| LuaError::RuntimeError(format!("Invalid bcrypt hash - {e}"))),
+    })
+    .await
+}
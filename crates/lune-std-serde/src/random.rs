@@ -0,0 +1,79 @@
+use mlua::prelude::*;
+use rand_core::{OsRng, RngCore};
+use uuid::Uuid;
+
+/**
+    A UUID version that can be generated by Lune.
+*/
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum UuidVersion {
+    #[default]
+    V4,
+    V7,
+}
+
+impl FromLua for UuidVersion {
+    fn from_lua(value: LuaValue, _: &Lua) -> LuaResult<Self> {
+        if let LuaValue::Nil = value {
+            Ok(Self::default())
+        } else if let LuaValue::String(s) = &value {
+            match s.to_string_lossy().to_ascii_lowercase().trim() {
+                "v4" => Ok(Self::V4),
+                "v7" => Ok(Self::V7),
+                kind => Err(LuaError::FromLuaConversionError {
+                    from: value.type_name(),
+                    to: "UuidVersion".to_string(),
+                    message: Some(format!(
+                        "Invalid version '{kind}', valid versions are:  v4, v7"
+                    )),
+                }),
+            }
+        } else {
+            Err(LuaError::FromLuaConversionError {
+                from: value.type_name(),
+                to: "UuidVersion".to_string(),
+                message: None,
+            })
+        }
+    }
+}
+
+/**
+    The maximum number of random bytes that can be generated at once.
+*/
+const MAX_RANDOM_BYTES: usize = 64 * 1024 * 1024;
+
+/**
+    Generates the given number of cryptographically secure random bytes,
+    using the random number generator provided by the operating system.
+
+    # Errors
+
+    Errors if more than 64 MiB of random bytes are requested at once.
+*/
+pub fn random_bytes(count: usize) -> LuaResult<Vec<u8>> {
+    if count > MAX_RANDOM_BYTES {
+        return Err(LuaError::RuntimeError(format!(
+            "Can not generate more than {MAX_RANDOM_BYTES} random bytes at once, got {count}"
+        )));
+    }
+    let mut bytes = vec![0; count];
+    OsRng.fill_bytes(&mut bytes);
+    Ok(bytes)
+}
+
+/**
+    Generates a new UUID of the given version, formatted as a lowercase
+    hyphenated string, such as `67e55044-10b1-426f-9247-bb680e5fe0c8`.
+
+    Version 4 UUIDs are completely random, while version 7 UUIDs start
+    with a millisecond timestamp, making them sortable by creation time.
+*/
+#[must_use]
+pub fn uuid(version: UuidVersion) -> String {
+    let uuid = match version {
+        UuidVersion::V4 => Uuid::new_v4(),
+        UuidVersion::V7 => Uuid::now_v7(),
+    };
+    uuid.hyphenated().to_string()
+}
@@ -0,0 +1,354 @@
+use bstr::ByteSlice;
+use mlua::prelude::*;
+use rand_core::OsRng;
+
+use ed25519_dalek::pkcs8::{
+    DecodePrivateKey, DecodePublicKey, EncodePrivateKey, EncodePublicKey,
+    spki::der::pem::LineEnding,
+};
+use ed25519_dalek::{
+    Signature as Ed25519Signature, Signer, SigningKey as Ed25519SigningKey,
+    VerifyingKey as Ed25519VerifyingKey,
+};
+use p256::SecretKey as P256SecretKey;
+use p256::ecdsa::{
+    Signature as P256Signature, SigningKey as P256SigningKey, VerifyingKey as P256VerifyingKey,
+    signature::Verifier,
+};
+
+const PEM_PREFIX: &[u8] = b"-----BEGIN ";
+
+/**
+    A digital signature algorithm supported by Lune.
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SignVerifyAlgorithm {
+    Ed25519,
+    EcdsaP256,
+}
+
+impl SignVerifyAlgorithm {
+    #[must_use]
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Ed25519 => "ed25519",
+            Self::EcdsaP256 => "ecdsa-p256",
+        }
+    }
+}
+
+impl FromLua for SignVerifyAlgorithm {
+    fn from_lua(value: LuaValue, _: &Lua) -> LuaResult<Self> {
+        if let LuaValue::String(s) = &value {
+            match s.to_string_lossy().to_ascii_lowercase().trim() {
+                "ed25519" => Ok(Self::Ed25519),
+                "ecdsa-p256" | "p256" => Ok(Self::EcdsaP256),
+                kind => Err(LuaError::FromLuaConversionError {
+                    from: value.type_name(),
+                    to: "SignVerifyAlgorithm".to_string(),
+                    message: Some(format!(
+                        "Invalid algorithm '{kind}', valid algorithms are:  ed25519, ecdsa-p256"
+                    )),
+                }),
+            }
+        } else {
+            Err(LuaError::FromLuaConversionError {
+                from: value.type_name(),
+                to: "SignVerifyAlgorithm".to_string(),
+                message: None,
+            })
+        }
+    }
+}
+
+/**
+    A format that keys can be exported in.
+
+    - `Pem` is PKCS#8 for private keys, and SPKI for public keys, PEM-encoded.
+    - `Der` is the same as `Pem`, but in its binary DER form.
+    - `Raw` is the bare key bytes - for Ed25519, 32 bytes for either key, and for
+      ECDSA P-256, the 32 byte scalar or the uncompressed 65 byte SEC1 point.
+*/
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum KeyFormat {
+    #[default]
+    Pem,
+    Der,
+    Raw,
+}
+
+impl FromLua for KeyFormat {
+    fn from_lua(value: LuaValue, _: &Lua) -> LuaResult<Self> {
+        match &value {
+            LuaValue::Nil => Ok(Self::default()),
+            LuaValue::String(s) => match s.to_string_lossy().to_ascii_lowercase().trim() {
+                "pem" => Ok(Self::Pem),
+                "der" => Ok(Self::Der),
+                "raw" => Ok(Self::Raw),
+                kind => Err(LuaError::FromLuaConversionError {
+                    from: value.type_name(),
+                    to: "KeyFormat".to_string(),
+                    message: Some(format!(
+                        "Invalid key format '{kind}', valid formats are:  pem, der, raw"
+                    )),
+                }),
+            },
+            _ => Err(LuaError::FromLuaConversionError {
+                from: value.type_name(),
+                to: "KeyFormat".to_string(),
+                message: None,
+            }),
+        }
+    }
+}
+
+fn invalid_key(algorithm: SignVerifyAlgorithm, kind: &str) -> LuaError {
+    LuaError::RuntimeError(format!(
+        "Invalid {kind} key for '{}' - expected a PEM, DER, or raw key",
+        algorithm.name()
+    ))
+}
+
+fn pem_str(bytes: &[u8]) -> Option<&str> {
+    if bytes.trim_start().starts_with(PEM_PREFIX) {
+        bytes.to_str().ok()
+    } else {
+        None
+    }
+}
+
+/**
+    A private key, used for signing.
+*/
+#[derive(Debug, Clone)]
+pub enum PrivateKey {
+    Ed25519(Box<Ed25519SigningKey>),
+    EcdsaP256(P256SigningKey),
+}
+
+impl PrivateKey {
+    /**
+        Generates a new random private key for the given algorithm.
+    */
+    #[must_use]
+    pub fn generate(algorithm: SignVerifyAlgorithm) -> Self {
+        match algorithm {
+            SignVerifyAlgorithm::Ed25519 => {
+                Self::Ed25519(Box::new(Ed25519SigningKey::generate(&mut OsRng)))
+            }
+            SignVerifyAlgorithm::EcdsaP256 => Self::EcdsaP256(P256SigningKey::random(&mut OsRng)),
+        }
+    }
+
+    /**
+        Imports a private key from PEM, DER, or raw bytes.
+
+        PEM and DER keys must be PKCS#8, except for ECDSA P-256
+        keys, which may also be SEC1 (`EC PRIVATE KEY`) keys.
+
+        # Errors
+
+        Errors if the key is not a valid private key for the algorithm.
+    */
+    pub fn import(algorithm: SignVerifyAlgorithm, bytes: &[u8]) -> LuaResult<Self> {
+        let err = || invalid_key(algorithm, "private");
+        Ok(match algorithm {
+            SignVerifyAlgorithm::Ed25519 => {
+                let key = if let Some(pem) = pem_str(bytes) {
+                    Ed25519SigningKey::from_pkcs8_pem(pem).map_err(|_| err())?
+                } else if let Ok(raw) = <[u8; 32]>::try_from(bytes) {
+                    Ed25519SigningKey::from_bytes(&raw)
+                } else {
+                    Ed25519SigningKey::from_pkcs8_der(bytes).map_err(|_| err())?
+                };
+                Self::Ed25519(Box::new(key))
+            }
+            SignVerifyAlgorithm::EcdsaP256 => {
+                let key = if let Some(pem) = pem_str(bytes) {
+                    P256SecretKey::from_pkcs8_pem(pem)
+                        .or_else(|_| P256SecretKey::from_sec1_pem(pem))
+                        .map_err(|_| err())?
+                } else if bytes.len() == 32 {
+                    P256SecretKey::from_slice(bytes).map_err(|_| err())?
+                } else {
+                    P256SecretKey::from_pkcs8_der(bytes)
+                        .or_else(|_| P256SecretKey::from_sec1_der(bytes))
+                        .map_err(|_| err())?
+                };
+                Self::EcdsaP256(key.into())
+            }
+        })
+    }
+
+    /**
+        Exports the private key in the given format.
+
+        # Errors
+
+        Errors if the key could not be encoded.
+    */
+    pub fn export(&self, format: KeyFormat) -> LuaResult<Vec<u8>> {
+        Ok(match (self, format) {
+            (Self::Ed25519(key), KeyFormat::Pem) => key
+                .to_pkcs8_pem(LineEnding::LF)
+                .into_lua_err()?
+                .as_bytes()
+                .to_vec(),
+            (Self::Ed25519(key), KeyFormat::Der) => {
+                key.to_pkcs8_der().into_lua_err()?.as_bytes().to_vec()
+            }
+            (Self::Ed25519(key), KeyFormat::Raw) => key.to_bytes().to_vec(),
+            (Self::EcdsaP256(key), KeyFormat::Pem) => key
+                .to_pkcs8_pem(LineEnding::LF)
+                .into_lua_err()?
+                .as_bytes()
+                .to_vec(),
+            (Self::EcdsaP256(key), KeyFormat::Der) => {
+                key.to_pkcs8_der().into_lua_err()?.as_bytes().to_vec()
+            }
+            (Self::EcdsaP256(key), KeyFormat::Raw) => key.to_bytes().to_vec(),
+        })
+    }
+
+    /**
+        Returns the public key for this private key.
+    */
+    #[must_use]
+    pub fn public_key(&self) -> PublicKey {
+        match self {
+            Self::Ed25519(key) => PublicKey::Ed25519(key.verifying_key()),
+            Self::EcdsaP256(key) => PublicKey::EcdsaP256(*key.verifying_key()),
+        }
+    }
+
+    /**
+        Signs the given message, returning the raw signature bytes.
+
+        Signatures are always 64 bytes long - for ECDSA P-256, the message is
+        hashed using SHA-256, and the signature is the concatenation of `r` and `s`.
+    */
+    #[must_use]
+    pub fn sign(&self, message: &[u8]) -> Vec<u8> {
+        match self {
+            Self::Ed25519(key) => {
+                let signature: Ed25519Signature = key.sign(message);
+                signature.to_bytes().to_vec()
+            }
+            Self::EcdsaP256(key) => {
+                let signature: P256Signature = key.sign(message);
+                signature.to_bytes().to_vec()
+            }
+        }
+    }
+}
+
+/**
+    A public key, used for verifying signatures.
+*/
+#[derive(Debug, Clone)]
+pub enum PublicKey {
+    Ed25519(Ed25519VerifyingKey),
+    EcdsaP256(P256VerifyingKey),
+}
+
+impl PublicKey {
+    /**
+        Imports a public key from PEM, DER, or raw bytes.
+
+        PEM and DER public keys must be SPKI, but private keys are also
+        accepted in any format except raw, and their public key is used.
+
+        # Errors
+
+        Errors if the key is not a valid public or private key for the algorithm.
+    */
+    pub fn import(algorithm: SignVerifyAlgorithm, bytes: &[u8]) -> LuaResult<Self> {
+        let err = || invalid_key(algorithm, "public");
+        let from_private = || {
+            PrivateKey::import(algorithm, bytes)
+                .map(|key| key.public_key())
+                .map_err(|_| err())
+        };
+        Ok(match algorithm {
+            SignVerifyAlgorithm::Ed25519 => {
+                if let Some(pem) = pem_str(bytes) {
+                    match Ed25519VerifyingKey::from_public_key_pem(pem) {
+                        Ok(key) => Self::Ed25519(key),
+                        Err(_) => from_private()?,
+                    }
+                } else if let Ok(raw) = <[u8; 32]>::try_from(bytes) {
+                    Self::Ed25519(Ed25519VerifyingKey::from_bytes(&raw).map_err(|_| err())?)
+                } else {
+                    match Ed25519VerifyingKey::from_public_key_der(bytes) {
+                        Ok(key) => Self::Ed25519(key),
+                        Err(_) => from_private()?,
+                    }
+                }
+            }
+            SignVerifyAlgorithm::EcdsaP256 => {
+                if let Some(pem) = pem_str(bytes) {
+                    match P256VerifyingKey::from_public_key_pem(pem) {
+                        Ok(key) => Self::EcdsaP256(key),
+                        Err(_) => from_private()?,
+                    }
+                } else if matches!(bytes.len(), 33 | 65) {
+                    Self::EcdsaP256(P256VerifyingKey::from_sec1_bytes(bytes).map_err(|_| err())?)
+                } else {
+                    match P256VerifyingKey::from_public_key_der(bytes) {
+                        Ok(key) => Self::EcdsaP256(key),
+                        Err(_) => from_private()?,
+                    }
+                }
+            }
+        })
+    }
+
+    /**
+        Exports the public key in the given format.
+
+        # Errors
+
+        Errors if the key could not be encoded.
+    */
+    pub fn export(&self, format: KeyFormat) -> LuaResult<Vec<u8>> {
+        Ok(match (self, format) {
+            (Self::Ed25519(key), KeyFormat::Pem) => key
+                .to_public_key_pem(LineEnding::LF)
+                .into_lua_err()?
+                .into_bytes(),
+            (Self::Ed25519(key), KeyFormat::Der) => {
+                key.to_public_key_der().into_lua_err()?.into_vec()
+            }
+            (Self::Ed25519(key), KeyFormat::Raw) => key.to_bytes().to_vec(),
+            (Self::EcdsaP256(key), KeyFormat::Pem) => key
+                .to_public_key_pem(LineEnding::LF)
+                .into_lua_err()?
+                .into_bytes(),
+            (Self::EcdsaP256(key), KeyFormat::Der) => {
+                key.to_public_key_der().into_lua_err()?.into_vec()
+            }
+            (Self::EcdsaP256(key), KeyFormat::Raw) => {
+                key.to_encoded_point(false).as_bytes().to_vec()
+            }
+        })
+    }
+
+    /**
+        Verifies the given signature for the given message.
+
+        ECDSA P-256 signatures may either be 64 bytes long, as
+        returned by `PrivateKey::sign`, or DER-encoded.
+
+        Returns `false` if the signature is malformed or invalid.
+    */
+    #[must_use]
+    pub fn verify(&self, message: &[u8], signature: &[u8]) -> bool {
+        match self {
+            Self::Ed25519(key) => Ed25519Signature::from_slice(signature)
+                .is_ok_and(|signature| key.verify_strict(message, &signature).is_ok()),
+            Self::EcdsaP256(key) => P256Signature::from_slice(signature)
+                .or_else(|_| P256Signature::from_der(signature))
+                .is_ok_and(|signature| key.verify(message, &signature).is_ok()),
+        }
+    }
+}
@@ -0,0 +1,288 @@
+use bstr::{BString, ByteSlice};
+use mlua::prelude::*;
+use serde_json::Value as JsonValue;
+
+use crate::encode_decode::{DecodeOptions, Decoder};
+
+/**
+    A format supported by the streaming decoder.
+
+    - `Json` is concatenated JSON values, optionally separated by whitespace.
+    - `NdJson` is newline-delimited JSON, also known as JSON Lines.
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StreamDecodeFormat {
+    Json,
+    NdJson,
+}
+
+impl FromLua for StreamDecodeFormat {
+    fn from_lua(value: LuaValue, _: &Lua) -> LuaResult<Self> {
+        if let LuaValue::String(s) = &value {
+            match s.to_string_lossy().to_ascii_lowercase().trim() {
+                "json" => Ok(Self::Json),
+                "ndjson" | "jsonl" => Ok(Self::NdJson),
+                kind => Err(LuaError::FromLuaConversionError {
+                    from: value.type_name(),
+                    to: "StreamDecodeFormat".to_string(),
+                    message: Some(format!(
+                        "Invalid format '{kind}', valid formats are:  json, ndjson, jsonl"
+                    )),
+                }),
+            }
+        } else {
+            Err(LuaError::FromLuaConversionError {
+                from: value.type_name(),
+                to: "StreamDecodeFormat".to_string(),
+                message: None,
+            })
+        }
+    }
+}
+
+/**
+    Scanning state for the next json value in a stream.
+
+    Lets the decoder find where a value ends without re-parsing it from
+    the start each time more input arrives, only parsing complete values.
+*/
+#[derive(Debug, Clone, Copy, Default)]
+struct JsonScan {
+    scanned: usize,
+    depth: usize,
+    in_string: bool,
+    escaped: bool,
+}
+
+impl JsonScan {
+    /**
+        Continues scanning the given value, which starts with its first
+        byte, returning the length of the value if it is now complete.
+    */
+    fn scan(&mut self, value: &[u8]) -> Option<usize> {
+        // Numbers and literals such as true, false, and null have no closing
+        // delimiter, and end at the first byte that can not be a part of them
+        let is_scalar = !matches!(value.first(), Some(b'{' | b'[' | b'"'));
+        for (index, &byte) in value.iter().enumerate().skip(self.scanned) {
+            if is_scalar {
+                let continues = byte.is_ascii_alphanumeric() || matches!(byte, b'+' | b'-' | b'.');
+                if index > 0 && !continues {
+                    return Some(index);
+                }
+            } else if self.in_string {
+                if self.escaped {
+                    self.escaped = false;
+                } else if byte == b'\\' {
+                    self.escaped = true;
+                } else if byte == b'"' {
+                    self.in_string = false;
+                    if self.depth == 0 {
+                        return Some(index + 1);
+                    }
+                }
+            } else {
+                match byte {
+                    b'"' => self.in_string = true,
+                    b'{' | b'[' => self.depth += 1,
+                    b'}' | b']' => {
+                        self.depth = self.depth.saturating_sub(1);
+                        if self.depth == 0 {
+                            return Some(index + 1);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+        self.scanned = value.len();
+        None
+    }
+}
+
+/**
+    A decoder that is fed input in chunks of any size, and
+    yields complete values as soon as they have been decoded.
+
+    Created using `serde.decoder`.
+*/
+#[derive(Debug)]
+pub struct StreamDecoder {
+    format: StreamDecodeFormat,
+    options: DecodeOptions,
+    buffer: Vec<u8>,
+    position: usize,
+    line: usize,
+    finished: bool,
+    scan: JsonScan,
+}
+
+impl StreamDecoder {
+    #[must_use]
+    pub fn new(format: StreamDecodeFormat, options: DecodeOptions) -> Self {
+        Self {
+            format,
+            options,
+            buffer: Vec::new(),
+            position: 0,
+            line: 0,
+            finished: false,
+            scan: JsonScan::default(),
+        }
+    }
+
+    /**
+        Adds a chunk of input to the decoder.
+
+        # Errors
+
+        Errors if the decoder has already been finished.
+    */
+    pub fn push(&mut self, chunk: &[u8]) -> LuaResult<()> {
+        if self.finished {
+            return Err(LuaError::runtime(
+                "Can not push more input to a decoder that has been finished",
+            ));
+        }
+        // Drop any input that has already been decoded, so that
+        // we only ever hold on to input for incomplete values
+        self.buffer.drain(..self.position);
+        self.position = 0;
+        self.buffer.extend_from_slice(chunk);
+        Ok(())
+    }
+
+    /**
+        Marks the end of input, meaning any remaining input
+        must be a complete value, and will be decoded as such.
+    */
+    pub fn finish(&mut self) {
+        self.finished = true;
+    }
+
+    /**
+        Decodes the next complete value, if any.
+
+        # Errors
+
+        Errors if the next value is invalid, or if the input
+        has been finished and ends with an incomplete value.
+    */
+    pub fn next_value(&mut self) -> LuaResult<Option<JsonValue>> {
+        match self.format {
+            StreamDecodeFormat::Json => self.next_json(),
+            StreamDecodeFormat::NdJson => self.next_ndjson(),
+        }
+    }
+
+    fn next_json(&mut self) -> LuaResult<Option<JsonValue>> {
+        // Skip any whitespace between values, unless we are already partway through one
+        if self.scan.scanned == 0 {
+            let remaining = &self.buffer[self.position..];
+            let Some(start) = remaining.iter().position(|b| !b.is_ascii_whitespace()) else {
+                self.position = self.buffer.len();
+                return Ok(None);
+            };
+            self.position += start;
+        }
+
+        // A value at the very end of the input may still continue in the next chunk,
+        // so we can only be sure it is complete once input has been finished
+        let remaining = &self.buffer[self.position..];
+        let len = match self.scan.scan(remaining) {
+            Some(len) => len,
+            None if self.finished => remaining.len(),
+            None => return Ok(None),
+        };
+        self.scan = JsonScan::default();
+
+        match serde_json::from_slice(&remaining[..len]) {
+            Ok(value) => {
+                self.position += len;
+                Ok(Some(value))
+            }
+            Err(e) => {
+                // There is no way to know where the next value starts after
+                // invalid input, so we discard everything that is buffered
+                self.position = self.buffer.len();
+                Err(LuaError::RuntimeError(format!(
+                    "Failed to decode json value - {e}"
+                )))
+            }
+        }
+    }
+
+    fn next_ndjson(&mut self) -> LuaResult<Option<JsonValue>> {
+        loop {
+            let remaining = &self.buffer[self.position..];
+            let line = match remaining.find_byte(b'\n') {
+                Some(end) => {
+                    self.position += end + 1;
+                    &remaining[..end]
+                }
+                None if self.finished && !remaining.is_empty() => {
+                    self.position = self.buffer.len();
+                    remaining
+                }
+                None => return Ok(None),
+            };
+            self.line += 1;
+
+            // Blank lines are not valid NDJSON, but are common enough
+            // in practice (trailing newlines, CRLF) that we skip them
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            return match serde_json::from_slice(line) {
+                Ok(value) => Ok(Some(value)),
+                Err(e) => Err(LuaError::RuntimeError(format!(
+                    "Failed to decode ndjson line {} - {e}",
+                    self.line
+                ))),
+            };
+        }
+    }
+
+    /**
+        Checks that no incomplete value is left in the decoder.
+
+        # Errors
+
+        Errors if the decoder has been finished and there is remaining input.
+    */
+    fn ensure_complete(&self) -> LuaResult<()> {
+        let remaining = &self.buffer[self.position..];
+        if self.finished && !remaining.trim().is_empty() {
+            return Err(LuaError::runtime(
+                "Failed to decode - input ended with an incomplete value",
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl LuaUserData for StreamDecoder {
+    fn add_fields<F: LuaUserDataFields<Self>>(fields: &mut F) {
+        fields.add_field_method_get("finished", |_, this| Ok(this.finished));
+    }
+
+    fn add_methods<M: LuaUserDataMethods<Self>>(methods: &mut M) {
+        methods.add_method_mut("push", |_, this, chunk: BString| this.push(&chunk));
+        methods.add_method_mut("finish", |_, this, ()| {
+            this.finish();
+            Ok(())
+        });
+        methods.add_method_mut("next", |lua, this, ()| {
+            // NOTE: Top-level null values can not be told apart from the end
+            // of available values in lua, so we skip them unless they are preserved
+            while let Some(value) = this.next_value()? {
+                if !value.is_null() || this.options.preserve_nulls {
+                    return Decoder::new(lua, &this.options)?.json_to_lua(value);
+                }
+            }
+            this.ensure_complete()?;
+            Ok(LuaValue::Nil)
+        });
+    }
+}
@@ -0,0 +1,684 @@
+use std::collections::HashSet;
+use std::ffi::c_void;
+use std::fmt;
+
+use mlua::prelude::*;
+use quick_xml::escape::{escape, partial_escape, resolve_predefined_entity};
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::name::ResolveResult;
+use quick_xml::reader::NsReader;
+
+use crate::markers::{Markers, TableKind};
+
+const XML_DECLARATION: &str = r#"<?xml version="1.0" encoding="UTF-8"?>"#;
+const INDENT: &str = "  ";
+
+/**
+    Options for decoding XML.
+*/
+#[derive(Debug, Clone, Default)]
+pub struct XmlDecodeOptions {
+    pub preserve_whitespace: bool,
+    pub preserve_cdata: bool,
+    pub preserve_comments: bool,
+}
+
+impl FromLua for XmlDecodeOptions {
+    fn from_lua(value: LuaValue, _: &Lua) -> LuaResult<Self> {
+        Ok(match value {
+            LuaValue::Nil => Self::default(),
+            LuaValue::Table(t) => Self {
+                preserve_whitespace: t
+                    .get::<Option<bool>>("preserveWhitespace")?
+                    .unwrap_or_default(),
+                preserve_cdata: t.get::<Option<bool>>("preserveCdata")?.unwrap_or_default(),
+                preserve_comments: t
+                    .get::<Option<bool>>("preserveComments")?
+                    .unwrap_or_default(),
+            },
+            _ => {
+                return Err(LuaError::FromLuaConversionError {
+                    from: value.type_name(),
+                    to: "XmlDecodeOptions".to_string(),
+                    message: Some(format!(
+                        "Invalid xml decoding options - expected table or nil, got {}",
+                        value.type_name()
+                    )),
+                });
+            }
+        })
+    }
+}
+
+/**
+    Options for encoding XML.
+*/
+#[derive(Debug, Clone, Default)]
+pub struct XmlEncodeOptions {
+    pub pretty: bool,
+    pub declaration: bool,
+}
+
+impl FromLua for XmlEncodeOptions {
+    fn from_lua(value: LuaValue, _: &Lua) -> LuaResult<Self> {
+        Ok(match value {
+            LuaValue::Nil => Self::default(),
+            LuaValue::Table(t) => Self {
+                pretty: t.get::<Option<bool>>("pretty")?.unwrap_or_default(),
+                declaration: t.get::<Option<bool>>("declaration")?.unwrap_or_default(),
+            },
+            _ => {
+                return Err(LuaError::FromLuaConversionError {
+                    from: value.type_name(),
+                    to: "XmlEncodeOptions".to_string(),
+                    message: Some(format!(
+                        "Invalid xml encoding options - expected table or nil, got {}",
+                        value.type_name()
+                    )),
+                });
+            }
+        })
+    }
+}
+
+/**
+    Builds the lua representation of an XML document from parsed events.
+
+    Elements are tables with a `name`, an optional `namespace`, and `attributes`
+    and `children` tables. Text is stored as plain strings in `children`, while
+    CDATA sections and comments are stored as `{ cdata = ... }` and
+    `{ comment = ... }` tables, but only if they are preserved.
+*/
+struct XmlDecoder<'a> {
+    lua: &'a Lua,
+    markers: Markers,
+    options: XmlDecodeOptions,
+    stack: Vec<(LuaTable, LuaTable)>,
+    root: Option<LuaTable>,
+    text: String,
+}
+
+impl<'a> XmlDecoder<'a> {
+    fn new(lua: &'a Lua, options: &XmlDecodeOptions) -> LuaResult<Self> {
+        Ok(Self {
+            lua,
+            markers: Markers::get(lua)?,
+            options: options.clone(),
+            stack: Vec::new(),
+            root: None,
+            text: String::new(),
+        })
+    }
+
+    fn open(&mut self, reader: &NsReader<&[u8]>, start: &BytesStart) -> LuaResult<()> {
+        self.flush_text()?;
+        if self.stack.is_empty() && self.root.is_some() {
+            return Err(LuaError::RuntimeError(
+                "documents can only have a single root element".to_string(),
+            ));
+        }
+
+        let decoder = reader.decoder();
+        let name = decoder
+            .decode(start.name().as_ref())
+            .into_lua_err()?
+            .into_owned();
+        let namespace = match reader.resolve_element(start.name()).0 {
+            ResolveResult::Bound(ns) if !ns.as_ref().is_empty() => {
+                Some(decoder.decode(ns.as_ref()).into_lua_err()?.into_owned())
+            }
+            ResolveResult::Bound(_) | ResolveResult::Unbound => None,
+            ResolveResult::Unknown(prefix) => {
+                return Err(LuaError::RuntimeError(format!(
+                    "undeclared namespace prefix '{}' in element '{name}'",
+                    String::from_utf8_lossy(&prefix)
+                )));
+            }
+        };
+
+        let attributes = self.lua.create_table()?;
+        let order = self.lua.create_table()?;
+        for attribute in start.attributes() {
+            let attribute = attribute.map_err(|e| LuaError::RuntimeError(e.to_string()))?;
+            let key = decoder.decode(attribute.key.as_ref()).into_lua_err()?;
+            if let (ResolveResult::Unknown(prefix), _) = reader.resolve_attribute(attribute.key) {
+                return Err(LuaError::RuntimeError(format!(
+                    "undeclared namespace prefix '{}' in attribute '{key}'",
+                    String::from_utf8_lossy(&prefix)
+                )));
+            }
+            let value = attribute
+                .decode_and_unescape_value(decoder)
+                .map_err(|e| LuaError::RuntimeError(e.to_string()))?;
+            attributes.raw_set(key.as_ref(), value.as_ref())?;
+            order.raw_push(key.as_ref())?;
+        }
+        self.markers.set_key_order(&attributes, order)?;
+        self.markers.mark(&attributes, TableKind::Object)?;
+
+        let children = self.lua.create_table()?;
+        self.markers.mark(&children, TableKind::Array)?;
+
+        let element = self.lua.create_table()?;
+        element.raw_set("name", name)?;
+        element.raw_set("namespace", namespace.as_deref())?;
+        element.raw_set("attributes", attributes)?;
+        element.raw_set("children", &children)?;
+
+        if let Some((_, parent)) = self.stack.last() {
+            parent.raw_push(&element)?;
+        } else {
+            self.root = Some(element.clone());
+        }
+        self.stack.push((element, children));
+        Ok(())
+    }
+
+    fn close(&mut self) -> LuaResult<()> {
+        self.flush_text()?;
+        self.stack.pop();
+        Ok(())
+    }
+
+    fn text(&mut self, text: &str) -> LuaResult<()> {
+        if self.stack.is_empty() && !is_blank(text) {
+            return Err(LuaError::RuntimeError(
+                "text is not allowed outside of the root element".to_string(),
+            ));
+        }
+        self.text.push_str(text);
+        Ok(())
+    }
+
+    fn cdata(&mut self, cdata: &str) -> LuaResult<()> {
+        if !self.options.preserve_cdata {
+            return self.text(cdata);
+        }
+        self.flush_text()?;
+        match self.stack.last() {
+            Some((_, children)) => {
+                let node = self.lua.create_table()?;
+                node.raw_set("cdata", cdata)?;
+                children.raw_push(node)
+            }
+            None => Err(LuaError::RuntimeError(
+                "cdata is not allowed outside of the root element".to_string(),
+            )),
+        }
+    }
+
+    fn comment(&mut self, comment: &str) -> LuaResult<()> {
+        // Comments outside of the root element have nowhere to go, so they are always skipped
+        if let (true, Some((_, children))) = (self.options.preserve_comments, self.stack.last()) {
+            let children = children.clone();
+            self.flush_text()?;
+            let node = self.lua.create_table()?;
+            node.raw_set("comment", comment)?;
+            children.raw_push(node)?;
+        }
+        Ok(())
+    }
+
+    fn flush_text(&mut self) -> LuaResult<()> {
+        if self.text.is_empty() {
+            return Ok(());
+        }
+        let text = std::mem::take(&mut self.text);
+        match self.stack.last() {
+            Some((_, children)) if self.options.preserve_whitespace || !is_blank(&text) => {
+                children.raw_push(text)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn finish(mut self) -> LuaResult<LuaTable> {
+        self.flush_text()?;
+        if let Some((element, _)) = self.stack.last() {
+            return Err(LuaError::RuntimeError(format!(
+                "element '{}' was never closed",
+                element.raw_get::<String>("name")?
+            )));
+        }
+        self.root.ok_or_else(|| {
+            LuaError::RuntimeError("document does not contain a root element".to_string())
+        })
+    }
+}
+
+fn is_blank(text: &str) -> bool {
+    text.bytes()
+        .all(|b| matches!(b, b' ' | b'\t' | b'\r' | b'\n'))
+}
+
+/**
+    Creates an error for a problem found while decoding, including the
+    line and column of the given byte position in the source document.
+*/
+fn decode_error(source: &str, position: u64, message: impl fmt::Display) -> LuaError {
+    let position = usize::try_from(position)
+        .unwrap_or(usize::MAX)
+        .min(source.len());
+    let before = &source.as_bytes()[..position];
+    let line = before.split(|&b| b == b'\n').count();
+    let column = position
+        - before
+            .iter()
+            .rposition(|&b| b == b'\n')
+            .map_or(0, |i| i + 1)
+        + 1;
+    LuaError::RuntimeError(format!(
+        "Failed to decode xml - {message} (line {line}, column {column})"
+    ))
+}
+
+/**
+    Decodes the given XML document into its root element.
+
+    Text nodes that only contain whitespace are removed unless whitespace
+    is preserved, and CDATA sections are merged into the surrounding text
+    unless they are preserved. The XML declaration, processing instructions,
+    and the document type definition are always skipped.
+
+    # Errors
+
+    Errors if the document is not valid UTF-8, is not well-formed,
+    uses undeclared namespace prefixes, or uses entities other than
+    the predefined XML entities and character references.
+*/
+pub fn decode(
+    lua: &Lua,
+    bytes: impl AsRef<[u8]>,
+    options: &XmlDecodeOptions,
+) -> LuaResult<LuaTable> {
+    let source = std::str::from_utf8(bytes.as_ref()).map_err(|e| {
+        LuaError::RuntimeError(format!(
+            "Failed to decode xml - document is not valid utf-8 ({e})"
+        ))
+    })?;
+
+    let mut reader = NsReader::from_str(source);
+    let mut decoder = XmlDecoder::new(lua, options)?;
+    loop {
+        let position = reader.buffer_position();
+        let event = match reader.read_event() {
+            Ok(event) => event,
+            Err(e) => return Err(decode_error(source, reader.error_position(), e)),
+        };
+        let result = match event {
+            Event::Start(start) => decoder.open(&reader, &start),
+            Event::Empty(start) => decoder.open(&reader, &start).and_then(|()| decoder.close()),
+            Event::End(_) => decoder.close(),
+            Event::Text(text) => text
+                .decode()
+                .into_lua_err()
+                .and_then(|text| decoder.text(&text)),
+            Event::GeneralRef(reference) => match reference.resolve_char_ref() {
+                Ok(Some(c)) => decoder.text(c.encode_utf8(&mut [0; 4])),
+                Ok(None) => {
+                    let name = reference.decode().into_lua_err()?;
+                    match resolve_predefined_entity(&name) {
+                        Some(resolved) => decoder.text(resolved),
+                        None => Err(LuaError::RuntimeError(format!("unknown entity '&{name};'"))),
+                    }
+                }
+                Err(e) => Err(LuaError::RuntimeError(e.to_string())),
+            },
+            Event::CData(cdata) => cdata
+                .decode()
+                .into_lua_err()
+                .and_then(|cdata| decoder.cdata(&cdata)),
+            Event::Comment(comment) => comment
+                .decode()
+                .into_lua_err()
+                .and_then(|comment| decoder.comment(&comment)),
+            Event::Decl(_) | Event::PI(_) | Event::DocType(_) => Ok(()),
+            Event::Eof => break,
+        };
+        result.map_err(|e| match e {
+            LuaError::RuntimeError(message) => decode_error(source, position, message),
+            e => e,
+        })?;
+    }
+
+    decoder.finish().map_err(|e| match e {
+        LuaError::RuntimeError(message) => {
+            LuaError::RuntimeError(format!("Failed to decode xml - {message}"))
+        }
+        e => e,
+    })
+}
+
+/**
+    A single child node of an element, read from its lua representation.
+*/
+enum XmlNode {
+    Element(LuaTable),
+    Text(String),
+    CData(String),
+    Comment(String),
+}
+
+impl XmlNode {
+    fn from_lua(lua: &Lua, value: LuaValue) -> LuaResult<Self> {
+        match value {
+            LuaValue::String(s) => Ok(Self::Text(s.to_str()?.to_string())),
+            LuaValue::Integer(_) | LuaValue::Number(_) => match lua.coerce_string(value)? {
+                Some(s) => Ok(Self::Text(s.to_str()?.to_string())),
+                None => Ok(Self::Text(String::new())),
+            },
+            LuaValue::Table(t) => {
+                if t.contains_key("name")? {
+                    Ok(Self::Element(t))
+                } else if let Some(cdata) = t.get::<Option<String>>("cdata")? {
+                    Ok(Self::CData(cdata))
+                } else if let Some(comment) = t.get::<Option<String>>("comment")? {
+                    Ok(Self::Comment(comment))
+                } else {
+                    Err(LuaError::RuntimeError(
+                        "Invalid xml node - expected an element, cdata or comment table"
+                            .to_string(),
+                    ))
+                }
+            }
+            value => Err(LuaError::RuntimeError(format!(
+                "Invalid xml node - expected table, string or number, got {}",
+                value.type_name()
+            ))),
+        }
+    }
+}
+
+/**
+    Writes the lua representation of an XML document as text.
+*/
+struct XmlEncoder<'a> {
+    lua: &'a Lua,
+    markers: Markers,
+    options: XmlEncodeOptions,
+    output: String,
+    visited: HashSet<*const c_void>,
+    bindings: Vec<(String, String)>,
+}
+
+impl XmlEncoder<'_> {
+    fn binding(&self, prefix: &str) -> Option<&str> {
+        match prefix {
+            "xml" => Some("http://www.w3.org/XML/1998/namespace"),
+            "xmlns" => Some("http://www.w3.org/2000/xmlns/"),
+            _ => self
+                .bindings
+                .iter()
+                .rev()
+                .find(|(p, _)| p == prefix)
+                .map(|(_, uri)| uri.as_str()),
+        }
+    }
+
+    fn attributes(&self, element: &LuaTable) -> LuaResult<Vec<(String, String)>> {
+        let Some(attributes) = element.get::<Option<LuaTable>>("attributes")? else {
+            return Ok(Vec::new());
+        };
+
+        let mut pairs = Vec::new();
+        for pair in attributes.pairs::<LuaValue, LuaValue>() {
+            let (key, value) = pair?;
+            let LuaValue::String(key) = key else {
+                return Err(LuaError::RuntimeError(format!(
+                    "Invalid xml attribute - expected string key, got {}",
+                    key.type_name()
+                )));
+            };
+            let key = key.to_str()?.to_string();
+            validate_name("attribute", &key)?;
+            let value = match value {
+                LuaValue::String(s) => s.to_str()?.to_string(),
+                LuaValue::Boolean(b) => b.to_string(),
+                LuaValue::Integer(_) | LuaValue::Number(_) => {
+                    match self.lua.coerce_string(value)? {
+                        Some(s) => s.to_str()?.to_string(),
+                        None => String::new(),
+                    }
+                }
+                value => {
+                    return Err(LuaError::RuntimeError(format!(
+                        "Invalid xml attribute '{key}' - expected string, number or boolean, got {}",
+                        value.type_name()
+                    )));
+                }
+            };
+            pairs.push((key, value));
+        }
+        pairs.sort();
+
+        self.markers
+            .restore_key_order(self.lua, &attributes, &mut pairs)?;
+
+        Ok(pairs)
+    }
+
+    /**
+        Adds the namespaces declared by the attributes of an element to the
+        current scope, and declares the namespace of the element itself if
+        it is not yet in scope.
+    */
+    fn declare_namespaces(
+        &mut self,
+        name: &str,
+        namespace: Option<String>,
+        attributes: &mut Vec<(String, String)>,
+    ) -> LuaResult<()> {
+        for (key, value) in attributes.iter() {
+            if key == "xmlns" {
+                self.bindings.push((String::new(), value.clone()));
+            } else if let Some(prefix) = key.strip_prefix("xmlns:") {
+                self.bindings.push((prefix.to_string(), value.clone()));
+            }
+        }
+
+        // Declare the namespace of the element if it is not already in scope
+        let prefix = name.split_once(':').map_or("", |(prefix, _)| prefix);
+        match (namespace, self.binding(prefix)) {
+            (Some(namespace), None) => {
+                let key = if prefix.is_empty() {
+                    "xmlns".to_string()
+                } else {
+                    format!("xmlns:{prefix}")
+                };
+                attributes.insert(0, (key, namespace.clone()));
+                self.bindings.push((prefix.to_string(), namespace));
+            }
+            (Some(namespace), Some(bound)) if namespace != bound => {
+                return Err(LuaError::RuntimeError(format!(
+                    "Invalid xml element '{name}' - namespace is '{namespace}', \
+                    but the prefix is already bound to '{bound}'"
+                )));
+            }
+            (None, None) if !prefix.is_empty() => {
+                return Err(LuaError::RuntimeError(format!(
+                    "Invalid xml element '{name}' - namespace prefix '{prefix}' is not declared"
+                )));
+            }
+            _ => {}
+        }
+        for (key, _) in attributes.iter() {
+            let Some((prefix, _)) = key.split_once(':') else {
+                continue;
+            };
+            if self.binding(prefix).is_none() {
+                return Err(LuaError::RuntimeError(format!(
+                    "Invalid xml attribute '{key}' - namespace prefix '{prefix}' is not declared"
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    fn element(&mut self, element: &LuaTable, depth: usize) -> LuaResult<()> {
+        let ptr = element.to_pointer();
+        if !self.visited.insert(ptr) {
+            return Err(LuaError::RuntimeError(
+                "Invalid xml element - elements can not contain themselves".to_string(),
+            ));
+        }
+        let scope = self.bindings.len();
+
+        let name = match element.get::<LuaValue>("name")? {
+            LuaValue::String(s) => s.to_str()?.to_string(),
+            value => {
+                return Err(LuaError::RuntimeError(format!(
+                    "Invalid xml element - expected string name, got {}",
+                    value.type_name()
+                )));
+            }
+        };
+        validate_name("element", &name)?;
+        let namespace = element.get::<Option<String>>("namespace")?;
+
+        let mut attributes = self.attributes(element)?;
+        self.declare_namespaces(&name, namespace, &mut attributes)?;
+
+        let mut children = Vec::new();
+        if let Some(table) = element.get::<Option<LuaTable>>("children")? {
+            for child in table.sequence_values::<LuaValue>() {
+                children.push(XmlNode::from_lua(self.lua, child?)?);
+            }
+        }
+
+        self.output.push('<');
+        self.output.push_str(&name);
+        for (key, value) in &attributes {
+            self.output.push(' ');
+            self.output.push_str(key);
+            self.output.push_str("=\"");
+            self.output.push_str(&escape_attribute(value));
+            self.output.push('"');
+        }
+
+        if children.is_empty() {
+            self.output.push_str("/>");
+        } else {
+            self.output.push('>');
+            // Indenting text would change it, so elements containing text are always written inline
+            let indent = self.options.pretty
+                && !children
+                    .iter()
+                    .any(|child| matches!(child, XmlNode::Text(_) | XmlNode::CData(_)));
+            for child in &children {
+                if indent {
+                    self.newline(depth + 1);
+                }
+                self.node(child, depth + 1)?;
+            }
+            if indent {
+                self.newline(depth);
+            }
+            self.output.push_str("</");
+            self.output.push_str(&name);
+            self.output.push('>');
+        }
+
+        self.bindings.truncate(scope);
+        self.visited.remove(&ptr);
+        Ok(())
+    }
+
+    fn node(&mut self, node: &XmlNode, depth: usize) -> LuaResult<()> {
+        match node {
+            XmlNode::Element(element) => self.element(element, depth)?,
+            XmlNode::Text(text) => self.output.push_str(&partial_escape(text.as_str())),
+            XmlNode::CData(cdata) => {
+                self.output.push_str("<![CDATA[");
+                // The closing sequence can not be escaped, so it gets split across sections
+                self.output
+                    .push_str(&cdata.replace("]]>", "]]]]><![CDATA[>"));
+                self.output.push_str("]]>");
+            }
+            XmlNode::Comment(comment) => {
+                if comment.contains("--") || comment.ends_with('-') {
+                    return Err(LuaError::RuntimeError(
+                        "Invalid xml comment - comments can not contain '--' or end with '-'"
+                            .to_string(),
+                    ));
+                }
+                self.output.push_str("<!--");
+                self.output.push_str(comment);
+                self.output.push_str("-->");
+            }
+        }
+        Ok(())
+    }
+
+    fn newline(&mut self, depth: usize) {
+        self.output.push('\n');
+        for _ in 0..depth {
+            self.output.push_str(INDENT);
+        }
+    }
+}
+
+fn validate_name(kind: &str, name: &str) -> LuaResult<()> {
+    let valid = !name.is_empty()
+        && !name.starts_with(|c: char| c.is_ascii_digit() || c == '-' || c == '.')
+        && !name.contains(|c: char| c.is_whitespace() || "<>&\"'=/!?;".contains(c));
+    if valid {
+        Ok(())
+    } else {
+        Err(LuaError::RuntimeError(format!(
+            "Invalid xml {kind} name '{name}'"
+        )))
+    }
+}
+
+/*
+    Escapes an attribute value, including whitespace characters
+    that would otherwise be normalized to spaces by XML parsers
+*/
+fn escape_attribute(value: &str) -> String {
+    escape(value)
+        .replace('\t', "&#9;")
+        .replace('\n', "&#10;")
+        .replace('\r', "&#13;")
+}
+
+/**
+    Encodes the given element, and all of its children, into an XML document.
+
+    Attributes are written in their original order for decoded elements,
+    and sorted by name otherwise. Any element with a namespace that is not
+    yet declared gets a new `xmlns` attribute for it.
+
+    # Errors
+
+    Errors if the element or any of its children are invalid, use
+    undeclared namespace prefixes, or if an element contains itself.
+*/
+pub fn encode(lua: &Lua, value: LuaValue, options: &XmlEncodeOptions) -> LuaResult<LuaString> {
+    let LuaValue::Table(root) = value else {
+        return Err(LuaError::RuntimeError(format!(
+            "Invalid xml element - expected table, got {}",
+            value.type_name()
+        )));
+    };
+
+    let mut encoder = XmlEncoder {
+        lua,
+        markers: Markers::get(lua)?,
+        options: options.clone(),
+        output: String::new(),
+        visited: HashSet::new(),
+        bindings: Vec::new(),
+    };
+    if options.declaration {
+        encoder.output.push_str(XML_DECLARATION);
+        if options.pretty {
+            encoder.output.push('\n');
+        }
+    }
+    encoder.element(&root, 0)?;
+    if options.pretty {
+        encoder.output.push('\n');
+    }
+
+    lua.create_string(encoder.output)
+}
@@ -44,6 +44,18 @@ pub fn inject_std(lua: Lua) -> LuaResult<()> {
     Ok(())
 }
 
+/**
+    Cleans up all standard libraries in the given Lua state / VM.
+
+    This should be called once the Lua state will no longer be used,
+    so that libraries can release resources such as temporary files.
+*/
+pub fn cleanup_std(lua: &Lua) {
+    for library in LuneStandardLibrary::ALL {
+        library.cleanup(lua);
+    }
+}
+
 /**
     Injects all extension libraries into the given Lua state / VM.
 
@@ -8,10 +8,12 @@ use mlua::prelude::*;
 #[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
 #[rustfmt::skip]
 pub enum LuneStandardLibrary {
+    #[cfg(feature = "archive")]  Archive,
     #[cfg(feature = "datetime")] DateTime,
     #[cfg(feature = "fs")]       Fs,
     #[cfg(feature = "luau")]     Luau,
     #[cfg(feature = "net")]      Net,
+    #[cfg(feature = "path")]     Path,
     #[cfg(feature = "task")]     Task,
     #[cfg(feature = "process")]  Process,
     #[cfg(feature = "regex")]    Regex,
@@ -26,10 +28,12 @@ impl LuneStandardLibrary {
     */
     #[rustfmt::skip]
     pub const ALL: &'static [Self] = &[
+        #[cfg(feature = "archive")]  Self::Archive,
         #[cfg(feature = "datetime")] Self::DateTime,
         #[cfg(feature = "fs")]       Self::Fs,
         #[cfg(feature = "luau")]     Self::Luau,
         #[cfg(feature = "net")]      Self::Net,
+        #[cfg(feature = "path")]     Self::Path,
         #[cfg(feature = "task")]     Self::Task,
         #[cfg(feature = "process")]  Self::Process,
         #[cfg(feature = "regex")]    Self::Regex,
@@ -46,10 +50,12 @@ impl LuneStandardLibrary {
     #[allow(unreachable_patterns)]
     pub fn name(&self) -> &'static str {
         match self {
+            #[cfg(feature = "archive")]  Self::Archive  => "archive",
             #[cfg(feature = "datetime")] Self::DateTime => "datetime",
             #[cfg(feature = "fs")]       Self::Fs       => "fs",
             #[cfg(feature = "luau")]     Self::Luau     => "luau",
             #[cfg(feature = "net")]      Self::Net      => "net",
+            #[cfg(feature = "path")]     Self::Path     => "path",
             #[cfg(feature = "task")]     Self::Task     => "task",
             #[cfg(feature = "process")]  Self::Process  => "process",
             #[cfg(feature = "regex")]    Self::Regex    => "regex",
@@ -69,10 +75,12 @@ impl LuneStandardLibrary {
     #[allow(unreachable_patterns)]
     pub fn typedefs(&self) -> String {
     	match self {
+            #[cfg(feature = "archive")]  Self::Archive  => lune_std_archive::typedefs(),
             #[cfg(feature = "datetime")] Self::DateTime => lune_std_datetime::typedefs(),
             #[cfg(feature = "fs")]       Self::Fs       => lune_std_fs::typedefs(),
             #[cfg(feature = "luau")]     Self::Luau     => lune_std_luau::typedefs(),
             #[cfg(feature = "net")]      Self::Net      => lune_std_net::typedefs(),
+            #[cfg(feature = "path")]     Self::Path     => lune_std_path::typedefs(),
             #[cfg(feature = "task")]     Self::Task     => lune_std_task::typedefs(),
             #[cfg(feature = "process")]  Self::Process  => lune_std_process::typedefs(),
             #[cfg(feature = "regex")]    Self::Regex    => lune_std_regex::typedefs(),
@@ -96,10 +104,12 @@ impl LuneStandardLibrary {
     pub fn module(&self, lua: Lua) -> LuaResult<LuaTable> {
         let mod_lua = lua.clone();
         let res: LuaResult<LuaTable> = match self {
+            #[cfg(feature = "archive")]  Self::Archive  => lune_std_archive::module(mod_lua),
             #[cfg(feature = "datetime")] Self::DateTime => lune_std_datetime::module(mod_lua),
             #[cfg(feature = "fs")]       Self::Fs       => lune_std_fs::module(mod_lua),
             #[cfg(feature = "luau")]     Self::Luau     => lune_std_luau::module(mod_lua),
             #[cfg(feature = "net")]      Self::Net      => lune_std_net::module(mod_lua),
+            #[cfg(feature = "path")]     Self::Path     => lune_std_path::module(mod_lua),
             #[cfg(feature = "task")]     Self::Task     => lune_std_task::module(mod_lua),
             #[cfg(feature = "process")]  Self::Process  => lune_std_process::module(mod_lua),
             #[cfg(feature = "regex")]    Self::Regex    => lune_std_regex::module(mod_lua),
@@ -117,6 +127,20 @@ impl LuneStandardLibrary {
             ))),
         }
     }
+
+    /**
+        Cleans up any resources the library holds in the given Lua state,
+        such as temporary files, which should not outlive the runtime.
+    */
+    #[rustfmt::skip]
+    #[allow(unreachable_patterns)]
+    pub fn cleanup(&self, lua: &Lua) {
+        match self {
+            #[cfg(feature = "fs")]       Self::Fs       => lune_std_fs::cleanup(lua),
+
+            _ => {}
+        }
+    }
 }
 
 impl FromStr for LuneStandardLibrary {
@@ -125,10 +149,12 @@ impl FromStr for LuneStandardLibrary {
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let low = s.trim().to_ascii_lowercase();
         Ok(match low.as_str() {
+            #[cfg(feature = "archive")]  "archive"  => Self::Archive,
             #[cfg(feature = "datetime")] "datetime" => Self::DateTime,
             #[cfg(feature = "fs")]       "fs"       => Self::Fs,
             #[cfg(feature = "luau")]     "luau"     => Self::Luau,
             #[cfg(feature = "net")]      "net"      => Self::Net,
+            #[cfg(feature = "path")]     "path"     => Self::Path,
             #[cfg(feature = "task")]     "task"     => Self::Task,
             #[cfg(feature = "process")]  "process"  => Self::Process,
             #[cfg(feature = "regex")]    "regex"    => Self::Regex,
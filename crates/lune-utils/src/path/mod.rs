@@ -5,7 +5,7 @@ pub mod constants;
 
 pub use self::std::{
     append_extension, clean_path, clean_path_and_make_absolute, get_current_dir, get_current_exe,
-    relative_path_normalize, relative_path_parent,
+    relative_path_between, relative_path_normalize, relative_path_parent,
 };
 
 pub use self::luau::{LuauFilePath, LuauModulePath};
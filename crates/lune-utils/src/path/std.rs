@@ -150,3 +150,49 @@ pub fn relative_path_parent(rel: &mut PathBuf) {
         rel.pop();
     }
 }
+
+/**
+    Creates a relative path that leads from the `base` directory to `path`.
+
+    Both paths are made absolute and cleaned before comparing them,
+    so relative paths are resolved against the current working directory.
+
+    Returns `None` if the paths do not share a common root, such as
+    when they are on different drives on Windows.
+*/
+#[must_use]
+pub fn relative_path_between(path: impl AsRef<Path>, base: impl AsRef<Path>) -> Option<PathBuf> {
+    let path = clean_path_and_make_absolute(path);
+    let base = clean_path_and_make_absolute(base);
+
+    let mut path_components = path.components().peekable();
+    let mut base_components = base.components().peekable();
+
+    // Skip the shared prefix of both paths, which must at least include the root
+    let mut shared_any = false;
+    while let (Some(a), Some(b)) = (path_components.peek(), base_components.peek()) {
+        if a != b {
+            break;
+        }
+        if !matches!(a, Component::Normal(..)) {
+            shared_any = true;
+        }
+        path_components.next();
+        base_components.next();
+    }
+    if !shared_any {
+        return None;
+    }
+
+    let mut rel = PathBuf::new();
+    for _ in base_components {
+        rel.push(Component::ParentDir);
+    }
+    rel.extend(path_components);
+
+    if rel.as_os_str().is_empty() {
+        rel.push(Component::CurDir);
+    }
+
+    Some(rel)
+}
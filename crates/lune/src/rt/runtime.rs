@@ -95,10 +95,12 @@ impl Runtime {
 
         // Inject all the globals that are enabled
         #[cfg(any(
+            feature = "std-archive",
             feature = "std-datetime",
             feature = "std-fs",
             feature = "std-luau",
             feature = "std-net",
+            feature = "std-path",
             feature = "std-process",
             feature = "std-regex",
             feature = "std-roblox",
@@ -117,10 +119,12 @@ impl Runtime {
         // _G table needs to be injected again after sandboxing,
         // otherwise it will be read-only and completely unusable
         #[cfg(any(
+            feature = "std-archive",
             feature = "std-datetime",
             feature = "std-fs",
             feature = "std-luau",
             feature = "std-net",
+            feature = "std-path",
             feature = "std-process",
             feature = "std-regex",
             feature = "std-roblox",
@@ -353,10 +357,12 @@ impl Runtime {
         // Inject all the standard libraries that are enabled - this needs to be done after
         // storing the args/env, since some standard libraries use those during initialization
         #[cfg(any(
+            feature = "std-archive",
             feature = "std-datetime",
             feature = "std-fs",
             feature = "std-luau",
             feature = "std-net",
+            feature = "std-path",
             feature = "std-process",
             feature = "std-regex",
             feature = "std-roblox",
@@ -400,6 +406,31 @@ impl Runtime {
     }
 }
 
+impl Drop for Runtime {
+    fn drop(&mut self) {
+        // Standard libraries may hold on to resources that should not outlive
+        // the runtime, and the Lua state itself may be kept alive by references
+        // elsewhere, so we need to explicitly let the libraries clean up here
+        #[cfg(any(
+            feature = "std-archive",
+            feature = "std-datetime",
+            feature = "std-fs",
+            feature = "std-luau",
+            feature = "std-net",
+            feature = "std-path",
+            feature = "std-process",
+            feature = "std-regex",
+            feature = "std-roblox",
+            feature = "std-serde",
+            feature = "std-stdio",
+            feature = "std-task",
+        ))]
+        {
+            lune_std::cleanup_std(&self.lua);
+        }
+    }
+}
+
 fn strip_shebang(mut contents: Vec<u8>) -> Vec<u8> {
     if contents.starts_with(b"#!") {
         if let Some(first_newline_idx) = contents
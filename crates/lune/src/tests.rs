@@ -45,10 +45,12 @@ macro_rules! create_tests {
 }
 
 #[cfg(any(
+    feature = "std-archive",
     feature = "std-datetime",
     feature = "std-fs",
     feature = "std-luau",
     feature = "std-net",
+    feature = "std-path",
     feature = "std-process",
     feature = "std-regex",
     feature = "std-roblox",
@@ -81,6 +83,12 @@ create_tests! {
     global_warn: "globals/warn",
 }
 
+#[cfg(feature = "std-archive")]
+create_tests! {
+    archive_create: "archive/create",
+    archive_extract: "archive/extract",
+}
+
 #[cfg(feature = "std-datetime")]
 create_tests! {
     datetime_format_local_time: "datetime/formatLocalTime",
@@ -100,10 +108,16 @@ create_tests! {
 #[cfg(feature = "std-fs")]
 create_tests! {
     fs_files: "fs/files",
+    fs_lock: "fs/lock",
     fs_copy: "fs/copy",
     fs_dirs: "fs/dirs",
     fs_metadata: "fs/metadata",
     fs_move: "fs/move",
+    fs_open: "fs/open",
+    fs_permissions: "fs/permissions",
+    fs_temp: "fs/temp",
+    fs_walk: "fs/walk",
+    fs_watch: "fs/watch",
 }
 
 #[cfg(feature = "std-luau")]
@@ -119,14 +133,22 @@ create_tests! {
     net_request_codes: "net/request/codes",
     net_request_compression: "net/request/compression",
     net_request_https: "net/request/https",
+    net_request_max_redirects: "net/request/max_redirects",
     net_request_methods: "net/request/methods",
+    net_request_proxy: "net/request/proxy",
     net_request_query: "net/request/query",
     net_request_redirect: "net/request/redirect",
+    net_request_stream: "net/request/stream",
+    net_request_timeout: "net/request/timeout",
+    net_request_tls: "net/request/tls",
 
     net_serve_addresses: "net/serve/addresses",
     net_serve_handles: "net/serve/handles",
+    net_serve_http2: "net/serve/http2",
     net_serve_non_blocking: "net/serve/non_blocking",
     net_serve_requests: "net/serve/requests",
+    net_serve_streaming: "net/serve/streaming",
+    net_serve_tls: "net/serve/tls",
     net_serve_websockets: "net/serve/websockets",
 
     net_socket_basic: "net/socket/basic",
@@ -141,6 +163,13 @@ create_tests! {
     net_url_decode: "net/url/decode",
 }
 
+#[cfg(feature = "std-path")]
+create_tests! {
+    path_components: "path/components",
+    path_join: "path/join",
+    path_normalize: "path/normalize",
+}
+
 #[cfg(feature = "std-process")]
 create_tests! {
     process_args: "process/args",
@@ -238,14 +267,39 @@ create_tests! {
 
 #[cfg(feature = "std-serde")]
 create_tests! {
+    serde_binary_text_base32: "serde/binary-text/base32",
+    serde_binary_text_base58: "serde/binary-text/base58",
+    serde_binary_text_base64: "serde/binary-text/base64",
+    serde_binary_text_hex: "serde/binary-text/hex",
     serde_compression_files: "serde/compression/files",
     serde_compression_roundtrip: "serde/compression/roundtrip",
+    serde_compression_stream: "serde/compression/stream",
+    serde_decoder_json: "serde/decoder/json",
+    serde_decoder_ndjson: "serde/decoder/ndjson",
     serde_json_decode: "serde/json/decode",
     serde_json_encode: "serde/json/encode",
+    serde_markers_null: "serde/markers/null",
+    serde_markers_order: "serde/markers/order",
+    serde_markers_tables: "serde/markers/tables",
+    serde_msgpack_roundtrip: "serde/msgpack/roundtrip",
+    serde_cbor_roundtrip: "serde/cbor/roundtrip",
+    serde_csv_decode: "serde/csv/decode",
+    serde_csv_encode: "serde/csv/encode",
+    serde_csv_reader: "serde/csv/reader",
     serde_toml_decode: "serde/toml/decode",
     serde_toml_encode: "serde/toml/encode",
+    serde_xml_cdata: "serde/xml/cdata",
+    serde_xml_decode: "serde/xml/decode",
+    serde_xml_encode: "serde/xml/encode",
+    serde_xml_namespaces: "serde/xml/namespaces",
     serde_hashing_hash: "serde/hashing/hash",
+    serde_hashing_hasher: "serde/hashing/hasher",
     serde_hashing_hmac: "serde/hashing/hmac",
+    serde_crypto_encrypt: "serde/crypto/encrypt",
+    serde_crypto_kdf: "serde/crypto/kdf",
+    serde_crypto_password: "serde/crypto/password",
+    serde_crypto_random: "serde/crypto/random",
+    serde_crypto_sign: "serde/crypto/sign",
 }
 
 #[cfg(feature = "std-stdio")]